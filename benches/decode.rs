@@ -0,0 +1,149 @@
+use criterion::{BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use json_decoder::{JSONDecoder, JSONEncoder, StreamingDecoder};
+use serde_json::{Value, json};
+use std::hint::black_box;
+
+// Shape of a generated payload
+#[derive(Clone, Copy)]
+struct Shape {
+    // Records in the root array
+    records: usize,
+    // How many records share each distinct record value
+    sharing: usize,
+    // Levels of objects nested in every record
+    depth: usize,
+}
+
+impl Shape {
+    const BASE: Shape = Shape {
+        records: 1000,
+        sharing: 1,
+        depth: 4,
+    };
+
+    // Encoded payload of `records` records, of which only every `sharing`-th
+    // is distinct, so the encoder stores the others as references to it
+    fn payload(self) -> Vec<u8> {
+        let distinct = (self.records / self.sharing).max(1);
+        let records = (0..self.records)
+            .map(|i| self.record(i % distinct))
+            .collect();
+        let value = json!({ "version": 1, "records": Value::Array(records) });
+
+        let mut payload = Vec::new();
+        JSONEncoder::from_value(&value)
+            .and_then(|encoder| encoder.write_to(&mut payload))
+            .expect("generated payloads encode");
+        payload
+    }
+
+    fn record(self, id: usize) -> Value {
+        (0..self.depth).fold(json!({ "id": id, "leaf": true }), |child, level| {
+            json!({
+                "id": id,
+                "level": level,
+                "name": format!("record {id} level {level}"),
+                "tags": ["a", "b", id % 7],
+                "child": child,
+            })
+        })
+    }
+}
+
+// Decodes into a tree and serializes it, or serializes while decoding
+fn bench_payload(c: &mut Criterion, group: &str, shapes: impl IntoIterator<Item = (usize, Shape)>) {
+    let mut group = c.benchmark_group(group);
+
+    for (parameter, shape) in shapes {
+        let payload = shape.payload();
+        // Sharing shrinks the payload but not the output, so measure the latter
+        let decoded = JSONDecoder::from_reader(&payload[..]).unwrap();
+        let output = serde_json::to_vec(decoded.decoded_data()).unwrap();
+        group.throughput(Throughput::Bytes(output.len() as u64));
+
+        group.bench_with_input(
+            BenchmarkId::new("tree", parameter),
+            &payload,
+            |b, payload| {
+                b.iter(|| {
+                    let decoder = JSONDecoder::from_reader(black_box(&payload[..])).unwrap();
+                    serde_json::to_vec(decoder.decoded_data()).unwrap()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("stream", parameter),
+            &payload,
+            |b, payload| {
+                b.iter(|| {
+                    let decoder = StreamingDecoder::from_reader(black_box(&payload[..])).unwrap();
+                    serde_json::to_vec(&decoder).unwrap()
+                })
+            },
+        );
+    }
+
+    group.finish();
+}
+
+fn entries(c: &mut Criterion) {
+    let shapes = [100, 1000, 10_000].map(|records| {
+        (
+            records,
+            Shape {
+                records,
+                ..Shape::BASE
+            },
+        )
+    });
+    bench_payload(c, "entries", shapes);
+}
+
+fn sharing(c: &mut Criterion) {
+    let shapes = [1, 10, 100].map(|sharing| {
+        (
+            sharing,
+            Shape {
+                sharing,
+                ..Shape::BASE
+            },
+        )
+    });
+    bench_payload(c, "sharing", shapes);
+}
+
+fn depth(c: &mut Criterion) {
+    let shapes = [1, 8, 64].map(|depth| {
+        let records = Shape::BASE.records * Shape::BASE.depth / depth;
+        (
+            depth,
+            Shape {
+                records,
+                depth,
+                ..Shape::BASE
+            },
+        )
+    });
+    bench_payload(c, "depth", shapes);
+}
+
+// Long strings, each an entry of its own, which decoding moves into the tree
+fn strings(c: &mut Criterion) {
+    let value: Value = (0..1000)
+        .map(|i| json!({ "id": i, "text": format!("{i:x>16384}") }))
+        .collect();
+    let mut payload = Vec::new();
+    JSONEncoder::from_value(&value)
+        .and_then(|encoder| encoder.write_to(&mut payload))
+        .expect("generated payloads encode");
+
+    let mut group = c.benchmark_group("strings");
+    group.throughput(Throughput::Bytes(payload.len() as u64));
+    group.bench_function("tree", |b| {
+        b.iter(|| JSONDecoder::from_slice(black_box(&payload)).unwrap())
+    });
+    group.finish();
+}
+
+criterion_group!(benches, entries, sharing, depth, strings);
+criterion_main!(benches);
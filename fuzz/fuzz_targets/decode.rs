@@ -0,0 +1,28 @@
+#![no_main]
+
+use json_decoder::{DecodeOptions, JSONDecoder, Placeholder, StreamingDecoder};
+use libfuzzer_sys::fuzz_target;
+
+// The first byte picks the options, the rest is the payload
+fuzz_target!(|data: &[u8]| {
+    let Some((&flags, payload)) = data.split_first() else {
+        return;
+    };
+
+    let options = DecodeOptions {
+        relaxed: flags & 1 != 0,
+        strict: flags & 2 != 0,
+        lenient: (flags & 4 != 0).then_some(Placeholder::Marker),
+        ..DecodeOptions::default()
+    };
+
+    let tree = JSONDecoder::from_reader_with_options(payload, options.clone())
+        .map(|decoder| serde_json::to_vec(decoder.decoded_data()).unwrap());
+    let stream = StreamingDecoder::from_reader_with_options(payload, options.clone())
+        .map(|decoder| serde_json::to_vec(&decoder));
+
+    // Whenever both decoders succeed they must resolve references the same way
+    if let (Ok(tree), Ok(Ok(stream))) = (tree, stream) {
+        assert!(tree == stream, "streamed output differs from the tree");
+    }
+});
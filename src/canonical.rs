@@ -0,0 +1,63 @@
+use serde_json::Value;
+use std::io::{self, Write};
+
+/// Writes `value` as canonical JSON following RFC 8785 (JCS).
+///
+/// Object members are sorted by the UTF-16 code units of their keys, numbers
+/// use the ECMAScript number-to-string conversion and no insignificant
+/// whitespace is emitted, so equal values always produce identical bytes.
+pub fn write_canonical<W: Write>(value: &Value, mut writer: W) -> io::Result<()> {
+    write_value(value, &mut writer)
+}
+
+fn write_value<W: Write>(value: &Value, writer: &mut W) -> io::Result<()> {
+    match value {
+        Value::Null | Value::Bool(_) | Value::String(_) => {
+            serde_json::to_writer(&mut *writer, value).map_err(io::Error::from)
+        }
+        Value::Number(n) => {
+            // Every JSON number is an IEEE 754 double in JCS
+            let f = n.as_f64().unwrap_or(f64::NAN);
+
+            if !f.is_finite() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("number {n} is not representable in canonical JSON"),
+                ));
+            }
+
+            writer.write_all(ryu_js::Buffer::new().format_finite(f).as_bytes())
+        }
+        Value::Array(arr) => {
+            writer.write_all(b"[")?;
+
+            for (i, item) in arr.iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+
+                write_value(item, writer)?;
+            }
+
+            writer.write_all(b"]")
+        }
+        Value::Object(obj) => {
+            let mut members: Vec<(&String, &Value)> = obj.iter().collect();
+            members.sort_by(|(a, _), (b, _)| a.encode_utf16().cmp(b.encode_utf16()));
+
+            writer.write_all(b"{")?;
+
+            for (i, (key, item)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    writer.write_all(b",")?;
+                }
+
+                serde_json::to_writer(&mut *writer, key).map_err(io::Error::from)?;
+                writer.write_all(b":")?;
+                write_value(item, writer)?;
+            }
+
+            writer.write_all(b"}")
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use anyhow::{Context, Result};
+use base64::{Engine, engine::general_purpose::STANDARD};
+use json_decoder::{PathSegment, format_path};
+use ratatui::{
+    DefaultTerminal, Frame,
+    crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState, Paragraph},
+};
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    io::{self, Write},
+};
+
+// Longest scalar preview shown next to a key
+const MAX_PREVIEW: usize = 200;
+
+const HELP: &str =
+    "j/k move  l/h expand/collapse  space toggle  / search  n next  y copy path  q quit";
+
+/// Opens an interactive tree viewer over `value` until the user quits.
+pub fn browse(value: &Value) -> Result<()> {
+    let mut terminal = ratatui::try_init().with_context(|| "Failed to start terminal UI")?;
+    let result = Browser::new(value).run(&mut terminal);
+    ratatui::restore();
+
+    result
+}
+
+// One visible line of the tree
+struct Row<'a> {
+    path: Vec<PathSegment>,
+    value: &'a Value,
+}
+
+enum Mode {
+    Normal,
+    Search(String),
+}
+
+struct Browser<'a> {
+    root: &'a Value,
+    expanded: HashSet<Vec<PathSegment>>,
+    rows: Vec<Row<'a>>,
+    state: ListState,
+    mode: Mode,
+    last_search: Option<String>,
+    message: Option<String>,
+}
+
+impl<'a> Browser<'a> {
+    fn new(root: &'a Value) -> Self {
+        let mut browser = Browser {
+            root,
+            expanded: HashSet::from([Vec::new()]),
+            rows: Vec::new(),
+            state: ListState::default().with_selected(Some(0)),
+            mode: Mode::Normal,
+            last_search: None,
+            message: None,
+        };
+        browser.rebuild();
+
+        browser
+    }
+
+    fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        loop {
+            terminal
+                .draw(|frame| self.draw(frame))
+                .with_context(|| "Failed to draw terminal UI")?;
+
+            let Event::Key(key) = event::read().with_context(|| "Failed to read terminal input")?
+            else {
+                continue;
+            };
+
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            let quit = match &mut self.mode {
+                Mode::Normal => self.on_key(key),
+                Mode::Search(query) => {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let query = std::mem::take(query);
+                            self.mode = Mode::Normal;
+                            self.search(query);
+                        }
+                        KeyCode::Esc => self.mode = Mode::Normal,
+                        KeyCode::Backspace => {
+                            query.pop();
+                        }
+                        KeyCode::Char(c) => query.push(c),
+                        _ => {}
+                    }
+
+                    false
+                }
+            };
+
+            if quit {
+                return Ok(());
+            }
+        }
+    }
+
+    // Returns whether the browser should quit
+    fn on_key(&mut self, key: KeyEvent) -> bool {
+        self.message = None;
+
+        let selected = self.state.selected().unwrap_or(0);
+        let page = 20;
+
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return true,
+            KeyCode::Down | KeyCode::Char('j') => self.select(selected.saturating_add(1)),
+            KeyCode::Up | KeyCode::Char('k') => self.select(selected.saturating_sub(1)),
+            KeyCode::PageDown => self.select(selected.saturating_add(page)),
+            KeyCode::PageUp => self.select(selected.saturating_sub(page)),
+            KeyCode::Home | KeyCode::Char('g') => self.select(0),
+            KeyCode::End | KeyCode::Char('G') => self.select(usize::MAX),
+            KeyCode::Right | KeyCode::Char('l') | KeyCode::Enter => self.expand(),
+            KeyCode::Left | KeyCode::Char('h') => self.collapse(),
+            KeyCode::Char(' ') => self.toggle(),
+            KeyCode::Char('/') => self.mode = Mode::Search(String::new()),
+            KeyCode::Char('n') => {
+                if let Some(query) = self.last_search.clone() {
+                    self.search(query);
+                }
+            }
+            KeyCode::Char('y') => self.copy_path(),
+            _ => {}
+        }
+
+        false
+    }
+
+    fn current(&self) -> Option<&Row<'a>> {
+        self.rows.get(self.state.selected().unwrap_or(0))
+    }
+
+    fn select(&mut self, index: usize) {
+        let last = self.rows.len().saturating_sub(1);
+        self.state.select(Some(index.min(last)));
+    }
+
+    fn select_path(&mut self, path: &[PathSegment]) {
+        if let Some(index) = self.rows.iter().position(|row| row.path == path) {
+            self.state.select(Some(index));
+        }
+    }
+
+    fn expand(&mut self) {
+        let Some(row) = self.current() else {
+            return;
+        };
+
+        if !is_container(row.value) {
+            return;
+        }
+
+        let path = row.path.clone();
+
+        match self.expanded.contains(&path) {
+            // Already open: step into the first child
+            true => self.select(self.state.selected().unwrap_or(0) + 1),
+            false => {
+                self.expanded.insert(path);
+                self.rebuild();
+            }
+        }
+    }
+
+    fn collapse(&mut self) {
+        let Some(row) = self.current() else {
+            return;
+        };
+
+        let path = row.path.clone();
+
+        match self.expanded.contains(&path) && is_container(row.value) {
+            true => {
+                self.expanded.remove(&path);
+                self.rebuild();
+            }
+            // Already closed: step out to the parent
+            false => {
+                if let Some((_, parent)) = path.split_last() {
+                    let parent = parent.to_vec();
+                    self.select_path(&parent);
+                }
+            }
+        }
+    }
+
+    fn toggle(&mut self) {
+        let Some(row) = self.current() else {
+            return;
+        };
+
+        if !is_container(row.value) {
+            return;
+        }
+
+        let path = row.path.clone();
+
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+
+        self.rebuild();
+    }
+
+    // Finds the next node after the selection whose key or scalar value
+    // contains `query`, expanding its ancestors
+    fn search(&mut self, query: String) {
+        let needle = query.to_lowercase();
+        let start = self
+            .current()
+            .map(|row| row.path.clone())
+            .unwrap_or_default();
+        let nodes = all_nodes(self.root);
+        let position = nodes
+            .iter()
+            .position(|(path, _)| *path == start)
+            .unwrap_or(0);
+
+        let found = nodes
+            .iter()
+            .skip(position + 1)
+            .chain(nodes.iter().take(position + 1))
+            .find(|(path, value)| matches(path, value, &needle))
+            .map(|(path, _)| path.clone());
+
+        match found {
+            Some(path) => {
+                for end in 0..path.len() {
+                    self.expanded.insert(path[..end].to_vec());
+                }
+
+                self.rebuild();
+                self.select_path(&path);
+            }
+            None => self.message = Some(format!("No match for {query:?}")),
+        }
+
+        self.last_search = Some(query);
+    }
+
+    // Copies through the OSC 52 escape sequence, which also works over SSH
+    fn copy_path(&mut self) {
+        let Some(row) = self.current() else {
+            return;
+        };
+
+        let path = format_path(&row.path);
+        let mut stdout = io::stdout();
+        let copied = write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(&path))
+            .and_then(|()| stdout.flush());
+
+        self.message = Some(match copied {
+            Ok(()) => format!("Copied {path}"),
+            Err(e) => format!("Failed to copy path: {e}"),
+        });
+    }
+
+    fn rebuild(&mut self) {
+        let mut rows = Vec::new();
+        let mut stack = vec![(Vec::new(), self.root)];
+
+        while let Some((path, value)) = stack.pop() {
+            if self.expanded.contains(&path) {
+                // Pushed in reverse so children come out in order
+                let children = children(value);
+                for (segment, child) in children.into_iter().rev() {
+                    let mut child_path = path.clone();
+                    child_path.push(segment);
+                    stack.push((child_path, child));
+                }
+            }
+
+            rows.push(Row { path, value });
+        }
+
+        self.rows = rows;
+        self.select(self.state.selected().unwrap_or(0));
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [tree, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| ListItem::new(self.line(row)))
+            .collect();
+        let list = List::new(items).highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree, &mut self.state);
+
+        let status_text = match (&self.mode, &self.message, self.current()) {
+            (Mode::Search(query), _, _) => format!("/{query}"),
+            (Mode::Normal, Some(message), _) => message.clone(),
+            (Mode::Normal, None, Some(row)) => format!("{}    {HELP}", format_path(&row.path)),
+            (Mode::Normal, None, None) => String::from(HELP),
+        };
+        frame.render_widget(
+            Paragraph::new(status_text).style(Style::new().add_modifier(Modifier::REVERSED)),
+            status,
+        );
+    }
+
+    fn line(&self, row: &Row) -> Line<'static> {
+        let indent = "  ".repeat(row.path.len());
+        let marker = match (is_container(row.value), self.expanded.contains(&row.path)) {
+            (true, true) => "▾ ",
+            (true, false) => "▸ ",
+            (false, _) => "  ",
+        };
+        let label = match row.path.last() {
+            Some(PathSegment::Key(key)) => format!("{}: ", Value::from(key.as_str())),
+            Some(PathSegment::Index(index)) => format!("[{index}]: "),
+            None => String::from("$: "),
+        };
+
+        let (summary, color) = match row.value {
+            Value::Object(obj) => (format!("{{}} {} keys", obj.len()), Color::Cyan),
+            Value::Array(arr) => (format!("[] {} items", arr.len()), Color::Cyan),
+            Value::String(_) => (preview(row.value), Color::Green),
+            Value::Number(_) => (preview(row.value), Color::Yellow),
+            Value::Bool(_) | Value::Null => (preview(row.value), Color::Magenta),
+        };
+
+        Line::from(vec![
+            Span::raw(indent),
+            Span::raw(marker),
+            Span::styled(label, Style::new().add_modifier(Modifier::BOLD)),
+            Span::styled(summary, Style::new().fg(color)),
+        ])
+    }
+}
+
+fn is_container(value: &Value) -> bool {
+    value.is_array() || value.is_object()
+}
+
+fn children(value: &Value) -> Vec<(PathSegment, &Value)> {
+    match value {
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (PathSegment::Index(i), item))
+            .collect(),
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(key, item)| (PathSegment::Key(key.clone()), item))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+// Every node in display order, regardless of what is expanded
+fn all_nodes(root: &Value) -> Vec<(Vec<PathSegment>, &Value)> {
+    let mut nodes = Vec::new();
+    let mut stack = vec![(Vec::new(), root)];
+
+    while let Some((path, value)) = stack.pop() {
+        for (segment, child) in children(value).into_iter().rev() {
+            let mut child_path = path.clone();
+            child_path.push(segment);
+            stack.push((child_path, child));
+        }
+
+        nodes.push((path, value));
+    }
+
+    nodes
+}
+
+fn matches(path: &[PathSegment], value: &Value, needle: &str) -> bool {
+    let key_matches = match path.last() {
+        Some(PathSegment::Key(key)) => key.to_lowercase().contains(needle),
+        _ => false,
+    };
+
+    key_matches
+        || match value {
+            Value::String(s) => s.to_lowercase().contains(needle),
+            Value::Number(n) => n.to_string().contains(needle),
+            _ => false,
+        }
+}
+
+fn preview(value: &Value) -> String {
+    let text = value.to_string();
+
+    match text.char_indices().nth(MAX_PREVIEW) {
+        Some((end, _)) => format!("{}…", &text[..end]),
+        None => text,
+    }
+}
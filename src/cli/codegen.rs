@@ -0,0 +1,337 @@
+use super::{
+    diagnostic::WriteFailure,
+    schema::{ObjectShape, Shape},
+    transform::KeyCase,
+};
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{collections::HashSet, fmt::Write as _, io::Write};
+
+const DERIVES: &str = "#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]";
+
+// Names of the generated code that types must not shadow
+const TAKEN: &[&str] = &[
+    "Deserialize",
+    "Option",
+    "Self",
+    "Serialize",
+    "String",
+    "Value",
+    "Vec",
+];
+
+/// Writes Rust type definitions, with serde derives, that `value` and values
+/// of the same shape deserialize into. The type of the root is named `name`.
+///
+/// Objects become structs, with optional fields for the keys missing from
+/// some objects, arrays become `Vec`s of one merged element type, and places
+/// holding several types become untagged enums.
+pub fn write_rust(value: &Value, name: &str, writer: &mut dyn Write) -> Result<()> {
+    let mut generator = Generator {
+        definitions: Vec::new(),
+        names: TAKEN.iter().map(|name| name.to_string()).collect(),
+    };
+    let shape = Shape::of(value);
+
+    // Roots other than objects and mixed values get an alias
+    let root = generator.type_of(&shape, name);
+    if root != type_name(name) {
+        let alias = generator.unique(&type_name(name));
+        generator
+            .definitions
+            .insert(0, format!("pub type {alias} = {root};\n"));
+    }
+
+    // An alias alone derives nothing
+    let mut code = match generator.definitions.len() > 1 || root == type_name(name) {
+        true => String::from("use serde::{Deserialize, Serialize};\n\n"),
+        false => String::new(),
+    };
+    code.push_str(&generator.definitions.join("\n"));
+
+    writer
+        .write_all(code.as_bytes())
+        .context(WriteFailure("Failed to write type definitions"))
+}
+
+struct Generator {
+    // Structs and enums in the order their first use was found
+    definitions: Vec<String>,
+    names: HashSet<String>,
+}
+
+impl Generator {
+    // The Rust type of the values of `shape`, defining the structs and enums
+    // it needs, named after `hint`
+    fn type_of(&mut self, shape: &Shape, hint: &str) -> String {
+        let kinds = [
+            shape.boolean,
+            shape.integer || shape.number,
+            shape.string,
+            shape.array.is_some(),
+            shape.object.is_some(),
+        ]
+        .into_iter()
+        .filter(|&found| found)
+        .count();
+
+        let inner = match kinds {
+            0 => "serde_json::Value".to_string(),
+            1 => self.single_type(shape, hint),
+            _ => self.define_enum(shape, hint),
+        };
+
+        match shape.null && kinds > 0 {
+            true => format!("Option<{inner}>"),
+            false => inner,
+        }
+    }
+
+    fn single_type(&mut self, shape: &Shape, hint: &str) -> String {
+        if let Some(object) = &shape.object {
+            return self.define_struct(object, hint);
+        }
+
+        if let Some(array) = &shape.array {
+            return match &array.items {
+                Some(items) => format!("Vec<{}>", self.type_of(items, &singular(hint))),
+                None => "Vec<serde_json::Value>".to_string(),
+            };
+        }
+
+        match shape {
+            Shape { boolean: true, .. } => "bool",
+            Shape { number: true, .. } => number_type(shape),
+            Shape { integer: true, .. } => integer_type(shape),
+            _ => "String",
+        }
+        .to_string()
+    }
+
+    fn define_struct(&mut self, object: &ObjectShape, hint: &str) -> String {
+        let name = self.unique(&type_name(hint));
+        let slot = self.reserve();
+        let mut fields = HashSet::new();
+        let mut definition = format!("{DERIVES}\npub struct {name} {{\n");
+
+        for property in &object.properties {
+            let mut field = field_name(&property.key);
+            while !fields.insert(field.clone()) {
+                field.push('_');
+            }
+
+            let mut ty = self.type_of(&property.shape, &property.key);
+            let mut attributes = Vec::new();
+
+            if field.trim_start_matches("r#") != property.key {
+                attributes.push(format!("rename = {:?}", property.key));
+            }
+
+            // Keys missing from some objects are left out again when written
+            if !object.is_required(property) {
+                if !ty.starts_with("Option<") {
+                    ty = format!("Option<{ty}>");
+                }
+                attributes.push("skip_serializing_if = \"Option::is_none\"".to_string());
+            }
+
+            if !attributes.is_empty() {
+                let _ = writeln!(definition, "    #[serde({})]", attributes.join(", "));
+            }
+            let _ = writeln!(definition, "    pub {field}: {ty},");
+        }
+
+        definition.push_str("}\n");
+        self.definitions[slot] = definition;
+        name
+    }
+
+    // Variants are tried in order, so integers are read before floats
+    fn define_enum(&mut self, shape: &Shape, hint: &str) -> String {
+        let name = self.unique(&type_name(hint));
+        let slot = self.reserve();
+        let mut variants = Vec::new();
+
+        if shape.boolean {
+            variants.push(("Bool", "bool".to_string()));
+        }
+        // Integers need a variant of their own unless that of floats holds them
+        if shape.integer && (!shape.number || number_type(shape) == "f64") {
+            variants.push(("Integer", integer_type(shape).to_string()));
+        }
+        if shape.number {
+            variants.push(("Number", number_type(shape).to_string()));
+        }
+        if shape.string {
+            variants.push(("String", "String".to_string()));
+        }
+        if let Some(array) = &shape.array {
+            let ty = match &array.items {
+                Some(items) => format!("Vec<{}>", self.type_of(items, &singular(hint))),
+                None => "Vec<serde_json::Value>".to_string(),
+            };
+            variants.push(("Array", ty));
+        }
+        if let Some(object) = &shape.object {
+            variants.push((
+                "Object",
+                self.define_struct(object, &format!("{hint} object")),
+            ));
+        }
+
+        let mut definition = format!("{DERIVES}\n#[serde(untagged)]\npub enum {name} {{\n");
+        for (variant, ty) in variants {
+            let _ = writeln!(definition, "    {variant}({ty}),");
+        }
+        definition.push_str("}\n");
+
+        self.definitions[slot] = definition;
+        name
+    }
+
+    // Keeps the place of a definition before those of the types it uses
+    fn reserve(&mut self) -> usize {
+        self.definitions.push(String::new());
+        self.definitions.len() - 1
+    }
+
+    fn unique(&mut self, name: &str) -> String {
+        let mut unique = name.to_string();
+        let mut n = 1;
+
+        while !self.names.insert(unique.clone()) {
+            n += 1;
+            unique = format!("{name}{n}");
+        }
+
+        unique
+    }
+}
+
+// The narrowest type holding every integer of `shape`; `serde_json::Number`
+// holds those beyond 64 bits with its `arbitrary_precision` feature only
+fn integer_type(shape: &Shape) -> &'static str {
+    match shape {
+        Shape {
+            beyond_64_bits: true,
+            ..
+        }
+        | Shape {
+            negative: true,
+            above_i64: true,
+            ..
+        } => "serde_json::Number",
+        Shape {
+            above_i64: true, ..
+        } => "u64",
+        _ => "i64",
+    }
+}
+
+// Integers that `f64` can not hold keep floats found with them exact too
+fn number_type(shape: &Shape) -> &'static str {
+    match shape.integer && integer_type(shape) != "i64" {
+        true => "serde_json::Number",
+        false => "f64",
+    }
+}
+
+// `user id` to `UserId`, a valid identifier whatever the key
+fn type_name(hint: &str) -> String {
+    let camel = identifier(&KeyCase::Camel.convert(hint)).replace('_', "");
+    let mut chars = camel.chars();
+
+    match chars.next() {
+        Some(first) if first.is_ascii_alphabetic() => {
+            first.to_ascii_uppercase().to_string() + chars.as_str()
+        }
+        _ => format!("Type{camel}"),
+    }
+}
+
+fn field_name(key: &str) -> String {
+    let snake = identifier(&KeyCase::Snake.convert(key)).to_lowercase();
+
+    match snake.as_str() {
+        "" | "_" => "field".to_string(),
+        "self" | "super" | "crate" => format!("{snake}_"),
+        _ if snake.starts_with(|c: char| c.is_ascii_digit()) => format!("_{snake}"),
+        _ if is_keyword(&snake) => format!("r#{snake}"),
+        _ => snake,
+    }
+}
+
+// Any character that can not be part of an identifier becomes `_`
+fn identifier(s: &str) -> String {
+    s.chars()
+        .map(|c| match c.is_ascii_alphanumeric() {
+            true => c,
+            false => '_',
+        })
+        .collect()
+}
+
+// Element types are named after the singular of their array
+fn singular(hint: &str) -> String {
+    if let Some(stem) = hint.strip_suffix("ies") {
+        return format!("{stem}y");
+    }
+
+    match hint.strip_suffix('s') {
+        Some(stem) if !stem.is_empty() && !stem.ends_with(['s', 'u', 'i']) => stem.to_string(),
+        _ => format!("{hint} item"),
+    }
+}
+
+fn is_keyword(s: &str) -> bool {
+    matches!(
+        s,
+        "as" | "async"
+            | "await"
+            | "break"
+            | "const"
+            | "continue"
+            | "dyn"
+            | "else"
+            | "enum"
+            | "extern"
+            | "false"
+            | "fn"
+            | "for"
+            | "gen"
+            | "if"
+            | "impl"
+            | "in"
+            | "let"
+            | "loop"
+            | "match"
+            | "mod"
+            | "move"
+            | "mut"
+            | "pub"
+            | "ref"
+            | "return"
+            | "static"
+            | "struct"
+            | "trait"
+            | "true"
+            | "try"
+            | "type"
+            | "unsafe"
+            | "use"
+            | "where"
+            | "while"
+            | "yield"
+            | "abstract"
+            | "become"
+            | "box"
+            | "do"
+            | "final"
+            | "macro"
+            | "override"
+            | "priv"
+            | "typeof"
+            | "unsized"
+            | "virtual"
+    )
+}
@@ -0,0 +1,178 @@
+use clap::ValueEnum;
+use serde_json::ser::{CharEscape, Formatter};
+use std::{env, io};
+
+// SGR sequences of each part of a JSON document
+const KEY: &[u8] = b"\x1b[1;34m";
+const STRING: &[u8] = b"\x1b[32m";
+const NUMBER: &[u8] = b"\x1b[36m";
+const LITERAL: &[u8] = b"\x1b[33m";
+const RESET: &[u8] = b"\x1b[0m";
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// Color output written to a terminal, unless `NO_COLOR` is set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether to color output, `terminal` telling whether it is written to a
+    /// terminal.
+    pub fn enabled(self, terminal: bool) -> bool {
+        match self {
+            ColorChoice::Auto => terminal && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty()),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        }
+    }
+}
+
+/// Colors the keys, strings, numbers and literals a formatter writes.
+pub struct ColorFormatter<F> {
+    inner: F,
+    // Whether an object key is being written, whatever its type
+    key: bool,
+}
+
+impl<F> ColorFormatter<F> {
+    pub fn new(inner: F) -> Self {
+        ColorFormatter { inner, key: false }
+    }
+
+    fn paint<W>(
+        &mut self,
+        writer: &mut W,
+        color: &[u8],
+        write: impl FnOnce(&mut F, &mut W) -> io::Result<()>,
+    ) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        // Keys are colored as a whole, numbers being written as strings
+        if self.key {
+            return write(&mut self.inner, writer);
+        }
+
+        writer.write_all(color)?;
+        write(&mut self.inner, writer)?;
+        writer.write_all(RESET)
+    }
+}
+
+macro_rules! numbers {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method<W>(&mut self, writer: &mut W, value: $ty) -> io::Result<()>
+            where
+                W: ?Sized + io::Write,
+            {
+                self.paint(writer, NUMBER, |inner, writer| inner.$method(writer, value))
+            }
+        )*
+    };
+}
+
+macro_rules! forwarded {
+    ($($method:ident $(($arg:ident: $ty:ty))?),* $(,)?) => {
+        $(
+            fn $method<W>(&mut self, writer: &mut W $(, $arg: $ty)?) -> io::Result<()>
+            where
+                W: ?Sized + io::Write,
+            {
+                self.inner.$method(writer $(, $arg)?)
+            }
+        )*
+    };
+}
+
+impl<F: Formatter> Formatter for ColorFormatter<F> {
+    numbers! {
+        write_i8: i8,
+        write_i16: i16,
+        write_i32: i32,
+        write_i64: i64,
+        write_i128: i128,
+        write_u8: u8,
+        write_u16: u16,
+        write_u32: u32,
+        write_u64: u64,
+        write_u128: u128,
+        write_f32: f32,
+        write_f64: f64,
+        write_number_str: &str,
+    }
+
+    forwarded! {
+        write_string_fragment(fragment: &str),
+        write_char_escape(char_escape: CharEscape),
+        begin_array,
+        end_array,
+        begin_array_value(first: bool),
+        end_array_value,
+        begin_object,
+        end_object,
+        begin_object_value,
+        end_object_value,
+        write_raw_fragment(fragment: &str),
+    }
+
+    fn write_null<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.paint(writer, LITERAL, |inner, writer| inner.write_null(writer))
+    }
+
+    fn write_bool<W>(&mut self, writer: &mut W, value: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.paint(writer, LITERAL, |inner, writer| {
+            inner.write_bool(writer, value)
+        })
+    }
+
+    fn begin_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        if !self.key {
+            writer.write_all(STRING)?;
+        }
+
+        self.inner.begin_string(writer)
+    }
+
+    fn end_string<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.end_string(writer)?;
+
+        match self.key {
+            true => Ok(()),
+            false => writer.write_all(RESET),
+        }
+    }
+
+    fn begin_object_key<W>(&mut self, writer: &mut W, first: bool) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.inner.begin_object_key(writer, first)?;
+        self.key = true;
+        writer.write_all(KEY)
+    }
+
+    fn end_object_key<W>(&mut self, writer: &mut W) -> io::Result<()>
+    where
+        W: ?Sized + io::Write,
+    {
+        self.key = false;
+        writer.write_all(RESET)?;
+        self.inner.end_object_key(writer)
+    }
+}
@@ -0,0 +1,194 @@
+use anyhow::{Context, Result, bail};
+use clap::{
+    Arg, ArgAction, ArgMatches, Command, Id, builder::BoolishValueParser, parser::ValueSource,
+};
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+// Configuration file looked up in the current directory and its ancestors
+const PROJECT_FILE: &str = ".json_decoder.toml";
+
+/// Makes the options set in the configuration files the defaults of
+/// `command`, so that options given on the command line override them.
+///
+/// The user's `json_decoder/config.toml`, in `$XDG_CONFIG_HOME` or
+/// `~/.config`, is read first, then the nearest `.json_decoder.toml`, whose
+/// options take precedence. Keys are the long names of options, such as
+/// `indent = 2`, `dialect = "nuxt"` or `lenient = true`.
+pub fn apply_defaults(mut command: Command) -> Result<Command> {
+    for path in user_file().into_iter().chain(project_file()) {
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let table: toml::Table = text
+            .parse()
+            .with_context(|| format!("Invalid configuration file {}", path.display()))?;
+
+        for (key, value) in &table {
+            command = set_default(command, key, value)
+                .with_context(|| format!("Invalid configuration file {}", path.display()))?;
+        }
+    }
+
+    Ok(command)
+}
+
+/// Lets every option that can be configured also be set by a
+/// `JSON_DECODER_<NAME>` environment variable, such as `JSON_DECODER_INDENT`,
+/// which takes precedence over the configuration files.
+pub fn with_env(command: Command) -> Command {
+    command.mut_args(|arg| match (configurable(&arg), arg.get_long()) {
+        (true, Some(long)) => {
+            let name = format!("JSON_DECODER_{}", long.to_uppercase().replace('-', "_"));
+
+            match arg.get_action() {
+                // Flags are also set by `1`, `yes` or `on`
+                ArgAction::SetTrue => arg.env(name).value_parser(BoolishValueParser::new()),
+                _ => arg.env(name),
+            }
+        }
+        _ => arg,
+    })
+}
+
+/// Parses the command line of `command`, which also takes a hidden
+/// `--no-<name>` for every flag and option with an optional value that can be
+/// configured, such as `--no-compact` or `--no-lenient`. It turns the option
+/// off again whatever the configuration files and environment variables set,
+/// unless the option is given after it.
+pub fn get_matches(command: Command) -> ArgMatches {
+    let negations = negations(&command);
+    let command = negations
+        .iter()
+        .fold(command, |command, (_, negation, global)| {
+            command.arg(
+                Arg::new(negation.clone())
+                    .long(negation.as_str().to_owned())
+                    .action(ArgAction::SetTrue)
+                    .global(*global)
+                    .hide(true),
+            )
+        });
+
+    let matches = command.clone().get_matches();
+    let given: Vec<_> = negations
+        .into_iter()
+        .filter(|(_, negation, _)| on_command_line(&matches, negation))
+        .collect();
+
+    if given.is_empty() {
+        return matches;
+    }
+
+    // The defaults are the configured values, and of the two given on the
+    // command line only the last is kept
+    given
+        .into_iter()
+        .fold(command, |command, (id, negation, _)| {
+            command
+                .mut_arg(&id, |arg| {
+                    arg.default_value(None)
+                        .env(None)
+                        .overrides_with(negation.clone())
+                })
+                .mut_arg(&negation, |arg| arg.overrides_with(id))
+        })
+        .get_matches()
+}
+
+// Options that can be turned off, with the id of their negation and whether
+// it is global
+fn negations(command: &Command) -> Vec<(Id, Id, bool)> {
+    command
+        .get_arguments()
+        .filter(|arg| configurable(arg) && negatable(arg))
+        .filter_map(|arg| {
+            let negation = format!("no-{}", arg.get_long()?);
+            let taken = command
+                .get_arguments()
+                .any(|other| other.get_long() == Some(negation.as_str()));
+
+            (!taken).then(|| {
+                (
+                    arg.get_id().clone(),
+                    Id::from(negation),
+                    arg.is_global_set(),
+                )
+            })
+        })
+        .collect()
+}
+
+// Global options given after a subcommand are found in its matches, which
+// lack the others
+fn on_command_line(matches: &ArgMatches, id: &Id) -> bool {
+    (matches.ids().any(|known| known == id)
+        && matches.value_source(id.as_str()) == Some(ValueSource::CommandLine))
+        || matches
+            .subcommand()
+            .is_some_and(|(_, matches)| on_command_line(matches, id))
+}
+
+// Flags, save those already turning something off, and options with an
+// optional value
+fn negatable(arg: &Arg) -> bool {
+    match arg.get_action() {
+        ArgAction::SetTrue => !arg.get_long().is_some_and(|long| long.starts_with("no-")),
+        _ => arg.get_num_args().is_some_and(|n| n.min_values() == 0),
+    }
+}
+
+// Options taking a single value and flags, save those running on their own
+fn configurable(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Set | ArgAction::SetTrue) && !arg.is_exclusive_set()
+}
+
+fn user_file() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config")))?;
+
+    Some(dir.join("json_decoder").join("config.toml")).filter(|path| path.is_file())
+}
+
+fn project_file() -> Option<PathBuf> {
+    let dir = env::current_dir().ok()?;
+
+    dir.ancestors()
+        .map(|dir| dir.join(PROJECT_FILE))
+        .find(|path| path.is_file())
+}
+
+// Makes `value` the default of the option named `key`
+fn set_default(command: Command, key: &str, value: &toml::Value) -> Result<Command> {
+    let Some(arg) = command
+        .get_arguments()
+        .find(|arg| arg.get_long() == Some(key))
+        .filter(|arg| configurable(arg))
+    else {
+        bail!("`{key}` is not an option that can be configured");
+    };
+
+    let id = arg.get_id().clone();
+    let value = match value {
+        toml::Value::String(s) => s.clone(),
+        toml::Value::Integer(n) => n.to_string(),
+        toml::Value::Float(n) => n.to_string(),
+        // Options with an optional value, such as `lenient`, take the value
+        // they have when given alone
+        toml::Value::Boolean(true) if arg.get_action().takes_values() => command
+            .clone()
+            .try_get_matches_from(["json_decoder", &format!("--{key}")])
+            .ok()
+            .and_then(|matches| Some(matches.get_raw(id.as_str())?.next()?.to_owned()))
+            .map(|value| value.to_string_lossy().into_owned())
+            .with_context(|| format!("`{key}` needs a value"))?,
+        toml::Value::Boolean(false) if arg.get_action().takes_values() => return Ok(command),
+        toml::Value::Boolean(flag) => flag.to_string(),
+        _ => bail!("`{key}` must be a string, a number or a boolean"),
+    };
+
+    Ok(command.mut_arg(id, |arg| arg.default_value(value)))
+}
@@ -0,0 +1,392 @@
+use super::{
+    diagnostic::WriteFailure,
+    input::{CLIPBOARD, Source},
+    pager::Pager,
+};
+use anyhow::{Context, Result, bail};
+use arboard::Clipboard;
+use clap::Args;
+use serde_json::{Map, Value};
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions, Permissions},
+    io::{self, BufWriter, IsTerminal, Read, Seek, SeekFrom, StdoutLock, Write},
+    path::{Path, PathBuf},
+};
+use tempfile::NamedTempFile;
+
+// Output path naming stdout
+const STDOUT: &str = "-";
+
+// Appended to the name of files overwritten by `--in-place --backup`
+const BACKUP_SUFFIX: &str = ".bak";
+
+// Serializers write a few bytes at a time, so outputs are buffered
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+#[derive(Args, Debug)]
+pub struct DestinationArgs {
+    /// Output file, `-` for stdout or `clipboard` to copy the result to the
+    /// clipboard; repeat it to write the same result to several destinations
+    /// (defaults to stdout)
+    #[arg(short, long, global = true)]
+    pub output: Vec<PathBuf>,
+
+    /// Text written between the results of multiple inputs sharing one output
+    #[arg(long, default_value = "\n", global = true)]
+    pub separator: String,
+
+    /// Write each result next to its input file
+    #[arg(long, conflicts_with = "output", global = true)]
+    pub per_file: bool,
+
+    /// Write each result below this directory, mirroring the tree under
+    /// `--input-dir`
+    #[arg(long, conflicts_with_all = ["output", "per_file"], global = true)]
+    pub output_dir: Option<PathBuf>,
+
+    /// Extension of the files written by `--per-file` and `--output-dir`
+    #[arg(long, default_value = "json", global = true)]
+    pub extension: String,
+
+    /// Write each top-level key of an object result to `<DIR>/<key>.<extension>`
+    #[arg(
+        long,
+        value_name = "DIR",
+        conflicts_with_all = ["output", "per_file", "output_dir"],
+        global = true
+    )]
+    pub split_keys: Option<PathBuf>,
+
+    /// Overwrite each input file with its result
+    #[arg(
+        long,
+        conflicts_with_all = ["output", "per_file", "output_dir", "split_keys", "watch"],
+        global = true
+    )]
+    pub in_place: bool,
+
+    /// Keep a copy of each file overwritten by `--in-place` with a `.bak` suffix
+    #[arg(long, requires = "in_place", global = true)]
+    pub backup: bool,
+
+    /// Write output files directly instead of through a temporary file that
+    /// replaces them once complete
+    #[arg(long, global = true)]
+    pub no_atomic: bool,
+
+    /// Append to output files instead of replacing them, after a separator
+    /// when they are not empty
+    #[arg(long, conflicts_with = "in_place", global = true)]
+    pub append: bool,
+
+    /// Write to the terminal directly instead of through `$PAGER`
+    #[arg(long, global = true)]
+    pub no_pager: bool,
+}
+
+/// Destination of results, committed once everything has been written.
+pub enum Output {
+    Stdout(BufWriter<StdoutLock<'static>>),
+    File(BufWriter<File>),
+    // Renamed over `path` on commit and removed when dropped before
+    Temp {
+        file: BufWriter<NamedTempFile>,
+        path: PathBuf,
+    },
+    Tee(Vec<Output>),
+    // Copied to the clipboard on commit
+    Clipboard(Vec<u8>),
+    // Stdout when it is a terminal
+    Pager(Pager),
+}
+
+impl DestinationArgs {
+    /// Returns the output shared by all inputs, unless each gets its own.
+    pub fn open_shared(&self) -> Result<Option<Output>> {
+        match self.per_file || self.output_dir.is_some() || self.in_place {
+            true => Ok(None),
+            false => match self.output.as_slice() {
+                [] => self.open(None).map(Some),
+                [output] => self.open(Some(output)).map(Some),
+                outputs => {
+                    let outputs: Result<Vec<Output>> = outputs
+                        .iter()
+                        .map(|output| self.open(Some(output)))
+                        .collect();
+                    Ok(Some(Output::Tee(outputs?)))
+                }
+            },
+        }
+    }
+
+    /// Whether results are only written to stdout, and stdout is a terminal.
+    pub fn is_terminal(&self) -> bool {
+        let shared = !self.per_file
+            && self.output_dir.is_none()
+            && self.split_keys.is_none()
+            && !self.in_place;
+
+        shared
+            && self.output.iter().all(|output| output == Path::new(STDOUT))
+            && io::stdout().is_terminal()
+    }
+
+    /// Opens the output dedicated to results read from `source`.
+    pub fn open_for(&self, source: &Source) -> Result<Output> {
+        let Source::File { path, relative } = source else {
+            bail!("Per-file outputs require input files, not {source}");
+        };
+
+        let output_path = match &self.output_dir {
+            Some(output_dir) => {
+                let relative = relative.as_deref().or(path.file_name().map(Path::new));
+                output_dir.join(relative.unwrap_or(path))
+            }
+            None => path.clone(),
+        };
+        let output_path = output_path.with_extension(&self.extension);
+
+        if output_path == *path {
+            bail!("Refusing to overwrite the input file {source}");
+        }
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)
+                .context(WriteFailure("Failed to create output directory"))?;
+        }
+
+        self.open(Some(&output_path))
+    }
+
+    /// Writes every member of `obj` to its own file below `dir` with `write`.
+    pub fn split(
+        &self,
+        dir: &Path,
+        obj: &Map<String, Value>,
+        mut write: impl FnMut(&Value, &mut dyn Write) -> Result<()>,
+    ) -> Result<()> {
+        fs::create_dir_all(dir).context(WriteFailure("Failed to create output directory"))?;
+
+        let mut names: HashMap<String, &str> = HashMap::new();
+
+        for (key, item) in obj {
+            let name = file_name(key);
+
+            if let Some(other) = names.insert(name.clone(), key) {
+                bail!("Keys {other:?} and {key:?} would both be written to {name:?}");
+            }
+
+            let mut path = dir.join(name).into_os_string();
+            path.push(".");
+            path.push(&self.extension);
+
+            let mut output = self.open(Some(Path::new(&path)))?;
+            write(item, &mut output).with_context(|| format!("Failed to write key {key:?}"))?;
+            output.commit()?;
+        }
+
+        Ok(())
+    }
+
+    /// Overwrites the input file of `source` with `output`, the result
+    /// computed from it.
+    pub fn replace(&self, source: &Source, output: &[u8]) -> Result<()> {
+        let Source::File { path, .. } = source else {
+            bail!("--in-place requires input files, not {source}");
+        };
+
+        if self.backup {
+            let mut backup_path = path.clone().into_os_string();
+            backup_path.push(BACKUP_SUFFIX);
+            fs::copy(path, &backup_path).context(WriteFailure("Failed to create backup file"))?;
+        }
+
+        let mut writer = self.open(Some(path))?;
+        writer
+            .write_all(output)
+            .context(WriteFailure("Failed to overwrite input file"))?;
+        writer.commit()
+    }
+
+    // Output: file, clipboard, or stdout, paged on a terminal
+    fn open(&self, output: Option<&Path>) -> Result<Output> {
+        let output_path = match output {
+            Some(output_path) if output_path == Path::new(CLIPBOARD) => {
+                return Ok(Output::Clipboard(Vec::new()));
+            }
+            Some(output_path) if output_path != Path::new(STDOUT) => output_path,
+            _ if !self.no_pager && io::stdout().is_terminal() => {
+                return Ok(match Pager::spawn() {
+                    Some(pager) => Output::Pager(pager),
+                    None => Output::Stdout(buffered(io::stdout().lock())),
+                });
+            }
+            _ => return Ok(Output::Stdout(buffered(io::stdout().lock()))),
+        };
+
+        if self.append {
+            let mut f = OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(output_path)
+                .context(WriteFailure("Failed to open output file"))?;
+
+            if !ends_with(&mut f, self.separator.as_bytes())
+                .context(WriteFailure("Failed to read output file"))?
+            {
+                f.write_all(self.separator.as_bytes())
+                    .context(WriteFailure("Failed to write output separator"))?;
+            }
+
+            return Ok(Output::File(buffered(f)));
+        }
+
+        if self.no_atomic {
+            let f =
+                File::create(output_path).context(WriteFailure("Failed to create output file"))?;
+            return Ok(Output::File(buffered(f)));
+        }
+
+        // The temporary file must be on the same file system for the rename
+        let dir = match output_path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent,
+            _ => Path::new("."),
+        };
+
+        let mut builder = tempfile::Builder::new();
+        builder.prefix(".json_decoder");
+
+        // Keep the permissions of a replaced file, or the usual ones of a new one
+        let permissions = match fs::metadata(output_path) {
+            Ok(metadata) => Some(metadata.permissions()),
+            Err(_) => default_permissions(),
+        };
+
+        if let Some(permissions) = permissions {
+            builder.permissions(permissions);
+        }
+
+        let file = builder
+            .tempfile_in(dir)
+            .context(WriteFailure("Failed to create output file"))?;
+
+        Ok(Output::Temp {
+            file: buffered(file),
+            path: output_path.to_path_buf(),
+        })
+    }
+}
+
+impl Output {
+    /// Flushes the output and moves a temporary file into place.
+    pub fn commit(mut self) -> Result<()> {
+        self.flush()
+            .context(WriteFailure("Failed to write output"))?;
+
+        match self {
+            Output::Temp { file, path } => file
+                .into_inner()
+                .map_err(|e| e.into_error())
+                .context(WriteFailure("Failed to write output"))?
+                .persist(path)
+                .map(drop)
+                .context(WriteFailure("Failed to replace output file")),
+            Output::Tee(outputs) => outputs.into_iter().try_for_each(Output::commit),
+            Output::Clipboard(output) => {
+                let text = String::from_utf8(output).context(WriteFailure(
+                    "Only text output can be copied to the clipboard",
+                ))?;
+
+                Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.set_text(text))
+                    .context(WriteFailure("Failed to write the clipboard"))
+            }
+            // Dropping the pager waits for it to be quit
+            Output::Stdout(_) | Output::File(_) | Output::Pager(_) => Ok(()),
+        }
+    }
+}
+
+impl Write for Output {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Output::Stdout(stdout) => stdout.write(buf),
+            Output::File(file) => file.write(buf),
+            Output::Temp { file, .. } => file.write(buf),
+            Output::Tee(outputs) => {
+                for output in outputs {
+                    output.write_all(buf)?;
+                }
+
+                Ok(buf.len())
+            }
+            Output::Clipboard(output) => output.write(buf),
+            Output::Pager(pager) => pager.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Output::Stdout(stdout) => stdout.flush(),
+            Output::File(file) => file.flush(),
+            Output::Temp { file, .. } => file.flush(),
+            Output::Tee(outputs) => outputs.iter_mut().try_for_each(Output::flush),
+            Output::Clipboard(_) => Ok(()),
+            Output::Pager(pager) => pager.flush(),
+        }
+    }
+}
+
+fn buffered<W: Write>(writer: W) -> BufWriter<W> {
+    BufWriter::with_capacity(OUTPUT_BUFFER_SIZE, writer)
+}
+
+// Keys may hold anything, but must stay a single file name inside the directory
+fn file_name(key: &str) -> String {
+    match key {
+        "" | "." | ".." => key.replace('.', "_") + "_",
+        _ => key
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c if c.is_control() => '_',
+                c => c,
+            })
+            .collect(),
+    }
+}
+
+// Whether an empty file or one whose content already ends with `suffix`,
+// such as NDJSON output followed by the default newline separator
+fn ends_with(f: &mut File, suffix: &[u8]) -> io::Result<bool> {
+    let len = f.metadata()?.len();
+
+    if len == 0 {
+        return Ok(true);
+    }
+
+    let Some(start) = len.checked_sub(suffix.len() as u64) else {
+        return Ok(false);
+    };
+
+    let mut tail = vec![0; suffix.len()];
+    f.seek(SeekFrom::Start(start))?;
+    f.read_exact(&mut tail)?;
+
+    Ok(tail == suffix)
+}
+
+// Subject to the umask, like files created by `File::create`
+#[cfg(unix)]
+fn default_permissions() -> Option<Permissions> {
+    use std::os::unix::fs::PermissionsExt;
+
+    Some(Permissions::from_mode(0o666))
+}
+
+#[cfg(not(unix))]
+fn default_permissions() -> Option<Permissions> {
+    None
+}
@@ -0,0 +1,508 @@
+use super::input::{InputArgs, Source};
+use anyhow::Error;
+use clap::ValueEnum;
+use json_decoder::DecodeError;
+use miette::{
+    LabeledSpan, MietteDiagnostic, MietteError, MietteHandlerOpts, MietteSpanContents, Report,
+    SourceCode, SourceSpan, SpanContents,
+};
+use serde_json::json;
+use std::{
+    fmt,
+    io::{self, BufRead},
+    ops::Range,
+    process::ExitCode,
+};
+
+// Bytes of a long input line shown before and after the problem
+const WINDOW: usize = 60;
+
+/// How errors are printed to stderr.
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+pub enum ErrorFormat {
+    /// Human-readable text, pointing into the input line when possible
+    #[default]
+    Text,
+    /// One JSON object per error with its kind, message, line, entry and path
+    Json,
+}
+
+/// A decoding error rendered with the input line it was found on.
+#[derive(Debug)]
+pub struct SourceDiagnostic {
+    error: Error,
+    diagnostic: MietteDiagnostic,
+    source: Snippet,
+}
+
+// The part of the input line holding a problem, numbered as in the input
+#[derive(Debug, Clone)]
+struct Snippet {
+    name: String,
+    text: String,
+    // Line of the input the text is on, from 0
+    line: usize,
+}
+
+impl SourceCode for Snippet {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let contents = self
+            .text
+            .read_span(span, context_lines_before, context_lines_after)?;
+
+        Ok(Box::new(MietteSpanContents::new_named(
+            self.name.clone(),
+            contents.data(),
+            *contents.span(),
+            contents.line() + self.line,
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+impl fmt::Display for SourceDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.diagnostic.message)
+    }
+}
+
+impl std::error::Error for SourceDiagnostic {}
+
+/// Context of errors in an input found once it is read, such as a bad
+/// `--base64` wrapper, which exit with [`Failure::Input`].
+#[derive(Debug)]
+pub struct ReadFailure(pub &'static str);
+
+impl fmt::Display for ReadFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Context of errors writing the output, which exit with
+/// [`Failure::Output`].
+#[derive(Debug)]
+pub struct WriteFailure(pub &'static str);
+
+impl fmt::Display for WriteFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.0)
+    }
+}
+
+/// Classes of failures, with the exit status they end the process with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// Anything not covered below, such as a failed `lint` or query.
+    Other = 1,
+    /// Reading, fetching or extracting an input failed.
+    Input = 3,
+    /// An input is not a well-formed payload.
+    Parse = 4,
+    /// A reference in an input could not be resolved.
+    Resolve = 5,
+    /// Writing the output failed.
+    Output = 6,
+}
+
+impl Failure {
+    /// Class of `error`, from the errors in its chain.
+    pub fn of(error: &Error) -> Self {
+        let error = original(error);
+
+        if let Some(decode_error) = decode_error(error) {
+            return match decode_error.innermost() {
+                DecodeError::Io { .. } => Failure::Input,
+                DecodeError::InvalidJson { .. }
+                | DecodeError::InvalidRelaxedJson { .. }
+                | DecodeError::InvalidContinuation { .. }
+                | DecodeError::NonCanonical { .. }
+                | DecodeError::InvalidFragment { .. }
+                | DecodeError::EmptyEntryList => Failure::Parse,
+                DecodeError::AmbiguousDialect { .. } | DecodeError::UnsupportedDialect { .. } => {
+                    Failure::Other
+                }
+                _ => Failure::Resolve,
+            };
+        }
+
+        let json_error = error
+            .chain()
+            .find_map(|e| e.downcast_ref::<serde_json::Error>());
+        let writing = error.downcast_ref::<WriteFailure>().is_some();
+
+        match json_error {
+            // The only data errors while writing come from streamed decoding
+            Some(json_error) if writing && json_error.is_data() => Failure::Resolve,
+            _ if writing => Failure::Output,
+            // Wrappers are not the payload, so their JSON is not parsed as one
+            _ if error.downcast_ref::<ReadFailure>().is_some() => Failure::Input,
+            Some(json_error) if json_error.is_syntax() || json_error.is_eof() => Failure::Parse,
+            _ if error.chain().any(|e| {
+                e.is::<io::Error>() || e.is::<ureq::Error>() || e.is::<arboard::Error>()
+            }) =>
+            {
+                Failure::Input
+            }
+            _ => Failure::Other,
+        }
+    }
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+/// Sets up the rendering of diagnostics for the terminal.
+pub fn install_handler() {
+    let _ = miette::set_hook(Box::new(|_| {
+        Box::new(MietteHandlerOpts::new().context_lines(1).build())
+    }));
+}
+
+/// Prints `error` to stderr, with the offending input line when it is known.
+pub fn report(error: &Error, format: ErrorFormat) {
+    let diagnostic = error.downcast_ref::<SourceDiagnostic>();
+
+    match (format, diagnostic) {
+        (ErrorFormat::Text, Some(diagnostic)) => {
+            let report = Report::new(diagnostic.diagnostic.clone())
+                .with_source_code(diagnostic.source.clone());
+            eprintln!("{report:?}");
+        }
+        (ErrorFormat::Text, None) => eprintln!("Error: {error:?}"),
+        (ErrorFormat::Json, _) => eprintln!("{}", to_json(original(error))),
+    }
+}
+
+/// The error behind a diagnostic, to be inspected.
+pub fn original(error: &Error) -> &Error {
+    match error.downcast_ref::<SourceDiagnostic>() {
+        Some(diagnostic) => &diagnostic.error,
+        None => error,
+    }
+}
+
+/// The outermost decoding error in the chain of `error`.
+pub fn decode_error(error: &Error) -> Option<&DecodeError> {
+    error.chain().find_map(|e| e.downcast_ref::<DecodeError>())
+}
+
+/// Describes an error as a JSON object, located by the decoding error in its
+/// chain.
+pub fn to_json(error: &Error) -> serde_json::Value {
+    let decode_error = decode_error(error);
+    let kind = match decode_error {
+        Some(decode_error) => decode_error.kind(),
+        None if error.chain().any(|e| e.is::<io::Error>()) => "io",
+        None => "error",
+    };
+
+    json!({
+        "kind": kind,
+        "message": format!("{error:#}"),
+        "line": decode_error.and_then(DecodeError::line),
+        "entry": decode_error.and_then(DecodeError::entry),
+        "path": decode_error.and_then(DecodeError::path),
+    })
+}
+
+/// Attaches the input line to a decoding error from `source`, when the error
+/// points into the input and the input can be read again.
+pub fn diagnose(input: &InputArgs, source: &Source, error: Error) -> Error {
+    match source {
+        Source::File { .. } | Source::Data(_) => {}
+        // Reading them again would not return the same payload
+        Source::Stdin | Source::Url(_) | Source::Clipboard => return error,
+    }
+
+    let Some(decode_error) = decode_error(&error) else {
+        return error;
+    };
+    let Some(problem) = Problem::of(decode_error) else {
+        return error;
+    };
+    let Some((text, span)) = problem.locate(input, source) else {
+        return error;
+    };
+
+    let (snippet, span) = window(&text, span);
+
+    let diagnostic = MietteDiagnostic::new(format!("{error:#}"))
+        .with_label(LabeledSpan::at(span, problem.label))
+        .with_help(problem.help);
+
+    Error::new(SourceDiagnostic {
+        error,
+        diagnostic,
+        source: Snippet {
+            name: source.to_string(),
+            text: snippet,
+            line: problem.line - 1,
+        },
+    })
+}
+
+// Where in the input a decoding error is
+struct Problem {
+    line: usize,
+    target: Target,
+    label: String,
+    help: String,
+}
+
+enum Target {
+    Offset(usize),
+    Entry(usize),
+    PIndex,
+}
+
+impl Problem {
+    fn of(error: &DecodeError) -> Option<Self> {
+        let problem = match error {
+            DecodeError::InvalidJson {
+                line,
+                context,
+                offset,
+                source,
+            } => Problem {
+                line: *line,
+                target: Target::Offset(*offset),
+                label: without_position(source),
+                // A first line holding an object is likely plain JSON
+                help: match source.to_string().starts_with("invalid type: map") {
+                    true => format!(
+                        "the {context} must be a JSON array on a single line; \
+                         pass --passthrough to reformat input that is already JSON"
+                    ),
+                    false => format!("the {context} must be a JSON array on a single line"),
+                },
+            },
+            DecodeError::InvalidRelaxedJson {
+                line,
+                context,
+                offset,
+                source,
+            } => Problem {
+                line: *line,
+                target: Target::Offset(*offset),
+                label: without_position(source),
+                help: format!("the {context} must be a JSON5 array on a single line"),
+            },
+            DecodeError::InvalidContinuation { line, reason } => Problem {
+                line: *line,
+                target: Target::PIndex,
+                label: reason.clone(),
+                help: String::from(
+                    "continuation lines look like `P<index>:[...]`, where entry <index> \
+                     is a [\"P\", index] promise",
+                ),
+            },
+            // The message already names the path
+            DecodeError::Located {
+                entry,
+                line,
+                source,
+                ..
+            } => Problem {
+                line: *line,
+                target: Target::Entry(*entry),
+                label: source.innermost().to_string(),
+                help: match source.innermost() {
+                    DecodeError::DepthLimitExceeded { .. } => {
+                        String::from("deeper documents can be decoded with a higher --max-depth")
+                    }
+                    _ => format!("entry {entry} was being decoded"),
+                },
+            },
+            _ => return None,
+        };
+
+        Some(problem)
+    }
+
+    // Reads the input again and returns the problem's line with the span to
+    // point at
+    fn locate(&self, input: &InputArgs, source: &Source) -> Option<(String, Range<usize>)> {
+        let mut reader = input.open(source).ok()?;
+        let mut text = String::new();
+        // The problem's line, and whether it holds the entry array
+        let mut target = None;
+        // Merge order, line and entry count of every payload line
+        let mut payload = Vec::new();
+        // Whether the entry array, the first line that is not blank, was read
+        let mut started = false;
+        let mut ended = false;
+
+        for line in 1.. {
+            text.clear();
+
+            if reader.read_line(&mut text).ok()? == 0 {
+                break;
+            }
+
+            let blank = text.trim_start_matches('\u{feff}').trim().is_empty();
+            let entry_array = !started && !blank;
+
+            if line == self.line {
+                target = Some((text.clone(), entry_array));
+            }
+
+            ended |= started && blank;
+
+            if ended && target.is_some() {
+                break;
+            } else if ended || blank {
+                continue;
+            }
+
+            started = true;
+            let order = (!entry_array).then(|| merge_order(&text));
+            let entries = element_spans(array_part(&text, entry_array)).len();
+            payload.push(((order, line), entries));
+        }
+
+        // Lines past the end of input are shown empty
+        let (text, entry_array) = target.unwrap_or_default();
+        // Entries of the lines merged before, continuation lines being merged
+        // in P-index order
+        let first_entry = payload
+            .iter()
+            .find(|&&((_, line), _)| line == self.line)
+            .map_or(0, |&(position, _)| {
+                payload
+                    .iter()
+                    .filter(|&&(other, _)| other < position)
+                    .map(|&(_, entries)| entries)
+                    .sum()
+            });
+
+        let text = String::from(
+            text.trim_start_matches('\u{feff}')
+                .trim_end_matches(['\r', '\n']),
+        );
+
+        let span = match self.target {
+            Target::Offset(offset) => offset..offset + 1,
+            Target::PIndex => 0..text.find(':').unwrap_or(text.len()),
+            Target::Entry(entry) => {
+                let array = array_part(&text, entry_array);
+                let start = text.len() - array.len();
+                let span = element_spans(array).into_iter().nth(entry - first_entry)?;
+                start + span.start..start + span.end
+            }
+        };
+
+        Some((text, span))
+    }
+}
+
+// serde_json and json5 end their messages with a position that is wrong for
+// the window shown
+fn without_position(error: &dyn fmt::Display) -> String {
+    let message = error.to_string();
+
+    match message.rsplit_once(" at line ") {
+        Some((message, _)) => String::from(message),
+        None => message,
+    }
+}
+
+// Position of a continuation line once sorted by P-index as the decoder does,
+// malformed ones last; the index follows whatever promise prefix is used
+fn merge_order(text: &str) -> i128 {
+    text.split_once(':')
+        .and_then(|(p_index, _)| {
+            p_index
+                .trim()
+                .trim_start_matches(|c: char| !c.is_ascii_digit())
+                .parse()
+                .ok()
+        })
+        .unwrap_or(i128::MAX)
+}
+
+// The entry array of an input line, after the `P<index>:` of continuations
+fn array_part(text: &str, entry_array: bool) -> &str {
+    match entry_array {
+        true => text,
+        false => text.split_once(':').map_or("", |(_, array)| array),
+    }
+}
+
+// Byte ranges of the elements of the JSON array `text` starts with
+fn element_spans(text: &str) -> Vec<Range<usize>> {
+    let mut spans = Vec::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = None;
+    let mut end = 0;
+
+    for (i, b) in text.bytes().enumerate() {
+        if in_string {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            end = i + 1;
+            continue;
+        }
+
+        match b {
+            b'[' if depth == 0 => {
+                depth = 1;
+                continue;
+            }
+            b',' if depth == 1 => {
+                spans.extend(start.take().map(|start| start..end));
+                continue;
+            }
+            b']' if depth == 1 => {
+                spans.extend(start.take().map(|start| start..end));
+                break;
+            }
+            _ if b.is_ascii_whitespace() || depth == 0 => continue,
+            b'[' | b'{' => depth += 1,
+            b']' | b'}' => depth -= 1,
+            b'"' => in_string = true,
+            _ => {}
+        }
+
+        start.get_or_insert(i);
+        end = i + 1;
+    }
+
+    spans
+}
+
+// Cuts a long line down to the part around `span`, which is moved to match
+fn window(text: &str, span: Range<usize>) -> (String, Range<usize>) {
+    let start = text.floor_char_boundary(span.start.saturating_sub(WINDOW));
+    let end = text.ceil_char_boundary(span.end.min(span.start + WINDOW) + WINDOW);
+
+    let prefix = match start {
+        0 => "",
+        _ => "…",
+    };
+    let suffix = match end < text.len() {
+        true => "…",
+        false => "",
+    };
+
+    let snippet = format!("{prefix}{}{suffix}", &text[start..end]);
+    // A span past the end of the line points at its end
+    let shift = |i: usize| i.min(end) - start + prefix.len();
+
+    (snippet, shift(span.start)..shift(span.end))
+}
@@ -0,0 +1,160 @@
+use super::diagnostic::WriteFailure;
+use anyhow::{Context, Result};
+use json_decoder::{PathSegment, format_path, format_pointer};
+use serde_json::{Value, json};
+use std::io::Write;
+
+/// One difference between two decoded values.
+#[derive(Debug)]
+pub enum Change<'a> {
+    Added {
+        path: Vec<PathSegment>,
+        value: &'a Value,
+    },
+    Removed {
+        path: Vec<PathSegment>,
+        value: &'a Value,
+    },
+    Changed {
+        path: Vec<PathSegment>,
+        old: &'a Value,
+        new: &'a Value,
+    },
+}
+
+/// Compares `old` with `new` and returns every added, removed or changed
+/// path. Array elements are compared by position.
+pub fn diff<'a>(old: &'a Value, new: &'a Value) -> Vec<Change<'a>> {
+    let mut changes = Vec::new();
+    diff_values(old, new, &mut Vec::new(), &mut changes);
+
+    changes
+}
+
+/// Writes one line per change: `+` for added, `-` for removed and `~` for
+/// changed paths, followed by the compact JSON values.
+pub fn write_diff(changes: &[Change], writer: &mut dyn Write) -> Result<()> {
+    for change in changes {
+        let result = match change {
+            Change::Added { path, value } => writeln!(writer, "+ {}: {value}", format_path(path)),
+            Change::Removed { path, value } => {
+                writeln!(writer, "- {}: {value}", format_path(path))
+            }
+            Change::Changed { path, old, new } => {
+                writeln!(writer, "~ {}: {old} -> {new}", format_path(path))
+            }
+        };
+
+        result.context(WriteFailure("Failed to write diff"))?;
+    }
+
+    Ok(())
+}
+
+/// Converts `changes` into an RFC 6902 JSON Patch document turning the old
+/// value into the new one.
+pub fn to_patch(changes: &[Change]) -> Value {
+    let mut operations = Vec::with_capacity(changes.len());
+
+    // Removing an element shifts the following ones, so trailing elements of
+    // the same array are removed from the end
+    let groups = changes.chunk_by(
+        |a, b| matches!((array_removal(a), array_removal(b)), (Some(a), Some(b)) if a == b),
+    );
+
+    for group in groups {
+        let ordered: Box<dyn Iterator<Item = &Change>> = match array_removal(&group[0]) {
+            Some(_) => Box::new(group.iter().rev()),
+            None => Box::new(group.iter()),
+        };
+
+        operations.extend(ordered.map(|change| match change {
+            Change::Added { path, value } => {
+                json!({ "op": "add", "path": format_pointer(path), "value": value })
+            }
+            Change::Removed { path, .. } => {
+                json!({ "op": "remove", "path": format_pointer(path) })
+            }
+            Change::Changed { path, new, .. } => {
+                json!({ "op": "replace", "path": format_pointer(path), "value": new })
+            }
+        }));
+    }
+
+    Value::Array(operations)
+}
+
+// Path of the array an element is removed from
+fn array_removal<'a>(change: &'a Change) -> Option<&'a [PathSegment]> {
+    match change {
+        Change::Removed { path, .. } => match path.split_last() {
+            Some((PathSegment::Index(_), parent)) => Some(parent),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+fn diff_values<'a>(
+    old: &'a Value,
+    new: &'a Value,
+    path: &mut Vec<PathSegment>,
+    changes: &mut Vec<Change<'a>>,
+) {
+    match (old, new) {
+        (Value::Object(old_obj), Value::Object(new_obj)) => {
+            for (key, old_item) in old_obj {
+                path.push(PathSegment::Key(key.clone()));
+
+                match new_obj.get(key) {
+                    Some(new_item) => diff_values(old_item, new_item, path, changes),
+                    None => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_item,
+                    }),
+                }
+
+                path.pop();
+            }
+
+            for (key, new_item) in new_obj {
+                if !old_obj.contains_key(key) {
+                    path.push(PathSegment::Key(key.clone()));
+                    changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new_item,
+                    });
+                    path.pop();
+                }
+            }
+        }
+        (Value::Array(old_arr), Value::Array(new_arr)) => {
+            for i in 0..old_arr.len().max(new_arr.len()) {
+                path.push(PathSegment::Index(i));
+
+                match (old_arr.get(i), new_arr.get(i)) {
+                    (Some(old_item), Some(new_item)) => {
+                        diff_values(old_item, new_item, path, changes)
+                    }
+                    (Some(old_item), None) => changes.push(Change::Removed {
+                        path: path.clone(),
+                        value: old_item,
+                    }),
+                    (None, Some(new_item)) => changes.push(Change::Added {
+                        path: path.clone(),
+                        value: new_item,
+                    }),
+                    (None, None) => {}
+                }
+
+                path.pop();
+            }
+        }
+        _ if old != new => changes.push(Change::Changed {
+            path: path.clone(),
+            old,
+            new,
+        }),
+        _ => {}
+    }
+}
@@ -0,0 +1,73 @@
+use super::diagnostic::WriteFailure;
+use anyhow::{Context, Result};
+use json_decoder::{DecodeOptions, ReferenceKind, StreamingDecoder};
+use serde_json::Value;
+use std::io::{BufRead, Write};
+
+// Longest scalar shown in a node label
+const MAX_LABEL: usize = 40;
+
+/// Writes the entry-reference graph of one payload in Graphviz DOT format:
+/// one node per entry and one edge per index reference.
+///
+/// Key references are dashed and promises are drawn in blue. The payload is
+/// only loaded, so cycles or other broken structures can still be drawn.
+pub fn write_dot(
+    reader: Box<dyn BufRead>,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+) -> Result<()> {
+    let decoder = StreamingDecoder::from_reader_with_options(reader, options)?;
+    let mut dot =
+        String::from("digraph entries {\n    node [shape=box, fontname=\"monospace\"];\n");
+
+    for (index, entry) in decoder.encoded_list().iter().enumerate() {
+        let label = format!("{index}: {}", summary(entry));
+        dot.push_str(&format!("    {index} [label={}];\n", quoted(&label)));
+    }
+
+    for index in 0..decoder.encoded_list().len() {
+        let references = decoder
+            .references(index)
+            .with_context(|| format!("Invalid references in entry {index}"))?;
+
+        for reference in references {
+            let style = match reference.kind {
+                ReferenceKind::Element | ReferenceKind::Value => "",
+                ReferenceKind::Key => " [style=dashed]",
+                ReferenceKind::Promise => " [color=blue]",
+            };
+            dot.push_str(&format!(
+                "    {} -> {}{style};\n",
+                reference.from, reference.to
+            ));
+        }
+    }
+
+    dot.push_str("}\n");
+
+    writer
+        .write_all(dot.as_bytes())
+        .context(WriteFailure("Failed to write DOT graph"))
+}
+
+fn summary(entry: &Value) -> String {
+    match entry {
+        Value::Array(arr) if arr.iter().any(|item| item == "P") => String::from("promise"),
+        Value::Array(arr) => format!("array ({})", arr.len()),
+        Value::Object(obj) => format!("object ({})", obj.len()),
+        v => {
+            let text = v.to_string();
+
+            match text.char_indices().nth(MAX_LABEL) {
+                Some((end, _)) => format!("{}…", &text[..end]),
+                None => text,
+            }
+        }
+    }
+}
+
+// DOT string literal
+fn quoted(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
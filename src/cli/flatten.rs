@@ -0,0 +1,178 @@
+use anyhow::{Context, Result, bail};
+use json_decoder::{PathSegment, format_path};
+use serde_json::{Deserializer, Map, Value};
+use std::collections::HashMap;
+
+/// Flattens `value` into a single-level object mapping the path of every
+/// scalar, empty array and empty object, such as `a.b[0].c`, to the value.
+/// Keys that are not identifiers are written as `["my key"]`.
+pub fn flatten(value: Value) -> Value {
+    let mut flat = Map::new();
+    flatten_at(value, &mut Vec::new(), &mut flat);
+    Value::Object(flat)
+}
+
+fn flatten_at(value: Value, path: &mut Vec<PathSegment>, flat: &mut Map<String, Value>) {
+    match value {
+        Value::Object(obj) if !obj.is_empty() => {
+            for (key, item) in obj {
+                path.push(PathSegment::Key(key));
+                flatten_at(item, path, flat);
+                path.pop();
+            }
+        }
+        Value::Array(arr) if !arr.is_empty() => {
+            for (i, item) in arr.into_iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                flatten_at(item, path, flat);
+                path.pop();
+            }
+        }
+        // An empty root object has no members to list
+        Value::Object(_) if path.is_empty() => {}
+        value => {
+            flat.insert(flat_key(path), value);
+        }
+    }
+}
+
+// The path without the leading `$.`, the root being the empty key
+fn flat_key(path: &[PathSegment]) -> String {
+    let path = format_path(path);
+    let path = path.strip_prefix('$').unwrap_or(&path);
+    path.strip_prefix('.').unwrap_or(path).to_string()
+}
+
+/// Rebuilds the nested value of an object written by [`flatten`], whatever
+/// the order of its keys. Missing array elements are `null`.
+pub fn unflatten(value: Value) -> Result<Value> {
+    let Value::Object(flat) = value else {
+        bail!("--unflatten expects an object of flattened paths");
+    };
+
+    let entries = flat
+        .into_iter()
+        .map(|(key, item)| Ok((parse_flat_key(&key)?, key, item)))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Values other than empty containers can not have children
+    let leaves: HashMap<&[PathSegment], &str> = entries
+        .iter()
+        .filter(|(_, _, item)| !is_empty_container(item))
+        .map(|(path, key, _)| (path.as_slice(), key.as_str()))
+        .collect();
+
+    for (path, key, _) in &entries {
+        if let Some(parent) = (0..path.len()).find_map(|len| leaves.get(&path[..len])) {
+            bail!("Flattened path {key:?} is inside the value of {parent:?}");
+        }
+    }
+
+    // Indexes are bounded by the number of paths, so that a single large
+    // one does not allocate a huge array
+    let max_len = entries.len();
+    let mut root = Value::Null;
+
+    for (path, key, item) in entries {
+        let mut target = &mut root;
+
+        for segment in &path {
+            target = child(target, segment, max_len)
+                .with_context(|| format!("Flattened path {key:?} can not be rebuilt"))?;
+        }
+
+        match (&*target, &item) {
+            (Value::Null, _) => *target = item,
+            (Value::Object(_), Value::Object(_)) | (Value::Array(_), Value::Array(_))
+                if is_empty_container(&item) => {}
+            _ => bail!("Flattened path {key:?} conflicts with another one"),
+        }
+    }
+
+    Ok(match root {
+        Value::Null if max_len == 0 => Value::Object(Map::new()),
+        root => root,
+    })
+}
+
+// The member or element of `target` at `segment`, made a container if unset
+fn child<'a>(
+    target: &'a mut Value,
+    segment: &PathSegment,
+    max_len: usize,
+) -> Result<&'a mut Value> {
+    if target.is_null() {
+        *target = match segment {
+            PathSegment::Key(_) => Value::Object(Map::new()),
+            PathSegment::Index(_) => Value::Array(Vec::new()),
+        };
+    }
+
+    match (target, segment) {
+        (Value::Object(obj), PathSegment::Key(key)) => {
+            Ok(obj.entry(key.clone()).or_insert(Value::Null))
+        }
+        (Value::Array(arr), &PathSegment::Index(index)) if index < max_len => {
+            if arr.len() <= index {
+                arr.resize(index + 1, Value::Null);
+            }
+            Ok(&mut arr[index])
+        }
+        (Value::Array(_), PathSegment::Index(index)) => {
+            bail!("index {index} is not below the number of paths")
+        }
+        _ => bail!("an object and an array are at the same path"),
+    }
+}
+
+fn is_empty_container(value: &Value) -> bool {
+    match value {
+        Value::Object(obj) => obj.is_empty(),
+        Value::Array(arr) => arr.is_empty(),
+        _ => false,
+    }
+}
+
+// Parses keys such as `a.b[0]["my key"]`, the empty key being the root
+fn parse_flat_key(key: &str) -> Result<Vec<PathSegment>> {
+    let invalid = || format!("Invalid flattened path {key:?}");
+    let mut segments = Vec::new();
+    let mut rest = key;
+
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let tail = match inner.starts_with('"') {
+                true => {
+                    let mut strings = Deserializer::from_str(inner).into_iter::<String>();
+                    let name = strings
+                        .next()
+                        .with_context(invalid)?
+                        .with_context(invalid)?;
+                    segments.push(PathSegment::Key(name));
+                    &inner[strings.byte_offset()..]
+                }
+                false => {
+                    let end = inner.find(']').with_context(invalid)?;
+                    let index = inner[..end].parse().ok().with_context(invalid)?;
+                    segments.push(PathSegment::Index(index));
+                    &inner[end..]
+                }
+            };
+            rest = tail.strip_prefix(']').with_context(invalid)?;
+        } else {
+            // Only the first key is not preceded by a dot
+            let name = match segments.is_empty() {
+                true => rest,
+                false => rest.strip_prefix('.').with_context(invalid)?,
+            };
+            let end = name.find(['.', '[']).unwrap_or(name.len());
+            if end == 0 {
+                bail!(invalid());
+            }
+            segments.push(PathSegment::Key(name[..end].to_string()));
+            rest = &name[end..];
+        }
+    }
+
+    Ok(segments)
+}
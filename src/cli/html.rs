@@ -0,0 +1,43 @@
+use anyhow::{Context, Result, bail};
+use scraper::{Html, Selector};
+
+// Scripts are searched for the payload when no selector is given
+const DEFAULT_SELECTOR: &str = "script";
+
+/// Returns the encoded payload embedded in the first element of `html`
+/// matching `selector` that holds one.
+///
+/// Script wrappers such as `window.__DATA__ = [...];` are stripped, so only
+/// the entry array and its continuation lines remain.
+pub fn extract_payload(html: &str, selector: Option<&str>) -> Result<String> {
+    let css = selector.unwrap_or(DEFAULT_SELECTOR);
+    let selector = Selector::parse(css)
+        .map_err(|e| anyhow::anyhow!("{e}"))
+        .with_context(|| format!("Invalid selector {css:?}"))?;
+
+    let document = Html::parse_document(html);
+
+    for element in document.select(&selector) {
+        let text: String = element.text().collect();
+
+        if let Some(payload) = strip_wrapper(&text) {
+            return Ok(payload.to_string());
+        }
+    }
+
+    bail!("No element matching {css:?} holds an encoded payload")
+}
+
+// The payload starts at the entry array, possibly behind an assignment
+fn strip_wrapper(text: &str) -> Option<&str> {
+    let text = text.trim();
+    let start = text.find('[')?;
+
+    match text[..start].trim_end() {
+        "" => {}
+        prefix if prefix.ends_with('=') => {}
+        _ => return None,
+    }
+
+    Some(text[start..].trim_end().trim_end_matches(';').trim_end())
+}
@@ -0,0 +1,401 @@
+use super::{diagnostic::ReadFailure, html::extract_payload};
+use anyhow::{Context, Result, bail};
+use arboard::Clipboard;
+use base64::{
+    Engine, alphabet,
+    engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig},
+};
+use clap::{Args, ValueEnum};
+use flate2::bufread::MultiGzDecoder;
+use globset::Glob;
+use json_decoder::{DecodeError, DecodeOptions, JSONDecoder, StreamingDecoder};
+use memmap2::Mmap;
+use regex::Regex;
+use serde_json::Value;
+use std::{
+    fmt,
+    fs::File,
+    io::{self, BufRead, BufReader, Cursor, Read},
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+// Input path naming the system clipboard
+pub const CLIPBOARD: &str = "clipboard";
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+// Internal buffer size of the brotli decoder
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+// Wrapped payloads come from all kinds of producers, so padding is optional
+const BASE64_CONFIG: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+const BASE64_ENGINES: [GeneralPurpose; 2] = [
+    GeneralPurpose::new(&alphabet::STANDARD, BASE64_CONFIG),
+    GeneralPurpose::new(&alphabet::URL_SAFE, BASE64_CONFIG),
+];
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Decompress {
+    /// Detect gzip and zstd from the leading magic bytes; brotli has no
+    /// magic bytes and must be requested explicitly
+    #[default]
+    Auto,
+    /// Read the input as is
+    None,
+    Gzip,
+    Zstd,
+    #[value(name = "br")]
+    Brotli,
+}
+
+#[derive(Args, Debug)]
+pub struct InputArgs {
+    /// Input file, `http(s)://` URL to download or `clipboard` for the text
+    /// on the clipboard, repeatable (defaults to stdin)
+    #[arg(short, long, global = true)]
+    pub input: Vec<PathBuf>,
+
+    /// Directory to search recursively for input files
+    #[arg(long, global = true)]
+    pub input_dir: Option<PathBuf>,
+
+    /// Glob matched against paths below `--input-dir`
+    #[arg(long, default_value = "*", requires = "input_dir", global = true)]
+    pub pattern: String,
+
+    /// Encoded payload given inline; P-lines may be separated by newlines or a
+    /// literal `\n`
+    #[arg(long, conflicts_with_all = ["input", "input_dir"], global = true)]
+    pub data: Option<String>,
+
+    /// Decompression applied to the input; `auto` detects gzip and zstd by their magic bytes
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub decompress: Decompress,
+
+    /// Decode a base64 wrapper (optionally a quoted JSON string, or a field of
+    /// a JSON object) before anything else
+    #[arg(long, global = true)]
+    pub base64: bool,
+
+    /// Field of a JSON object input holding the base64 wrapper: a key, or a
+    /// JSON pointer such as `/data/payload` (defaults to the only string of
+    /// the object)
+    #[arg(long, requires = "base64", global = true)]
+    pub base64_field: Option<String>,
+
+    /// Read an HTML page and decode the payload embedded in its first `<script>`
+    /// holding one
+    #[arg(long, global = true)]
+    pub from_html: bool,
+
+    /// CSS selector of the element holding the payload, e.g. `#__DATA__`
+    #[arg(long, requires = "from_html", global = true)]
+    pub html_selector: Option<String>,
+
+    /// Read input files instead of memory-mapping them, for files that may be
+    /// truncated while they are decoded
+    #[arg(long, global = true)]
+    pub no_mmap: bool,
+}
+
+/// An opened input: a plain input file mapped into memory, or a reader.
+pub enum Payload {
+    Mapped(Mmap),
+    Reader(Box<dyn BufRead>),
+}
+
+impl Payload {
+    /// Decodes the payload into a tree.
+    pub fn decode(self, options: DecodeOptions) -> Result<JSONDecoder, DecodeError> {
+        match self {
+            Payload::Mapped(map) => JSONDecoder::from_slice_with_options(&map, options),
+            Payload::Reader(reader) => JSONDecoder::from_reader_with_options(reader, options),
+        }
+    }
+
+    /// The start of the payload, without consuming it.
+    pub fn head(&mut self) -> io::Result<&[u8]> {
+        match self {
+            Payload::Mapped(map) => Ok(map),
+            Payload::Reader(reader) => reader.fill_buf(),
+        }
+    }
+
+    /// Loads the payload to decode it while it is serialized.
+    pub fn stream(self, options: DecodeOptions) -> Result<StreamingDecoder, DecodeError> {
+        match self {
+            Payload::Mapped(map) => StreamingDecoder::from_slice_with_options(&map, options),
+            Payload::Reader(reader) => StreamingDecoder::from_reader_with_options(reader, options),
+        }
+    }
+}
+
+/// Where one payload is read from.
+#[derive(Debug, Clone)]
+pub enum Source {
+    Stdin,
+    Data(String),
+    Url(String),
+    Clipboard,
+    /// A file, with its path below `--input-dir` when found there.
+    File {
+        path: PathBuf,
+        relative: Option<PathBuf>,
+    },
+}
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Source::Stdin => f.write_str("<stdin>"),
+            Source::Data(_) => f.write_str("<data>"),
+            Source::Url(url) => f.write_str(url),
+            Source::Clipboard => f.write_str("<clipboard>"),
+            Source::File { path, .. } => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl InputArgs {
+    /// Returns every payload to process: inputs in command line order, then
+    /// the matching files below `--input-dir` sorted by path.
+    pub fn sources(&self, options: &DecodeOptions) -> Result<Vec<Source>> {
+        if let Some(data) = &self.data {
+            return Ok(vec![Source::Data(inline_payload(data, options))]);
+        }
+
+        let mut sources: Vec<Source> = self
+            .input
+            .iter()
+            .map(|input_path| match is_url(input_path) {
+                true => Source::Url(input_path.to_string_lossy().into_owned()),
+                false if input_path == Path::new(CLIPBOARD) => Source::Clipboard,
+                false => Source::File {
+                    path: input_path.clone(),
+                    relative: None,
+                },
+            })
+            .collect();
+
+        if let Some(input_dir) = &self.input_dir {
+            sources.extend(walk(input_dir, &self.pattern)?);
+        } else if sources.is_empty() {
+            sources.push(Source::Stdin);
+        }
+
+        Ok(sources)
+    }
+
+    // Input files that need no unwrapping are mapped instead of being read, so
+    // that their lines are parsed without copying them
+    pub fn payload(&self, source: &Source) -> Result<Payload> {
+        let plain = !self.no_mmap
+            && !self.base64
+            && !self.from_html
+            && matches!(self.decompress, Decompress::Auto | Decompress::None);
+
+        if let (true, Source::File { path, .. }) = (plain, source) {
+            let f = File::open(path).with_context(|| "Failed to open input file")?;
+
+            if f.metadata().is_ok_and(|m| m.is_file()) {
+                // SAFETY: the mapping is only read while decoding, and is
+                // dropped once the entries are parsed; `--no-mmap` covers
+                // files changed meanwhile
+                let map = unsafe { Mmap::map(&f) }.with_context(|| "Failed to map input file")?;
+                let compressed = self.decompress == Decompress::Auto
+                    && (map.starts_with(GZIP_MAGIC) || map.starts_with(ZSTD_MAGIC));
+
+                if !compressed {
+                    return Ok(Payload::Mapped(map));
+                }
+            }
+        }
+
+        self.open(source).map(Payload::Reader)
+    }
+
+    // Input: inline data, file, URL, clipboard or stdin, unwrapped, decompressed and extracted on the fly
+    pub fn open(&self, source: &Source) -> Result<Box<dyn BufRead>> {
+        let mut reader: Box<dyn BufRead> = match source {
+            Source::Data(data) => Box::new(Cursor::new(data.clone().into_bytes())),
+            Source::Url(url) => {
+                let response = ureq::get(url)
+                    .call()
+                    .with_context(|| format!("Failed to fetch {url}"))?;
+
+                // Content-Encoding is already undone by the HTTP client
+                Box::new(BufReader::new(response.into_body().into_reader()))
+            }
+            Source::Clipboard => {
+                let text = Clipboard::new()
+                    .and_then(|mut clipboard| clipboard.get_text())
+                    .with_context(|| "Failed to read the clipboard")?;
+
+                Box::new(Cursor::new(text.into_bytes()))
+            }
+            Source::File {
+                path: input_path, ..
+            } => {
+                let f = File::open(input_path).with_context(|| "Failed to open input file")?;
+                Box::new(BufReader::new(f))
+            }
+            Source::Stdin => Box::new(io::stdin().lock()),
+        };
+
+        if self.base64 {
+            reader = base64_decoded(reader, self.base64_field.as_deref())?;
+        }
+
+        reader = decompressed(reader, self.decompress)?;
+
+        if self.from_html {
+            let mut html = String::new();
+            reader
+                .read_to_string(&mut html)
+                .with_context(|| "Failed to read HTML input")?;
+
+            let payload = extract_payload(&html, self.html_selector.as_deref())?;
+            reader = Box::new(Cursor::new(payload.into_bytes()));
+        }
+
+        Ok(reader)
+    }
+}
+
+// Shells make real newlines awkward, so an escaped one before a P-line counts;
+// the prefix is matched in any case, as the decoder does, unless strict
+fn inline_payload(data: &str, options: &DecodeOptions) -> String {
+    let flags = if options.strict { "" } else { "(?i)" };
+    let p_line_re = Regex::new(&format!(
+        r"\\n({flags}{}\d+:)",
+        regex::escape(&options.promise_prefix)
+    ))
+    .expect("valid P-line regex");
+    p_line_re.replace_all(data, "\n$1").into_owned()
+}
+
+fn walk(input_dir: &Path, pattern: &str) -> Result<Vec<Source>> {
+    let matcher = Glob::new(pattern)
+        .with_context(|| format!("Invalid pattern {pattern:?}"))?
+        .compile_matcher();
+    let mut sources = Vec::new();
+
+    for entry in WalkDir::new(input_dir).sort_by_file_name() {
+        let entry = entry.with_context(|| "Failed to read input directory")?;
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        let relative = entry
+            .path()
+            .strip_prefix(input_dir)
+            .unwrap_or(entry.path())
+            .to_path_buf();
+
+        if matcher.is_match(&relative) {
+            sources.push(Source::File {
+                path: entry.into_path(),
+                relative: Some(relative),
+            });
+        }
+    }
+
+    Ok(sources)
+}
+
+fn is_url(input: &Path) -> bool {
+    input
+        .to_str()
+        .is_some_and(|s| s.starts_with("http://") || s.starts_with("https://"))
+}
+
+fn base64_decoded(mut reader: Box<dyn BufRead>, field: Option<&str>) -> Result<Box<dyn BufRead>> {
+    let mut text = String::new();
+    reader
+        .read_to_string(&mut text)
+        .with_context(|| "Failed to read base64 input")?;
+
+    // A wrapper copied out of a JSON document may still be a quoted string,
+    // or the document itself
+    if text.trim_start().starts_with(['"', '{']) {
+        let value: Value =
+            serde_json::from_str(&text).context(ReadFailure("Invalid JSON input"))?;
+        text = wrapped_field(value, field)
+            .context(ReadFailure("Failed to find the base64 wrapper"))?;
+    }
+
+    let text: String = text.chars().filter(|c| !c.is_ascii_whitespace()).collect();
+
+    let mut result = BASE64_ENGINES[0].decode(&text);
+
+    if result.is_err() {
+        result = BASE64_ENGINES[1].decode(&text);
+    }
+
+    let data = result.context(ReadFailure("Invalid base64 input"))?;
+
+    Ok(Box::new(Cursor::new(data)))
+}
+
+// The string `value` is, or the one of the object `value` named by `field`,
+// a key or a JSON pointer, which defaults to its only string member
+fn wrapped_field(value: Value, field: Option<&str>) -> Result<String> {
+    let found = match (&value, field) {
+        (Value::String(_), None) => Some(&value),
+        (Value::Object(_), Some(pointer)) if pointer.starts_with('/') => value.pointer(pointer),
+        (Value::Object(obj), Some(key)) => obj.get(key),
+        (Value::Object(obj), None) => {
+            let mut strings = obj.values().filter(|member| member.is_string());
+
+            match (strings.next(), strings.next()) {
+                (Some(member), None) => Some(member),
+                _ => bail!(
+                    "The input object has several strings or none; pick the one holding the \
+                     wrapper with --base64-field"
+                ),
+            }
+        }
+        (_, Some(_)) => bail!("--base64-field needs a JSON object input"),
+        (_, None) => bail!("The input is JSON but neither a string nor an object"),
+    };
+
+    // Only a field that was asked for can be missing or hold something else
+    let field = field.unwrap_or_default();
+    match found {
+        Some(Value::String(s)) => Ok(s.clone()),
+        Some(_) => bail!("Field {field:?} of the input is not a string"),
+        None => bail!("The input has no field {field:?}"),
+    }
+}
+
+fn decompressed(mut reader: Box<dyn BufRead>, decompress: Decompress) -> Result<Box<dyn BufRead>> {
+    let decompress = match decompress {
+        Decompress::Auto => {
+            // Peek without consuming so undetected input is read unchanged
+            let head = reader.fill_buf().with_context(|| "Failed to read input")?;
+
+            match head {
+                h if h.starts_with(GZIP_MAGIC) => Decompress::Gzip,
+                h if h.starts_with(ZSTD_MAGIC) => Decompress::Zstd,
+                _ => Decompress::None,
+            }
+        }
+        d => d,
+    };
+
+    Ok(match decompress {
+        Decompress::Gzip => Box::new(BufReader::new(MultiGzDecoder::new(reader))),
+        Decompress::Zstd => Box::new(BufReader::new(
+            zstd::Decoder::with_buffer(reader).with_context(|| "Failed to start zstd decoder")?,
+        )),
+        Decompress::Brotli => Box::new(BufReader::new(brotli::Decompressor::new(
+            reader,
+            BROTLI_BUFFER_SIZE,
+        ))),
+        Decompress::Auto | Decompress::None => reader,
+    })
+}
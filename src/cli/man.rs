@@ -0,0 +1,22 @@
+#[cfg(feature = "man")]
+use anyhow::Context;
+use anyhow::Result;
+#[cfg(not(feature = "man"))]
+use anyhow::bail;
+use clap::Command;
+use std::path::Path;
+
+/// Writes the man page of `command`, and one for each of its subcommands,
+/// below `dir`.
+#[cfg(feature = "man")]
+pub fn generate_man(command: Command, dir: &Path) -> Result<()> {
+    std::fs::create_dir_all(dir)
+        .and_then(|()| clap_mangen::generate_to(command, dir))
+        .with_context(|| format!("Failed to write man pages to {}", dir.display()))
+}
+
+/// Fails, as man pages are only generated by builds with the `man` feature.
+#[cfg(not(feature = "man"))]
+pub fn generate_man(_command: Command, _dir: &Path) -> Result<()> {
+    bail!("--generate-man requires a build with the man feature")
+}
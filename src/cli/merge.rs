@@ -0,0 +1,63 @@
+use anyhow::{Result, bail};
+use clap::ValueEnum;
+use json_decoder::{PathSegment, format_path};
+use serde_json::Value;
+
+/// How values found at the same path of several inputs are combined.
+///
+/// Objects are always merged key by key; the strategy decides what happens to
+/// every other pair of values.
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// The value of the later input replaces the earlier one
+    #[default]
+    LastWins,
+    /// Different values at the same path are an error
+    ErrorOnConflict,
+    /// Arrays are concatenated; any other value is replaced by the later one
+    ArrayConcat,
+}
+
+/// Deep-merges `value` into `target` according to `strategy`.
+pub fn merge(target: &mut Value, value: Value, strategy: MergeStrategy) -> Result<()> {
+    merge_at(target, value, strategy, &mut Vec::new())
+}
+
+fn merge_at(
+    target: &mut Value,
+    value: Value,
+    strategy: MergeStrategy,
+    path: &mut Vec<PathSegment>,
+) -> Result<()> {
+    match (target, value) {
+        (Value::Object(target_obj), Value::Object(obj)) => {
+            for (key, item) in obj {
+                match target_obj.get_mut(&key) {
+                    Some(target_item) => {
+                        path.push(PathSegment::Key(key));
+                        merge_at(target_item, item, strategy, path)?;
+                        path.pop();
+                    }
+                    None => {
+                        target_obj.insert(key, item);
+                    }
+                }
+            }
+        }
+        (Value::Array(target_arr), Value::Array(arr)) if strategy == MergeStrategy::ArrayConcat => {
+            target_arr.extend(arr);
+        }
+        (target, value) if *target == value => {}
+        (target, value) => match strategy {
+            MergeStrategy::ErrorOnConflict => {
+                bail!(
+                    "Conflicting values at {}: {target} and {value}",
+                    format_path(path)
+                )
+            }
+            MergeStrategy::LastWins | MergeStrategy::ArrayConcat => *target = value,
+        },
+    }
+
+    Ok(())
+}
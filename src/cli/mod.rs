@@ -0,0 +1,26 @@
+pub mod browse;
+pub mod codegen;
+pub mod color;
+pub mod config;
+pub mod destination;
+pub mod diagnostic;
+pub mod diff;
+pub mod dot;
+pub mod flatten;
+pub mod html;
+pub mod input;
+pub mod man;
+pub mod merge;
+pub mod output;
+pub mod pager;
+pub mod query;
+pub mod repl;
+pub mod schema;
+pub mod stats;
+pub mod timings;
+pub mod transform;
+pub mod tree;
+pub mod unused;
+pub mod validate;
+pub mod warnings;
+pub mod watch;
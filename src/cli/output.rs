@@ -0,0 +1,301 @@
+use super::{
+    color::{ColorChoice, ColorFormatter},
+    diagnostic::WriteFailure,
+    tree::write_tree,
+};
+use anyhow::{Context, Result, bail};
+use clap::{Args, ValueEnum};
+use json_decoder::{NativeNumbers, PathSegment, format_path, write_canonical};
+use serde::Serialize;
+use serde_json::{
+    Serializer, Value,
+    ser::{CompactFormatter, PrettyFormatter},
+};
+use std::{collections::HashSet, io::Write};
+
+// Offending paths listed before the rest are summarized
+const MAX_REPORTED_PATHS: usize = 10;
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputFormat {
+    #[default]
+    Json,
+    Toml,
+    Msgpack,
+    Cbor,
+    Csv,
+}
+
+#[derive(Args, Debug)]
+pub struct FormatArgs {
+    /// Format of the decoded output
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub output_format: OutputFormat,
+
+    /// Emit minified JSON without whitespace
+    #[arg(long, global = true)]
+    pub compact: bool,
+
+    /// Indentation of pretty-printed output: a number of spaces, `tab`, or a
+    /// literal whitespace string
+    #[arg(long, default_value = "4", value_parser = parse_indent, conflicts_with = "compact", global = true)]
+    pub indent: String,
+
+    /// Recursively sort object keys
+    #[arg(long, conflicts_with = "stream", global = true)]
+    pub sort_keys: bool,
+
+    /// Emit canonical JSON (RFC 8785 / JCS) for byte-stable output
+    #[arg(long, conflicts_with_all = ["stream", "compact", "indent"], global = true)]
+    pub canonical: bool,
+
+    /// Emit each element of a top-level array as compact JSON on its own line
+    #[arg(long, conflicts_with_all = ["stream", "output_format", "compact", "indent", "canonical"], global = true)]
+    pub ndjson: bool,
+
+    /// Write an outline of the keys, types and sizes of the result instead
+    /// of the result
+    #[arg(long, conflicts_with_all = ["stream", "output_format", "canonical", "ndjson", "compact"], global = true)]
+    pub tree: bool,
+
+    /// Field delimiter of CSV output: a single ASCII character or `tab`
+    #[arg(long, default_value = ",", value_parser = parse_delimiter, global = true)]
+    pub csv_delimiter: u8,
+
+    /// Text written for null and missing fields of CSV output
+    #[arg(long, default_value = "", global = true)]
+    pub csv_null: String,
+
+    /// When to color keys, strings, numbers and literals of pretty-printed
+    /// and compact JSON
+    #[arg(long, value_enum, default_value_t, global = true)]
+    pub color: ColorChoice,
+
+    /// Whether JSON output is colored, `--color` applied to the destination
+    #[arg(skip)]
+    pub colored: bool,
+}
+
+impl FormatArgs {
+    /// Writes a fully decoded value in the selected output format.
+    pub fn write_value(&self, value: &Value, writer: &mut dyn Write) -> Result<()> {
+        match self.output_format {
+            _ if self.tree => write_tree(value, writer),
+            OutputFormat::Json if self.ndjson => write_ndjson(value, writer),
+            OutputFormat::Json if self.canonical => write_canonical(value, writer)
+                .context(WriteFailure("Failed to write canonical JSON data")),
+            OutputFormat::Json => self.write_streamed(value, writer),
+            OutputFormat::Msgpack | OutputFormat::Cbor => {
+                self.write_streamed(&NativeNumbers(value), writer)
+            }
+            OutputFormat::Csv => self.write_csv(value, writer),
+            OutputFormat::Toml => {
+                check_toml(value)?;
+
+                let toml = toml::to_string_pretty(&NativeNumbers(value))
+                    .with_context(|| "Decoded data is not representable as TOML")?;
+
+                writer
+                    .write_all(toml.as_bytes())
+                    .context(WriteFailure("Failed to write TOML data"))
+            }
+        }
+    }
+
+    /// Whether [`write_streamed`](Self::write_streamed) produces the selected
+    /// output.
+    pub fn streams(&self) -> bool {
+        match self.output_format {
+            _ if self.tree => false,
+            OutputFormat::Json => !self.ndjson && !self.canonical,
+            OutputFormat::Msgpack | OutputFormat::Cbor => true,
+            OutputFormat::Toml | OutputFormat::Csv => false,
+        }
+    }
+
+    /// Writes `value` without materializing it first, for the output formats
+    /// that can be produced in a single pass.
+    pub fn write_streamed<T: Serialize>(&self, value: &T, writer: &mut dyn Write) -> Result<()> {
+        match self.output_format {
+            OutputFormat::Json => self.write_json(value, writer),
+            OutputFormat::Msgpack => write_msgpack(value, writer),
+            OutputFormat::Cbor => write_cbor(value, writer),
+            OutputFormat::Toml | OutputFormat::Csv => {
+                let name = self
+                    .output_format
+                    .to_possible_value()
+                    .map(|v| v.get_name().to_string());
+                bail!(
+                    "--stream does not support {} output",
+                    name.unwrap_or_default()
+                )
+            }
+        }
+    }
+
+    // One row per element of a top-level array of objects, with the union of
+    // their keys as header
+    fn write_csv(&self, value: &Value, writer: &mut dyn Write) -> Result<()> {
+        let Value::Array(items) = value else {
+            bail!("CSV output requires the decoded root to be an array of objects");
+        };
+
+        let mut rows = Vec::with_capacity(items.len());
+        let mut header: Vec<&str> = Vec::new();
+        let mut seen = HashSet::new();
+
+        for (i, item) in items.iter().enumerate() {
+            let Value::Object(row) = item else {
+                let path = format_path(&[PathSegment::Index(i)]);
+                bail!("CSV output requires an array of objects, but {path} is not an object");
+            };
+
+            for key in row.keys() {
+                if seen.insert(key.as_str()) {
+                    header.push(key);
+                }
+            }
+
+            rows.push(row);
+        }
+
+        let mut csv = csv::WriterBuilder::new()
+            .delimiter(self.csv_delimiter)
+            .from_writer(writer);
+
+        csv.write_record(&header)
+            .context(WriteFailure("Failed to write CSV data"))?;
+
+        for row in rows {
+            csv.write_record(header.iter().map(|key| match row.get(*key) {
+                None | Some(Value::Null) => self.csv_null.clone(),
+                Some(Value::String(s)) => s.clone(),
+                // Nested arrays and objects are kept as compact JSON
+                Some(v) => v.to_string(),
+            }))
+            .context(WriteFailure("Failed to write CSV data"))?;
+        }
+
+        csv.flush()
+            .context(WriteFailure("Failed to write CSV data"))
+    }
+
+    // Pretty-printed unless `--compact` was given
+    fn write_json<T: Serialize>(&self, value: &T, writer: &mut dyn Write) -> Result<()> {
+        let pretty = || PrettyFormatter::with_indent(self.indent.as_bytes());
+        let result = match (self.compact, self.colored) {
+            (true, false) => value.serialize(&mut Serializer::new(writer)),
+            (true, true) => {
+                let formatter = ColorFormatter::new(CompactFormatter);
+                value.serialize(&mut Serializer::with_formatter(writer, formatter))
+            }
+            (false, false) => value.serialize(&mut Serializer::with_formatter(writer, pretty())),
+            (false, true) => {
+                let formatter = ColorFormatter::new(pretty());
+                value.serialize(&mut Serializer::with_formatter(writer, formatter))
+            }
+        };
+
+        result.context(WriteFailure("Failed to write JSON data"))
+    }
+}
+
+fn write_ndjson(value: &Value, writer: &mut dyn Write) -> Result<()> {
+    let Value::Array(items) = value else {
+        bail!("--ndjson requires the decoded root to be an array");
+    };
+
+    for item in items {
+        serde_json::to_writer(&mut *writer, item)
+            .context(WriteFailure("Failed to write NDJSON data"))?;
+        writer
+            .write_all(b"\n")
+            .context(WriteFailure("Failed to write NDJSON data"))?;
+    }
+
+    Ok(())
+}
+
+fn write_msgpack<T: Serialize>(value: &T, writer: &mut dyn Write) -> Result<()> {
+    rmp_serde::encode::write(writer, value)
+        .context(WriteFailure("Failed to write MessagePack data"))
+}
+
+fn write_cbor<T: Serialize>(value: &T, writer: &mut dyn Write) -> Result<()> {
+    ciborium::into_writer(value, writer).context(WriteFailure("Failed to write CBOR data"))
+}
+
+fn parse_delimiter(s: &str) -> Result<u8, String> {
+    match s.as_bytes() {
+        b"tab" => Ok(b'\t'),
+        [c] if c.is_ascii() => Ok(*c),
+        _ => Err(String::from("expected a single ASCII character or `tab`")),
+    }
+}
+
+fn parse_indent(s: &str) -> Result<String, String> {
+    match s {
+        "tab" => Ok(String::from("\t")),
+        s if s.chars().all(|c| c.is_ascii_digit()) => s
+            .parse::<usize>()
+            .map(|n| " ".repeat(n))
+            .map_err(|e| e.to_string()),
+        s if s.chars().all(|c| c == ' ' || c == '\t') => Ok(s.to_string()),
+        _ => Err(String::from(
+            "expected a number of spaces, `tab`, or whitespace",
+        )),
+    }
+}
+
+// TOML documents are tables and have no null; report every value that breaks
+// either rule instead of the serializer's first failure
+fn check_toml(value: &Value) -> Result<()> {
+    let mut offenders = Vec::new();
+
+    match value {
+        Value::Object(_) => toml_offenders(value, &mut Vec::new(), &mut offenders),
+        _ => offenders.push(String::from("$: root must be an object")),
+    }
+
+    if offenders.is_empty() {
+        return Ok(());
+    }
+
+    let mut message = String::from("Decoded data is not representable as TOML:");
+
+    for offender in offenders.iter().take(MAX_REPORTED_PATHS) {
+        message.push_str("\n  ");
+        message.push_str(offender);
+    }
+
+    if offenders.len() > MAX_REPORTED_PATHS {
+        let more = offenders.len() - MAX_REPORTED_PATHS;
+        message.push_str(&format!("\n  ... and {more} more"));
+    }
+
+    bail!(message)
+}
+
+fn toml_offenders(value: &Value, path: &mut Vec<PathSegment>, offenders: &mut Vec<String>) {
+    match value {
+        Value::Null => offenders.push(format!(
+            "{}: null has no TOML equivalent",
+            format_path(path)
+        )),
+        Value::Array(arr) => {
+            for (i, item) in arr.iter().enumerate() {
+                path.push(PathSegment::Index(i));
+                toml_offenders(item, path, offenders);
+                path.pop();
+            }
+        }
+        Value::Object(obj) => {
+            for (key, item) in obj {
+                path.push(PathSegment::Key(key.clone()));
+                toml_offenders(item, path, offenders);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
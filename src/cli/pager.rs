@@ -0,0 +1,78 @@
+use std::{
+    env,
+    io::{self, BufWriter, Write},
+    process::{Child, ChildStdin, Command, Stdio},
+};
+
+// Used when `$PAGER` is not set
+const DEFAULT_PAGER: &str = "less";
+
+// Like git: quit when the output fits on one screen, keep colors and leave
+// the output on the screen once quit
+const DEFAULT_LESS: &str = "FRX";
+
+/// A pager showing what is written to it, waited for once dropped.
+pub struct Pager {
+    stdin: Option<BufWriter<ChildStdin>>,
+    child: Child,
+}
+
+impl Pager {
+    /// Starts `$PAGER`, or `less`, with the terminal as its output. Returns
+    /// `None` when paging is disabled by an empty `$PAGER` or `cat`, or the
+    /// pager can not be started.
+    pub fn spawn() -> Option<Self> {
+        let pager = env::var("PAGER").unwrap_or_else(|_| DEFAULT_PAGER.to_owned());
+        let mut words = pager.split_whitespace();
+        let program = words.next().filter(|&program| program != "cat")?;
+
+        let mut command = Command::new(program);
+        command.args(words).stdin(Stdio::piped());
+
+        if env::var_os("LESS").is_none() {
+            command.env("LESS", DEFAULT_LESS);
+        }
+
+        let mut child = command.spawn().ok()?;
+        let stdin = child.stdin.take().map(BufWriter::new);
+
+        Some(Pager { stdin, child })
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let Some(stdin) = &mut self.stdin else {
+            return Ok(buf.len());
+        };
+
+        match stdin.write(buf) {
+            // The pager was quit before the end of the output, which is
+            // dropped
+            Err(error) if error.kind() == io::ErrorKind::BrokenPipe => {
+                self.stdin = None;
+                Ok(buf.len())
+            }
+            result => result,
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self.stdin.as_mut().map(Write::flush) {
+            Some(Err(error)) if error.kind() == io::ErrorKind::BrokenPipe => {
+                self.stdin = None;
+                Ok(())
+            }
+            Some(result) => result,
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for Pager {
+    // The terminal is left to the pager until it is quit
+    fn drop(&mut self) {
+        drop(self.stdin.take());
+        let _ = self.child.wait();
+    }
+}
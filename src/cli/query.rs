@@ -0,0 +1,163 @@
+use anyhow::{Context, Result, anyhow};
+use clap::Args;
+use jaq_core::{
+    Compiler, Ctx, Filter, Vars, data,
+    load::{Arena, File, Loader},
+    unwrap_valr,
+};
+use jaq_json::Val;
+use serde::Deserialize;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+
+type JqFilter = Filter<data::JustLut<Val>>;
+
+#[derive(Args, Debug)]
+pub struct QueryArgs {
+    /// JSON Pointer (RFC 6901) of the subtree to keep, applied before any query
+    #[arg(long, value_parser = parse_pointer, conflicts_with = "stream", global = true)]
+    pub pointer: Option<String>,
+
+    /// JSONPath (RFC 9535) query; the matching values are written as an array
+    #[arg(long, value_parser = parse_json_path, conflicts_with = "stream", global = true)]
+    pub query: Option<JsonPath>,
+
+    /// jq filter; several outputs are written as an array
+    #[arg(long, value_parser = parse_filter, conflicts_with = "stream", global = true)]
+    pub filter: Option<String>,
+
+    /// JMESPath expression
+    #[arg(long, value_parser = parse_jmespath, conflicts_with = "stream", global = true)]
+    pub jmespath: Option<String>,
+}
+
+impl QueryArgs {
+    /// Whether the decoded value is kept whole.
+    pub fn is_empty(&self) -> bool {
+        self.pointer.is_none()
+            && self.query.is_none()
+            && self.filter.is_none()
+            && self.jmespath.is_none()
+    }
+
+    /// Narrows the decoded `value` down to the requested parts.
+    pub fn apply(&self, mut value: Value) -> Result<Value> {
+        if let Some(pointer) = &self.pointer {
+            value = value
+                .pointer_mut(pointer)
+                .map(Value::take)
+                .with_context(|| format!("JSON Pointer {pointer:?} does not match any value"))?;
+        }
+
+        if let Some(query) = &self.query {
+            value = run_json_path(query, &value);
+        }
+
+        if let Some(filter) = &self.filter {
+            value = run_filter(filter, value)?;
+        }
+
+        if let Some(expression) = &self.jmespath {
+            value = run_jmespath(expression, &value)?;
+        }
+
+        Ok(value)
+    }
+}
+
+/// Collects the values matching a JSONPath query into an array.
+pub fn run_json_path(query: &JsonPath, value: &Value) -> Value {
+    query.query(value).all().into_iter().cloned().collect()
+}
+
+/// Evaluates a JMESPath expression against `value`.
+pub fn run_jmespath(expression: &str, value: &Value) -> Result<Value> {
+    let expression = jmespath::compile(expression)?;
+    let result = expression
+        .search(value)
+        .with_context(|| "Failed to evaluate JMESPath expression")?;
+
+    Ok(serde_json::to_value(&*result)?)
+}
+
+/// Runs a jq filter over `value`; several outputs are collected into an array.
+pub fn run_filter(code: &str, value: Value) -> Result<Value> {
+    let filter = compile_filter(code)?;
+    let ctx = Ctx::<data::JustLut<Val>>::new(&filter.lut, Vars::new([]));
+    let input = Val::deserialize(value).with_context(|| "Failed to prepare filter input")?;
+
+    let mut outputs = filter
+        .id
+        .run((ctx, input))
+        .map(unwrap_valr)
+        .map(|output| {
+            let output = output.map_err(|e| anyhow!("{e}"))?;
+
+            // jaq values print as JSON
+            serde_json::from_str(&output.to_string())
+                .with_context(|| format!("Filter output {output} is not JSON"))
+        })
+        .collect::<Result<Vec<Value>>>()
+        .with_context(|| "Failed to run filter")?;
+
+    Ok(match outputs.len() {
+        1 => outputs.remove(0),
+        _ => Value::Array(outputs),
+    })
+}
+
+fn parse_pointer(s: &str) -> Result<String, String> {
+    match s.is_empty() || s.starts_with('/') {
+        true => Ok(s.to_string()),
+        false => Err(String::from(
+            "expected an empty pointer or one starting with `/`",
+        )),
+    }
+}
+
+fn parse_json_path(s: &str) -> Result<JsonPath, String> {
+    JsonPath::parse(s).map_err(|e| e.to_string())
+}
+
+// Expressions are not thread-safe, so they are compiled for every input
+fn parse_jmespath(s: &str) -> Result<String, String> {
+    jmespath::compile(s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+// Compiled again for every input, this only reports errors early
+fn parse_filter(s: &str) -> Result<String, String> {
+    compile_filter(s)
+        .map(|_| s.to_string())
+        .map_err(|e| e.to_string())
+}
+
+fn compile_filter(code: &str) -> Result<JqFilter> {
+    let defs = jaq_core::defs()
+        .chain(jaq_std::defs())
+        .chain(jaq_json::defs());
+    let funs = jaq_core::funs()
+        .chain(jaq_std::funs())
+        .chain(jaq_json::funs());
+
+    let arena = Arena::default();
+    let modules = Loader::new(defs)
+        .load(&arena, File { code, path: () })
+        .map_err(|errors| {
+            let errors: Vec<String> = errors.iter().map(|(_, e)| format!("{e:?}")).collect();
+            anyhow!("invalid filter: {}", errors.join(", "))
+        })?;
+
+    Compiler::default()
+        .with_funs(funs)
+        .compile(modules)
+        .map_err(|errors| {
+            let names: Vec<String> = errors
+                .iter()
+                .flat_map(|(_, errors)| errors.iter())
+                .map(|(name, undefined)| format!("{undefined:?} `{name}`"))
+                .collect();
+            anyhow!("undefined in filter: {}", names.join(", "))
+        })
+}
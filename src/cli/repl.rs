@@ -0,0 +1,97 @@
+use super::{
+    diagnostic::WriteFailure,
+    output::FormatArgs,
+    query::{run_filter, run_jmespath, run_json_path},
+};
+use anyhow::{Context, Result, bail};
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+const HELP: &str = "\
+Expressions are evaluated against the decoded value:
+  /a/b          JSON Pointer
+  $.a[*].b      JSONPath query, matches are printed as an array
+  :jmespath E   JMESPath expression (alias :jp)
+  anything else jq filter, e.g. `.a | keys`
+Commands:
+  :help         show this help
+  :quit         exit (also Ctrl-D)";
+
+/// Reads expressions from stdin and prints their result against `value`
+/// until end of input.
+pub fn repl(value: &Value, format: &FormatArgs) -> Result<()> {
+    let interactive = io::stdin().is_terminal();
+    let mut lines = io::stdin().lock().lines();
+
+    loop {
+        // The prompt goes to stderr to keep piped results clean
+        if interactive {
+            eprint!("> ");
+        }
+
+        let Some(line) = lines.next() else {
+            break;
+        };
+
+        let line = line.with_context(|| "Failed to read expression")?;
+        let expression = line.trim();
+
+        match expression {
+            "" => continue,
+            ":quit" | ":q" => break,
+            ":help" | ":h" => {
+                eprintln!("{HELP}");
+                continue;
+            }
+            _ => {}
+        }
+
+        let result = evaluate(expression, value).and_then(|result| write_result(result, format));
+
+        if let Err(e) = result {
+            eprintln!("Error: {e:#}");
+        }
+    }
+
+    Ok(())
+}
+
+/// Evaluates one expression against `value`: a JSON Pointer, a JSONPath
+/// query, `:jmespath <EXPR>` or a jq filter.
+pub fn evaluate(expression: &str, value: &Value) -> Result<Value> {
+    if let Some(jmespath) = expression
+        .strip_prefix(":jmespath ")
+        .or_else(|| expression.strip_prefix(":jp "))
+    {
+        return run_jmespath(jmespath.trim(), value);
+    }
+
+    match expression.chars().next() {
+        Some('/') => value
+            .pointer(expression)
+            .cloned()
+            .with_context(|| format!("JSON Pointer {expression:?} does not match any value")),
+        Some('$') => {
+            let query = JsonPath::parse(expression)
+                .with_context(|| format!("Invalid JSONPath query {expression:?}"))?;
+            Ok(run_json_path(&query, value))
+        }
+        Some(':') => bail!("Unknown command {expression:?}, see :help"),
+        _ => run_filter(expression, value.clone()),
+    }
+}
+
+fn write_result(mut result: Value, format: &FormatArgs) -> Result<()> {
+    if format.sort_keys {
+        result.sort_all_objects();
+    }
+
+    let mut stdout = io::stdout().lock();
+    format.write_value(&result, &mut stdout)?;
+
+    stdout
+        .write_all(b"\n")
+        .and_then(|()| stdout.flush())
+        .context(WriteFailure("Failed to write output"))
+}
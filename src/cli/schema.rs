@@ -0,0 +1,176 @@
+use serde_json::{Map, Number, Value, json};
+use std::collections::HashMap;
+
+// Dialect of the schemas written
+const DRAFT: &str = "https://json-schema.org/draft/2020-12/schema";
+
+/// The types found at one place of one or more values, merged so that an
+/// array is described by a single shape of its elements.
+#[derive(Debug, Default)]
+pub struct Shape {
+    pub null: bool,
+    pub boolean: bool,
+    pub integer: bool,
+    /// Integers below zero, above `i64::MAX`, and fitting in neither `i64`
+    /// nor `u64`.
+    pub negative: bool,
+    pub above_i64: bool,
+    pub beyond_64_bits: bool,
+    pub number: bool,
+    pub string: bool,
+    pub object: Option<ObjectShape>,
+    pub array: Option<ArrayShape>,
+}
+
+/// The members found in the objects at one place, in the order first seen.
+#[derive(Debug, Default)]
+pub struct ObjectShape {
+    pub properties: Vec<Property>,
+    // Position of every key in `properties`
+    index: HashMap<String, usize>,
+    // Number of objects merged
+    count: usize,
+}
+
+#[derive(Debug)]
+pub struct Property {
+    pub key: String,
+    pub shape: Shape,
+    // Number of objects having the key
+    count: usize,
+}
+
+#[derive(Debug, Default)]
+pub struct ArrayShape {
+    /// Shape of every element, `None` when all arrays are empty.
+    pub items: Option<Box<Shape>>,
+}
+
+impl Shape {
+    /// The shape of `value`.
+    pub fn of(value: &Value) -> Self {
+        let mut shape = Shape::default();
+        shape.add(value);
+        shape
+    }
+
+    /// Merges the shape of `value` into this one.
+    pub fn add(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.null = true,
+            Value::Bool(_) => self.boolean = true,
+            Value::Number(n) if is_integer(n) => {
+                self.integer = true;
+                self.negative |= n.as_i64().is_some_and(|i| i < 0);
+                self.above_i64 |= !n.is_i64() && n.is_u64();
+                self.beyond_64_bits |= !n.is_i64() && !n.is_u64();
+            }
+            Value::Number(_) => self.number = true,
+            Value::String(_) => self.string = true,
+            Value::Array(arr) => {
+                let array = self.array.get_or_insert_default();
+
+                for item in arr {
+                    array.items.get_or_insert_default().add(item);
+                }
+            }
+            Value::Object(obj) => self.object.get_or_insert_default().add(obj),
+        }
+    }
+
+    /// Writes this shape as a JSON Schema, such as `{"type": "string"}`.
+    pub fn to_schema(&self) -> Value {
+        let mut schema = Map::new();
+
+        let types: Vec<&str> = [
+            (self.null, "null"),
+            (self.boolean, "boolean"),
+            // Integers are numbers too
+            (self.integer && !self.number, "integer"),
+            (self.number, "number"),
+            (self.string, "string"),
+            (self.array.is_some(), "array"),
+            (self.object.is_some(), "object"),
+        ]
+        .into_iter()
+        .filter_map(|(found, name)| found.then_some(name))
+        .collect();
+
+        match types.as_slice() {
+            [] => {}
+            [name] => {
+                schema.insert("type".into(), json!(name));
+            }
+            names => {
+                schema.insert("type".into(), json!(names));
+            }
+        }
+
+        if let Some(items) = self.array.as_ref().and_then(|array| array.items.as_ref()) {
+            schema.insert("items".into(), items.to_schema());
+        }
+
+        if let Some(object) = &self.object {
+            let properties: Map<String, Value> = object
+                .properties
+                .iter()
+                .map(|property| (property.key.clone(), property.shape.to_schema()))
+                .collect();
+            let required: Vec<&str> = object
+                .properties
+                .iter()
+                .filter(|property| object.is_required(property))
+                .map(|property| property.key.as_str())
+                .collect();
+
+            schema.insert("properties".into(), Value::Object(properties));
+            schema.insert("required".into(), json!(required));
+        }
+
+        Value::Object(schema)
+    }
+}
+
+impl ObjectShape {
+    fn add(&mut self, obj: &Map<String, Value>) {
+        self.count += 1;
+
+        for (key, item) in obj {
+            let i = *self.index.entry(key.clone()).or_insert_with(|| {
+                self.properties.push(Property {
+                    key: key.clone(),
+                    shape: Shape::default(),
+                    count: 0,
+                });
+                self.properties.len() - 1
+            });
+
+            let property = &mut self.properties[i];
+            property.shape.add(item);
+            property.count += 1;
+        }
+    }
+
+    /// Whether every object merged has the key of `property`.
+    pub fn is_required(&self, property: &Property) -> bool {
+        property.count == self.count
+    }
+}
+
+/// Infers a draft 2020-12 JSON Schema describing `value`: the types found at
+/// every place, the keys every object has, and one shape of the elements of
+/// every array.
+pub fn infer_schema(value: &Value) -> Value {
+    let mut schema = Map::from_iter([("$schema".to_string(), json!(DRAFT))]);
+
+    if let Value::Object(inferred) = Shape::of(value).to_schema() {
+        schema.extend(inferred);
+    }
+
+    Value::Object(schema)
+}
+
+// Written without a fraction or exponent, whatever its size
+fn is_integer(n: &Number) -> bool {
+    n.is_i64() || n.is_u64() || !n.to_string().contains(['.', 'e', 'E'])
+}
@@ -0,0 +1,131 @@
+use super::diagnostic::WriteFailure;
+use anyhow::{Context, Result};
+use json_decoder::{DecodeOptions, JSONDecoder, ReferenceKind};
+use serde_json::Value;
+use std::{
+    collections::HashMap,
+    io::{BufRead, Write},
+};
+
+/// Decodes one payload and writes metrics about its structure: how many
+/// entries it has, how much they are shared and what the encoding saves.
+pub fn stats(
+    mut reader: Box<dyn BufRead>,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+) -> Result<()> {
+    let mut encoded = Vec::new();
+    reader
+        .read_to_end(&mut encoded)
+        .with_context(|| "Failed to read input")?;
+
+    let decoder = JSONDecoder::from_reader_with_options(encoded.as_slice(), options)?;
+    let decoded_size = serde_json::to_vec(decoder.decoded_data())?.len();
+    let decoded_values = count_values(decoder.decoded_data());
+
+    // Only entries reachable from the root take part in decoding
+    let mut children: HashMap<usize, Vec<usize>> = HashMap::new();
+    let mut incoming: HashMap<usize, usize> = HashMap::from([(0, 0)]);
+    let mut pending = vec![0];
+    let mut references = 0;
+
+    while let Some(index) = pending.pop() {
+        let entry_children = children.entry(index).or_default();
+
+        for reference in decoder.references(index)? {
+            references += 1;
+            entry_children.push(reference.to);
+
+            let count = incoming.entry(reference.to).or_insert(0);
+            *count += 1;
+
+            // Keys are plain strings and never reference anything further
+            if *count == 1 && reference.kind != ReferenceKind::Key {
+                pending.push(reference.to);
+            }
+        }
+    }
+
+    let entries = decoder.encoded_list().len();
+    let reachable = incoming.len();
+    let shared = incoming.values().filter(|&&count| count > 1).count();
+    let depth = max_depth(&children);
+
+    let lines = [
+        format!("Entries:             {entries}"),
+        format!("Reachable entries:   {reachable}"),
+        format!("References:          {references}"),
+        format!("Shared entries:      {shared}"),
+        format!(
+            "Dedup ratio:         {:.2} decoded values per entry",
+            decoded_values as f64 / reachable as f64
+        ),
+        format!("Max reference depth: {depth}"),
+        format!("Encoded size:        {} bytes", encoded.len()),
+        format!(
+            "Decoded size:        {decoded_size} bytes ({:.2}x)",
+            decoded_size as f64 / encoded.len().max(1) as f64
+        ),
+    ];
+
+    for line in lines {
+        writeln!(writer, "{line}").context(WriteFailure("Failed to write stats"))?;
+    }
+
+    Ok(())
+}
+
+// Values in the decoded tree, object keys included
+fn count_values(value: &Value) -> usize {
+    let mut count = 0;
+    let mut stack = vec![value];
+
+    while let Some(value) = stack.pop() {
+        count += 1;
+
+        match value {
+            Value::Array(arr) => stack.extend(arr),
+            Value::Object(obj) => {
+                count += obj.len();
+                stack.extend(obj.values());
+            }
+            _ => {}
+        }
+    }
+
+    count
+}
+
+// Longest chain of references starting at the root; decoding already
+// rejected cycles, so the graph is acyclic
+fn max_depth(children: &HashMap<usize, Vec<usize>>) -> usize {
+    let mut depths: HashMap<usize, usize> = HashMap::new();
+    let mut stack = vec![(0, false)];
+
+    while let Some((index, visited)) = stack.pop() {
+        let targets = children.get(&index).map(Vec::as_slice).unwrap_or_default();
+
+        match visited {
+            true => {
+                let depth = targets
+                    .iter()
+                    .map(|target| depths.get(target).map_or(1, |depth| depth + 1))
+                    .max()
+                    .unwrap_or(0);
+                depths.insert(index, depth);
+            }
+            false if !depths.contains_key(&index) => {
+                stack.push((index, true));
+                stack.extend(
+                    targets
+                        .iter()
+                        .filter(|target| !depths.contains_key(target))
+                        .map(|&target| (target, false)),
+                );
+            }
+            false => {}
+        }
+    }
+
+    depths.get(&0).copied().unwrap_or(0)
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{self, Write},
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    time::{Duration, Instant},
+};
+use tracing::{Subscriber, span};
+use tracing_subscriber::{Layer, filter::filter_fn, layer::Context, registry::LookupSpan};
+
+// Totals over every input of the run, in nanoseconds and bytes
+static PARSE: AtomicU64 = AtomicU64::new(0);
+static DECODE: AtomicU64 = AtomicU64::new(0);
+static SERIALIZE: AtomicU64 = AtomicU64::new(0);
+static STREAM: AtomicU64 = AtomicU64::new(0);
+static PEAK_ENTRIES: AtomicUsize = AtomicUsize::new(0);
+static OUTPUT_SIZE: AtomicU64 = AtomicU64::new(0);
+
+/// A layer timing the library's `load` and decoding spans.
+pub fn layer<S: Subscriber + for<'a> LookupSpan<'a>>() -> impl Layer<S> {
+    SpanTimer.with_filter(filter_fn(|metadata| {
+        SpanTimer::total(metadata.name()).is_some()
+    }))
+}
+
+/// Adds the time spent writing one output.
+pub fn add_serialize(elapsed: Duration) {
+    add(&SERIALIZE, elapsed);
+}
+
+/// Records the size of one entry table.
+pub fn add_entries(entries: usize) {
+    PEAK_ENTRIES.fetch_max(entries, Ordering::Relaxed);
+}
+
+/// Prints the totals of the run to stderr.
+pub fn report() {
+    let nanos = |total: &AtomicU64| Duration::from_nanos(total.load(Ordering::Relaxed));
+
+    let streamed = STREAM.load(Ordering::Relaxed) > 0;
+    let decoded = !streamed || DECODE.load(Ordering::Relaxed) > 0;
+
+    let lines = [
+        Some(format!("Parse time:              {:.2?}", nanos(&PARSE))),
+        decoded.then(|| format!("Decode time:             {:.2?}", nanos(&DECODE))),
+        decoded.then(|| format!("Serialize time:          {:.2?}", nanos(&SERIALIZE))),
+        // Streamed references are resolved while the output is written
+        streamed.then(|| format!("Decode + serialize time: {:.2?}", nanos(&STREAM))),
+        Some(format!(
+            "Peak entries:            {}",
+            PEAK_ENTRIES.load(Ordering::Relaxed)
+        )),
+        Some(format!(
+            "Output size:             {} bytes",
+            OUTPUT_SIZE.load(Ordering::Relaxed)
+        )),
+    ];
+
+    for line in lines.into_iter().flatten() {
+        eprintln!("{line}");
+    }
+}
+
+fn add(total: &AtomicU64, elapsed: Duration) {
+    let nanos = u64::try_from(elapsed.as_nanos()).unwrap_or(u64::MAX);
+    total.fetch_add(nanos, Ordering::Relaxed);
+}
+
+/// Counts the bytes written to the output.
+pub struct Counted<'a> {
+    pub inner: &'a mut dyn Write,
+}
+
+impl Write for Counted<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        OUTPUT_SIZE.fetch_add(written as u64, Ordering::Relaxed);
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+// Measures the library spans; `stream` resolves references while the output
+// is written, so it is timed apart from both decoding and serialization
+struct SpanTimer;
+
+impl SpanTimer {
+    fn total(name: &str) -> Option<&'static AtomicU64> {
+        match name {
+            "load" => Some(&PARSE),
+            "decode" | "deserialize" => Some(&DECODE),
+            "stream" => Some(&STREAM),
+            _ => None,
+        }
+    }
+}
+
+impl<S: Subscriber + for<'a> LookupSpan<'a>> Layer<S> for SpanTimer {
+    fn on_new_span(&self, _: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            span.extensions_mut().insert(Instant::now());
+        }
+    }
+
+    fn on_close(&self, id: span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else {
+            return;
+        };
+
+        if let (Some(total), Some(start)) =
+            (Self::total(span.name()), span.extensions().get::<Instant>())
+        {
+            add(total, start.elapsed());
+        }
+    }
+}
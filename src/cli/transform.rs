@@ -0,0 +1,282 @@
+use super::flatten::{flatten, unflatten};
+use anyhow::{Result, bail};
+use clap::{Args, ValueEnum};
+use globset::{GlobBuilder, GlobMatcher};
+use json_decoder::DuplicateKeys;
+use regex::Regex;
+use serde_json::{Map, Value, map::Entry};
+use std::mem;
+
+// What the values of redacted keys are replaced with
+const REDACTED: &str = "[REDACTED]";
+
+#[derive(Args, Debug)]
+pub struct TransformArgs {
+    /// Shorten strings longer than N bytes to their first N bytes followed
+    /// by `…(+<rest> bytes)`, for previews
+    #[arg(long, value_name = "N", conflicts_with = "stream", global = true)]
+    pub max_string_len: Option<usize>,
+
+    /// Replace the values of the keys matching any of these comma-separated
+    /// patterns with "[REDACTED]": case-insensitive globs such as `*token*`,
+    /// or regular expressions between slashes such as `/^e-?mail$/`
+    #[arg(long, value_name = "PATTERNS", value_parser = parse_patterns, conflicts_with = "stream", global = true)]
+    pub redact: Option<KeyPatterns>,
+
+    /// Convert every object key to this case, after redaction; keys of an
+    /// object converting to the same one are handled as --duplicate-keys says
+    #[arg(long, value_name = "CASE", conflicts_with = "stream", global = true)]
+    pub rename_keys: Option<KeyCase>,
+
+    /// Write a single-level object mapping dotted paths such as `a.b[0].c`
+    /// to the values found there, after any other transform
+    #[arg(long, conflicts_with = "stream", global = true)]
+    pub flatten: bool,
+
+    /// Rebuild the nested value of an object written by --flatten, before
+    /// any query or other transform
+    #[arg(long, conflicts_with_all = ["stream", "flatten"], global = true)]
+    pub unflatten: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyCase {
+    /// `user_id`
+    #[value(name = "snake_case")]
+    Snake,
+    /// `userId`
+    #[value(name = "camelCase")]
+    Camel,
+    /// `user-id`
+    #[value(name = "kebab-case")]
+    Kebab,
+}
+
+impl KeyCase {
+    /// Converts `key` to this case. Its words are split at underscores,
+    /// dashes, spaces and changes of case, so an acronym stays one word:
+    /// `HTTPServer` becomes `http_server`. Leading underscores and dashes,
+    /// such as those of `__typename`, are kept.
+    pub fn convert(self, key: &str) -> String {
+        let rest = key.trim_start_matches(['_', '-']);
+        let mut converted = key[..key.len() - rest.len()].to_string();
+
+        for (i, word) in words(rest).into_iter().enumerate() {
+            let word = word.to_lowercase();
+
+            match (self, i) {
+                (_, 0) => converted.push_str(&word),
+                (KeyCase::Snake, _) => converted.push_str(&format!("_{word}")),
+                (KeyCase::Kebab, _) => converted.push_str(&format!("-{word}")),
+                (KeyCase::Camel, _) => {
+                    let mut chars = word.chars();
+                    converted.extend(chars.next().into_iter().flat_map(char::to_uppercase));
+                    converted.push_str(chars.as_str());
+                }
+            }
+        }
+
+        converted
+    }
+}
+
+/// Patterns that object keys are matched against.
+#[derive(Clone, Debug)]
+pub struct KeyPatterns(Vec<KeyPattern>);
+
+#[derive(Clone, Debug)]
+enum KeyPattern {
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl KeyPatterns {
+    /// Whether `key` matches any of the patterns.
+    pub fn is_match(&self, key: &str) -> bool {
+        self.0.iter().any(|pattern| match pattern {
+            KeyPattern::Glob(matcher) => matcher.is_match(key),
+            KeyPattern::Regex(re) => re.is_match(key),
+        })
+    }
+}
+
+impl TransformArgs {
+    /// Whether the decoded value is written as is.
+    pub fn is_empty(&self) -> bool {
+        self.max_string_len.is_none()
+            && self.redact.is_none()
+            && self.rename_keys.is_none()
+            && !self.flatten
+            && !self.unflatten
+    }
+
+    /// Rebuilds the nested value of a flattened `value` when asked for.
+    pub fn unflattened(&self, value: Value) -> Result<Value> {
+        match self.unflatten {
+            true => unflatten(value),
+            false => Ok(value),
+        }
+    }
+
+    /// Rewrites the parts of the decoded `value` that were asked for; keys
+    /// renamed to the same one are handled according to `duplicate_keys`.
+    pub fn apply(&self, value: &mut Value, duplicate_keys: DuplicateKeys) -> Result<()> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        let mut pending = vec![&mut *value];
+
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::String(s) => {
+                    if let Some(max_len) = self.max_string_len {
+                        truncate(s, max_len);
+                    }
+                }
+                Value::Array(arr) => pending.extend(arr),
+                Value::Object(obj) => {
+                    for (key, item) in obj.iter_mut() {
+                        match &self.redact {
+                            Some(patterns) if patterns.is_match(key) => {
+                                *item = Value::from(REDACTED);
+                            }
+                            _ => pending.push(item),
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        if let Some(case) = self.rename_keys {
+            rename_keys(value, case, duplicate_keys)?;
+        }
+
+        if self.flatten {
+            *value = flatten(value.take());
+        }
+
+        Ok(())
+    }
+}
+
+// A renamed key keeps the position of the first key converting to it, as
+// duplicate keys do when decoding
+fn rename_keys(value: &mut Value, case: KeyCase, duplicate_keys: DuplicateKeys) -> Result<()> {
+    let mut pending = vec![value];
+
+    while let Some(value) = pending.pop() {
+        match value {
+            Value::Array(arr) => pending.extend(arr),
+            Value::Object(obj) => {
+                let mut renamed = Map::with_capacity(obj.len());
+                // Original keys of the members kept
+                let mut originals: Vec<String> = Vec::with_capacity(obj.len());
+
+                for (key, item) in mem::take(obj) {
+                    let mut entry = match renamed.entry(case.convert(&key)) {
+                        Entry::Vacant(e) => {
+                            e.insert(item);
+                            originals.push(key);
+                            continue;
+                        }
+                        Entry::Occupied(e) => e,
+                    };
+                    let first = originals
+                        .iter()
+                        .find(|original| case.convert(original) == *entry.key())
+                        .expect("converted key");
+
+                    match duplicate_keys {
+                        DuplicateKeys::Error => bail!(
+                            "Keys {first:?} and {key:?} of an object both convert to {:?}",
+                            entry.key()
+                        ),
+                        DuplicateKeys::First => {}
+                        DuplicateKeys::Last => {
+                            entry.insert(item);
+                        }
+                        DuplicateKeys::Warn => {
+                            tracing::warn!(
+                                "keys {first:?} and {key:?} of an object both convert to {:?}, \
+                                 keeping the last value",
+                                entry.key()
+                            );
+                            entry.insert(item);
+                        }
+                    }
+                }
+
+                *obj = renamed;
+                pending.extend(obj.values_mut());
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Splits `key` at separators and before the upper-case letter starting a word
+fn words(key: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+
+    for part in key.split(['_', '-', ' ']).filter(|part| !part.is_empty()) {
+        let chars: Vec<(usize, char)> = part.char_indices().collect();
+        let mut start = 0;
+
+        for (i, &(at, c)) in chars.iter().enumerate().skip(1) {
+            let prev = chars[i - 1].1;
+            let next = chars.get(i + 1).map(|&(_, next)| next);
+            let boundary = c.is_uppercase()
+                && (prev.is_lowercase()
+                    || prev.is_ascii_digit()
+                    || (prev.is_uppercase() && next.is_some_and(char::is_lowercase)));
+
+            if boundary {
+                words.push(&part[start..at]);
+                start = at;
+            }
+        }
+
+        words.push(&part[start..]);
+    }
+
+    words
+}
+
+// Keeps at most `max_len` bytes of `s`, cut at a character boundary
+fn truncate(s: &mut String, max_len: usize) {
+    if s.len() <= max_len {
+        return;
+    }
+
+    let end = s.floor_char_boundary(max_len);
+    let rest = s.len() - end;
+
+    s.truncate(end);
+    s.push_str(&format!("…(+{rest} bytes)"));
+}
+
+fn parse_patterns(s: &str) -> Result<KeyPatterns, String> {
+    s.split(',')
+        .filter(|pattern| !pattern.is_empty())
+        .map(
+            |pattern| match pattern.strip_prefix('/').and_then(|p| p.strip_suffix('/')) {
+                Some(re) => Regex::new(re)
+                    .map(KeyPattern::Regex)
+                    .map_err(|e| e.to_string()),
+                None => GlobBuilder::new(pattern)
+                    .case_insensitive(true)
+                    .build()
+                    .map(|glob| KeyPattern::Glob(glob.compile_matcher()))
+                    .map_err(|e| e.to_string()),
+            },
+        )
+        .collect::<Result<Vec<_>, _>>()
+        .and_then(|patterns| match patterns.is_empty() {
+            true => Err(String::from("expected at least one pattern")),
+            false => Ok(KeyPatterns(patterns)),
+        })
+}
@@ -0,0 +1,130 @@
+use super::diagnostic::WriteFailure;
+use anyhow::{Context, Result};
+use serde_json::Value;
+use std::{fmt, io::Write};
+
+/// Writes a `tree(1)`-style outline of `value`: every key with the type of
+/// its value and the size of arrays and objects. Arrays are outlined by their
+/// first element, followed by the number of elements left out.
+pub fn write_tree(value: &Value, writer: &mut dyn Write) -> Result<()> {
+    let mut counts = Counts::default();
+    counts.add(value);
+
+    let mut lines = vec![format!("$: {}", describe(value))];
+    outline(value, &mut String::new(), &mut lines);
+    lines.push(String::new());
+    lines.push(counts.to_string());
+
+    for line in lines {
+        writeln!(writer, "{line}").context(WriteFailure("Failed to write tree"))?;
+    }
+
+    Ok(())
+}
+
+// Appends the lines of the children of `value`, each after `prefix`
+fn outline(value: &Value, prefix: &mut String, lines: &mut Vec<String>) {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Object(obj) => obj.iter().map(|(key, item)| (label(key), item)).collect(),
+        Value::Array(arr) => arr
+            .first()
+            .map(|first| ("[0]".to_string(), first))
+            .into_iter()
+            .collect(),
+        _ => return,
+    };
+    let elided = match value {
+        Value::Array(arr) if arr.len() > 1 => Some(arr.len() - 1),
+        _ => None,
+    };
+
+    for (i, (name, item)) in children.iter().enumerate() {
+        let last = i + 1 == children.len() && elided.is_none();
+        let (branch, indent) = match last {
+            true => ("└── ", "    "),
+            false => ("├── ", "│   "),
+        };
+
+        lines.push(format!("{prefix}{branch}{name}: {}", describe(item)));
+
+        prefix.push_str(indent);
+        outline(item, prefix, lines);
+        prefix.truncate(prefix.len() - indent.len());
+    }
+
+    if let Some(more) = elided {
+        let noun = if more == 1 { "item" } else { "items" };
+        lines.push(format!("{prefix}└── … {more} more {noun}"));
+    }
+}
+
+fn describe(value: &Value) -> String {
+    match value {
+        Value::Null => "null".to_string(),
+        Value::Bool(_) => "boolean".to_string(),
+        Value::Number(_) => "number".to_string(),
+        Value::String(_) => "string".to_string(),
+        Value::Array(arr) => format!("array ({})", plural(arr.len(), "item")),
+        Value::Object(obj) => format!("object ({})", plural(obj.len(), "key")),
+    }
+}
+
+// Keys that would break the outline are written as JSON strings
+fn label(key: &str) -> String {
+    let plain = !key.is_empty()
+        && key.trim() == key
+        && !key.chars().any(char::is_control)
+        && !key.starts_with('"');
+
+    match plain {
+        true => key.to_string(),
+        false => Value::from(key).to_string(),
+    }
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    match n {
+        1 => format!("1 {noun}"),
+        n => format!("{n} {noun}s"),
+    }
+}
+
+// Containers and values of the whole document, outlined or not
+#[derive(Default)]
+struct Counts {
+    objects: usize,
+    arrays: usize,
+    values: usize,
+}
+
+impl Counts {
+    fn add(&mut self, value: &Value) {
+        let mut pending = vec![value];
+
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::Object(obj) => {
+                    self.objects += 1;
+                    pending.extend(obj.values());
+                }
+                Value::Array(arr) => {
+                    self.arrays += 1;
+                    pending.extend(arr);
+                }
+                _ => self.values += 1,
+            }
+        }
+    }
+}
+
+impl fmt::Display for Counts {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}, {}, {}",
+            plural(self.objects, "object"),
+            plural(self.arrays, "array"),
+            plural(self.values, "value")
+        )
+    }
+}
@@ -0,0 +1,41 @@
+use super::diagnostic::WriteFailure;
+use anyhow::{Context, Result};
+use json_decoder::{DecodeOptions, StreamingDecoder};
+use std::io::{BufRead, Write};
+
+/// Lists the entries of one payload that are not reachable from the root,
+/// with the input line they were read from and their size as compact JSON.
+pub fn write_unused(
+    reader: Box<dyn BufRead>,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+) -> Result<()> {
+    let decoder = StreamingDecoder::from_reader_with_options(reader, options)?;
+    let reachable = decoder.reachable()?;
+    let mut unused = 0;
+    let mut unused_size = 0;
+
+    for (index, entry) in decoder.encoded_list().iter().enumerate() {
+        if reachable[index] {
+            continue;
+        }
+
+        let size = serde_json::to_vec(entry)?.len();
+        unused += 1;
+        unused_size += size;
+
+        writeln!(
+            writer,
+            "entry {index} (line {}): {size} bytes",
+            decoder.entry_line(index)
+        )
+        .context(WriteFailure("Failed to write unused entries"))?;
+    }
+
+    writeln!(
+        writer,
+        "{unused} of {} entries unused, {unused_size} bytes",
+        reachable.len()
+    )
+    .context(WriteFailure("Failed to write unused entries"))
+}
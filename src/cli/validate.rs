@@ -0,0 +1,43 @@
+use anyhow::{Context, Result, bail};
+use json_decoder::{PathSegment, format_path};
+use jsonschema::paths::LocationSegment;
+use serde_json::Value;
+use std::{fs, path::Path};
+
+/// Checks `value` against the JSON Schema read from `schema_path`, failing
+/// with every violation and the path of the value it was found at.
+pub fn validate(value: &Value, schema_path: &Path) -> Result<()> {
+    let text = fs::read_to_string(schema_path)
+        .with_context(|| format!("Failed to read schema {}", schema_path.display()))?;
+    let schema: Value = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid JSON in schema {}", schema_path.display()))?;
+    let validator = jsonschema::validator_for(&schema)
+        .map_err(|error| anyhow::anyhow!("{error}"))
+        .with_context(|| format!("Invalid schema {}", schema_path.display()))?;
+
+    let violations: Vec<String> = validator
+        .iter_errors(value)
+        .map(|error| {
+            let path: Vec<PathSegment> = error
+                .instance_path()
+                .iter()
+                .map(|segment| match segment {
+                    LocationSegment::Property(key) => PathSegment::Key(key.into_owned()),
+                    LocationSegment::Index(index) => PathSegment::Index(index),
+                })
+                .collect();
+
+            format!("  {}: {error}", format_path(&path))
+        })
+        .collect();
+
+    if !violations.is_empty() {
+        bail!(
+            "Decoded value does not match schema {}:\n{}",
+            schema_path.display(),
+            violations.join("\n")
+        );
+    }
+
+    Ok(())
+}
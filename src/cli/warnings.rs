@@ -0,0 +1,31 @@
+use std::fmt;
+use tracing::{
+    Event, Subscriber,
+    field::{Field, Visit},
+};
+use tracing_subscriber::{Layer, layer::Context};
+
+/// Prints the warnings of the library to stderr.
+pub struct WarningPrinter;
+
+impl<S: Subscriber> Layer<S> for WarningPrinter {
+    fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+        let mut message = Message(None);
+        event.record(&mut message);
+
+        if let Message(Some(message)) = message {
+            eprintln!("Warning: {message}");
+        }
+    }
+}
+
+// The formatted message of an event
+struct Message(Option<String>);
+
+impl Visit for Message {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = Some(format!("{value:?}"));
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use super::{
+    diagnostic::{self, ErrorFormat},
+    input::{InputArgs, Source},
+};
+use anyhow::{Context, Result, bail};
+use globset::{Glob, GlobMatcher};
+use json_decoder::DecodeOptions;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use std::{
+    collections::HashSet,
+    path::{Path, PathBuf},
+    sync::mpsc,
+    time::Duration,
+};
+
+// Editors and scrapers often write a file in several steps
+const DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// Runs `decode` once, then again whenever an input file changes.
+///
+/// Errors of individual runs are reported without ending the watch.
+pub fn watch(
+    input: &InputArgs,
+    options: &DecodeOptions,
+    error_format: ErrorFormat,
+    mut decode: impl FnMut() -> Result<()>,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(tx).with_context(|| "Failed to start watcher")?;
+    let mut files = HashSet::new();
+
+    for source in input.sources(options)? {
+        let Source::File {
+            path,
+            relative: None,
+        } = source
+        else {
+            continue;
+        };
+
+        // Watching the directory also catches files replaced by a rename
+        let path = path
+            .canonicalize()
+            .with_context(|| format!("Failed to watch {}", path.display()))?;
+        let parent = path.parent().unwrap_or(Path::new("/"));
+        watcher
+            .watch(parent, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", parent.display()))?;
+        files.insert(path);
+    }
+
+    let input_dir = match &input.input_dir {
+        Some(input_dir) => {
+            watcher
+                .watch(input_dir, RecursiveMode::Recursive)
+                .with_context(|| format!("Failed to watch {}", input_dir.display()))?;
+
+            let matcher = Glob::new(&input.pattern)
+                .with_context(|| format!("Invalid pattern {:?}", input.pattern))?
+                .compile_matcher();
+            input_dir.canonicalize().ok().map(|dir| (dir, matcher))
+        }
+        None => None,
+    };
+
+    if files.is_empty() && input_dir.is_none() {
+        bail!("--watch requires input files");
+    }
+
+    report(decode(), error_format);
+
+    loop {
+        let event = rx.recv().with_context(|| "Watcher stopped")?;
+
+        if !is_relevant(event, &files, input_dir.as_ref()) {
+            continue;
+        }
+
+        while rx.recv_timeout(DEBOUNCE).is_ok() {}
+
+        report(decode(), error_format);
+    }
+}
+
+fn is_relevant(
+    event: notify::Result<Event>,
+    files: &HashSet<PathBuf>,
+    input_dir: Option<&(PathBuf, GlobMatcher)>,
+) -> bool {
+    let Ok(event) = event else {
+        return false;
+    };
+
+    if matches!(event.kind, EventKind::Access(_)) {
+        return false;
+    }
+
+    // Only files matching the pattern count, so outputs written into the
+    // input directory do not trigger another run
+    event.paths.iter().any(|path| {
+        files.contains(path)
+            || input_dir.is_some_and(|(input_dir, matcher)| {
+                path.strip_prefix(input_dir)
+                    .is_ok_and(|relative| matcher.is_match(relative))
+            })
+    })
+}
+
+fn report(result: Result<()>, error_format: ErrorFormat) {
+    if let Err(e) = result {
+        diagnostic::report(&e, error_format);
+    }
+}
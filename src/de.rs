@@ -0,0 +1,376 @@
+use crate::{
+    DecodeError, DecodeOptions, Dialect, JSONDecoder,
+    lines::ReadLines,
+    path::{PathSegment, format_path},
+};
+use serde::{
+    de::{
+        self, DeserializeOwned, DeserializeSeed, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+        value::MapAccessDeserializer,
+    },
+    forward_to_deserialize_any,
+};
+use serde_json::Value;
+use std::{io::BufRead, iter, slice, vec};
+use tracing::instrument;
+
+/// Deserializes an encoded payload from `reader` directly into `T`.
+///
+/// Only the entry list is kept in memory; references are resolved lazily while
+/// `T` is being built, so no intermediate [`serde_json::Value`] tree is created.
+/// An entry referenced several times is deserialized at every reference, and
+/// errors are located like those of [`JSONDecoder`].
+///
+/// ```no_run
+/// #[derive(serde::Deserialize)]
+/// struct Payload {
+///     root: serde_json::Value,
+/// }
+///
+/// let f = std::fs::File::open("sample_in.txt")?;
+/// let payload: Payload = json_decoder::from_reader(std::io::BufReader::new(f))?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn from_reader<R: BufRead, T: DeserializeOwned>(reader: R) -> Result<T, DecodeError> {
+    from_reader_with_options(reader, DecodeOptions::default())
+}
+
+/// Deserializes an encoded payload from `reader` into `T` with `options`.
+#[instrument(name = "deserialize", skip_all)]
+pub fn from_reader_with_options<R: BufRead, T: DeserializeOwned>(
+    reader: R,
+    options: DecodeOptions,
+) -> Result<T, DecodeError> {
+    if options.dialect != Dialect::Native {
+        let value = JSONDecoder::decode(ReadLines::new(reader), options)?.into_value();
+        return T::deserialize(value).map_err(|error| DecodeError::Deserialize(error.to_string()));
+    }
+
+    let decoder = JSONDecoder::load(ReadLines::new(reader), options)?;
+    let fragment = decoder.root()?;
+    let root = FragmentDeserializer {
+        decoder: &decoder,
+        fragment,
+        scope: Scope {
+            entry: Some(0),
+            followed: Vec::new(),
+            segment: None,
+            parent: None,
+        },
+        depth: 0,
+    };
+
+    T::deserialize(root).map_err(|error| error.located(format_path(&[]), 0, decoder.entry_line(0)))
+}
+
+struct FragmentDeserializer<'a, 'p> {
+    decoder: &'a JSONDecoder,
+    fragment: &'a Value,
+    scope: Scope<'p>,
+    depth: usize,
+}
+
+// Where a fragment is nested: the entries open around it, to detect cycles,
+// and its path, to locate errors
+struct Scope<'p> {
+    // Entry the fragment was referenced as, `None` when inline
+    entry: Option<usize>,
+    // Entries followed from the promise it stands for, the last one holding
+    // the fragment
+    followed: Vec<usize>,
+    // Location in the parent fragment
+    segment: Option<PathSegment>,
+    parent: Option<&'p Scope<'p>>,
+}
+
+impl<'a> FragmentDeserializer<'a, '_> {
+    fn entry(
+        &self,
+        index: &Value,
+        segment: PathSegment,
+    ) -> Result<FragmentDeserializer<'a, '_>, DecodeError> {
+        if let Some(number) = self.decoder.non_finite(index) {
+            return self.child(number?, None, segment);
+        }
+
+        let index = self.decoder.decode_index(index)?;
+
+        if let Some(cycle) = self.scope.cycle(index) {
+            return Err(self.decoder.cycle_error(cycle));
+        }
+
+        self.child(&self.decoder.encoded_list[index], Some(index), segment)
+    }
+
+    fn child(
+        &self,
+        fragment: &'a Value,
+        entry: Option<usize>,
+        segment: PathSegment,
+    ) -> Result<FragmentDeserializer<'a, '_>, DecodeError> {
+        let max_depth = self.decoder.options.max_depth;
+
+        if self.depth >= max_depth {
+            return Err(DecodeError::DepthLimitExceeded {
+                max_depth,
+                path: None,
+            });
+        }
+
+        Ok(FragmentDeserializer {
+            decoder: self.decoder,
+            fragment,
+            scope: Scope {
+                entry,
+                followed: Vec::new(),
+                segment: Some(segment),
+                parent: Some(&self.scope),
+            },
+            depth: self.depth + 1,
+        })
+    }
+
+    // Follows `["P", index]` promises until a non-promise fragment is reached
+    fn resolve(mut self) -> Result<Self, DecodeError> {
+        let (fragment, followed) = self.decoder.follow_promises(self.fragment)?;
+
+        for &index in &followed {
+            if let Some(cycle) = self.scope.cycle(index) {
+                return Err(self.decoder.cycle_error(cycle));
+            }
+        }
+
+        self.fragment = fragment;
+        // Deserializing an option resolves the fragment once more
+        if !followed.is_empty() {
+            self.scope.followed = followed;
+        }
+
+        Ok(self)
+    }
+
+    // Adds the path of `child`, or of this fragment, and the entry holding
+    // the problem to an error; `entry` defaults to the innermost one open
+    fn locate(
+        &self,
+        error: DecodeError,
+        child: Option<PathSegment>,
+        entry: Option<usize>,
+    ) -> DecodeError {
+        let entry = entry.unwrap_or_else(|| self.scope.innermost_entry());
+        let mut path: Vec<PathSegment> = child
+            .into_iter()
+            .chain(self.scope.ancestors().filter_map(|s| s.segment.clone()))
+            .collect();
+        path.reverse();
+
+        error.located(format_path(&path), entry, self.decoder.entry_line(entry))
+    }
+}
+
+impl Scope<'_> {
+    fn ancestors(&self) -> impl Iterator<Item = &Scope<'_>> {
+        iter::successors(Some(self), |s| s.parent)
+    }
+
+    // Entries open from this fragment up to the root, innermost first
+    fn open(&self) -> impl Iterator<Item = usize> + '_ {
+        self.ancestors()
+            .flat_map(|s| s.followed.iter().rev().copied().chain(s.entry))
+    }
+
+    fn innermost_entry(&self) -> usize {
+        self.open()
+            .next()
+            .expect("fragments are nested in an entry")
+    }
+
+    // The entries of the cycle that referencing `index` closes, first and
+    // last being `index`
+    fn cycle(&self, index: usize) -> Option<Vec<usize>> {
+        if !self.open().any(|i| i == index) {
+            return None;
+        }
+
+        let mut cycle: Vec<usize> = self.open().take_while(|&i| i != index).collect();
+        cycle.push(index);
+        cycle.reverse();
+        cycle.push(index);
+
+        Some(cycle)
+    }
+}
+
+impl<'de> de::Deserializer<'de> for FragmentDeserializer<'de, '_> {
+    type Error = DecodeError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let this = self.resolve()?;
+
+        match this.fragment {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(b) => visitor.visit_bool(*b),
+            Value::Number(n) => {
+                if let Some(u) = n.as_u64() {
+                    visitor.visit_u64(u)
+                } else if let Some(i) = n.as_i64() {
+                    visitor.visit_i64(i)
+                } else if let Some(u) = n.as_u128() {
+                    visitor.visit_u128(u)
+                } else if let Some(i) = n.as_i128() {
+                    visitor.visit_i128(i)
+                } else if let Some(f) = n.as_f64() {
+                    visitor.visit_f64(f)
+                } else {
+                    Err(de::Error::custom(format!("unsupported number {n}")))
+                }
+            }
+            Value::String(s) => visitor.visit_borrowed_str(s),
+            Value::Array(arr) => visitor.visit_seq(FragmentSeqAccess {
+                parent: this,
+                items: arr.iter().enumerate(),
+            }),
+            Value::Object(obj) => visitor.visit_map(FragmentMapAccess {
+                items: this.decoder.object_members(obj, || None)?.into_iter(),
+                parent: this,
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, DecodeError> {
+        let this = self.resolve()?;
+
+        match this.fragment {
+            Value::Null => visitor.visit_none(),
+            _ => visitor.visit_some(this),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, DecodeError> {
+        let this = self.resolve()?;
+
+        match this.fragment {
+            // Unit variant
+            Value::String(s) => visitor.visit_enum(s.as_str().into_deserializer()),
+            // Externally tagged variant with content
+            Value::Object(obj) if obj.len() == 1 => {
+                visitor.visit_enum(MapAccessDeserializer::new(FragmentMapAccess {
+                    items: this.decoder.object_members(obj, || None)?.into_iter(),
+                    parent: this,
+                    value: None,
+                }))
+            }
+            _ => Err(de::Error::custom(
+                "expected a string or a single-key object",
+            )),
+        }
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+// Errors of a child are located at its path, in the entry it was referenced
+// as when it is one; those of deeper fragments are located already
+fn deserialize_child<'de, T: DeserializeSeed<'de>>(
+    parent: &FragmentDeserializer<'de, '_>,
+    seed: T,
+    segment: PathSegment,
+    item: &'de Value,
+    reference: bool,
+) -> Result<T::Value, DecodeError> {
+    let child = match reference {
+        true => parent.entry(item, segment.clone()),
+        false => parent.child(item, None, segment.clone()),
+    }
+    .map_err(|error| parent.locate(error, Some(segment.clone()), None))?;
+    let entry = child.scope.entry;
+
+    seed.deserialize(child)
+        .map_err(|error| parent.locate(error, Some(segment), entry))
+}
+
+struct FragmentSeqAccess<'a, 'p> {
+    parent: FragmentDeserializer<'a, 'p>,
+    items: iter::Enumerate<slice::Iter<'a, Value>>,
+}
+
+impl<'de> SeqAccess<'de> for FragmentSeqAccess<'de, '_> {
+    type Error = DecodeError;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, DecodeError> {
+        let Some((i, item)) = self.items.next() else {
+            return Ok(None);
+        };
+
+        // Numbers are references, anything else is an inline fragment
+        let reference = item.is_number();
+
+        deserialize_child(&self.parent, seed, PathSegment::Index(i), item, reference).map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct FragmentMapAccess<'a, 'p> {
+    parent: FragmentDeserializer<'a, 'p>,
+    items: vec::IntoIter<(&'a str, &'a Value)>,
+    value: Option<(&'a str, &'a Value)>,
+}
+
+impl<'de> MapAccess<'de> for FragmentMapAccess<'de, '_> {
+    type Error = DecodeError;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, DecodeError> {
+        let Some((key, value)) = self.items.next() else {
+            return Ok(None);
+        };
+
+        self.value = Some((key, value));
+
+        seed.deserialize(de::value::BorrowedStrDeserializer::new(key))
+            .map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, DecodeError> {
+        let (key, value) = self
+            .value
+            .take()
+            .ok_or_else(|| de::Error::custom("value requested before key"))?;
+        let segment = PathSegment::Key(String::from(key));
+
+        deserialize_child(&self.parent, seed, segment, value, true)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
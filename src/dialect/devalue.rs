@@ -0,0 +1,173 @@
+use super::{Child, Expand, Node, Syntax, big_int, invalid, non_finite};
+use crate::{DecodeError, JSONDecoder};
+use serde_json::Value;
+use std::{borrow::Cow, convert};
+
+/// devalue's format, as written by SvelteKit and Nuxt: one JSON array of
+/// entries, the first one the root. Arrays and object values hold the indexes
+/// of their elements, negative indexes stand for values JSON lacks, and an
+/// array starting with a type name holds a value of that type.
+pub(crate) struct Devalue {
+    // Revive the types Nuxt's payload plugin adds
+    pub(crate) nuxt: bool,
+}
+
+const UNDEFINED: i64 = -1;
+const HOLE: i64 = -2;
+const NAN: i64 = -3;
+const POSITIVE_INFINITY: i64 = -4;
+const NEGATIVE_INFINITY: i64 = -5;
+const NEGATIVE_ZERO: i64 = -6;
+
+// Typed arrays are written as base64 of their bytes
+const TYPED_ARRAYS: &[&str] = &[
+    "ArrayBuffer",
+    "Int8Array",
+    "Uint8Array",
+    "Uint8ClampedArray",
+    "Int16Array",
+    "Uint16Array",
+    "Int32Array",
+    "Uint32Array",
+    "Float32Array",
+    "Float64Array",
+    "BigInt64Array",
+    "BigUint64Array",
+];
+
+impl Syntax for Devalue {}
+
+impl Expand for Devalue {
+    fn expand<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        _location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError> {
+        match fragment {
+            Value::Array(arr) => match arr.split_first() {
+                Some((Value::String(name), args)) => self.typed(decoder, name, args),
+                _ => Ok(Node::Array(self.references(decoder, arr))),
+            },
+            Value::Object(obj) => {
+                let members = obj
+                    .iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), reference(decoder, value)))
+                    .collect();
+
+                Ok(Node::Object(members))
+            }
+            v => Ok(Node::Leaf(v.clone())),
+        }
+    }
+}
+
+impl Devalue {
+    fn references<'a>(&self, decoder: &JSONDecoder, values: &[Value]) -> Vec<Child<'a>> {
+        values.iter().map(|v| reference(decoder, v)).collect()
+    }
+
+    fn typed<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        name: &str,
+        args: &'a [Value],
+    ) -> Result<Node<'a>, DecodeError> {
+        let first = || {
+            args.first()
+                .ok_or_else(|| invalid(decoder, format!("{name} value without an argument")))
+        };
+
+        if self.nuxt {
+            match name {
+                // Vue's reactivity wrappers and Nuxt's components hold their
+                // value as is
+                "Ref" | "ShallowRef" | "Reactive" | "ShallowReactive" | "NuxtError" | "Island" => {
+                    return Ok(Node::Apply(reference(decoder, first()?), convert::identity));
+                }
+                "EmptyRef" | "EmptyShallowRef" => {
+                    return Ok(Node::Apply(reference(decoder, first()?), empty_ref));
+                }
+                _ => {}
+            }
+        }
+
+        match name {
+            "Date" | "Object" | "URL" | "URLSearchParams" => Ok(Node::Leaf(first()?.clone())),
+            "BigInt" => Ok(Node::Leaf(match first()? {
+                Value::String(digits) => big_int(digits),
+                v => v.clone(),
+            })),
+            "RegExp" => {
+                let source = first()?.as_str().unwrap_or_default();
+                let flags = args.get(1).and_then(Value::as_str).unwrap_or_default();
+
+                Ok(Node::Leaf(Value::from(format!("/{source}/{flags}"))))
+            }
+            "Set" => Ok(Node::Array(self.references(decoder, args))),
+            "Map" if args.len().is_multiple_of(2) => Ok(Node::Map(self.references(decoder, args))),
+            "Map" => Err(invalid(
+                decoder,
+                "Map without a value for its last key".into(),
+            )),
+            // An object without a prototype, keys and values alternating
+            "null" => {
+                let members = args
+                    .chunks(2)
+                    .map(|pair| match pair {
+                        [Value::String(key), value] => {
+                            Ok((Cow::Borrowed(key.as_str()), reference(decoder, value)))
+                        }
+                        _ => Err(invalid(
+                            decoder,
+                            format!("null-prototype object member {pair:?}"),
+                        )),
+                    })
+                    .collect::<Result<_, DecodeError>>()?;
+
+                Ok(Node::Object(members))
+            }
+            name if TYPED_ARRAYS.contains(&name) => match first()? {
+                bytes @ Value::String(_) => Ok(Node::Leaf(bytes.clone())),
+                // Views of a buffer reference it
+                index => Ok(Node::Apply(reference(decoder, index), convert::identity)),
+            },
+            name => Err(invalid(decoder, format!("value of unknown type {name:?}"))),
+        }
+    }
+}
+
+// An index into the entries or one of the negative constants
+fn reference<'a>(decoder: &JSONDecoder, value: &Value) -> Child<'a> {
+    let Some(index) = value.as_i64() else {
+        return Child::Broken(DecodeError::InvalidIndex {
+            value: value.clone(),
+        });
+    };
+
+    match index {
+        UNDEFINED | HOLE => Child::Undefined,
+        NAN => non_finite(decoder, "NaN"),
+        POSITIVE_INFINITY => non_finite(decoder, "Infinity"),
+        NEGATIVE_INFINITY => non_finite(decoder, "-Infinity"),
+        NEGATIVE_ZERO => Child::Value(Value::from(0)),
+        index => match usize::try_from(index) {
+            Ok(i) if i < decoder.encoded_list.len() => Child::Entry(i),
+            _ => Child::Broken(DecodeError::IndexOutOfBounds {
+                index: index.into(),
+                len: decoder.encoded_list.len(),
+            }),
+        },
+    }
+}
+
+// Nuxt writes the value of refs the server left empty as a string: `_` for
+// `undefined`, `0n` for `0n` and JSON otherwise
+fn empty_ref(value: Value) -> Value {
+    match value {
+        Value::String(s) if s == "_" => Value::Null,
+        Value::String(s) if s == "0n" => Value::from(0),
+        Value::String(s) => serde_json::from_str(&s).unwrap_or(Value::String(s)),
+        v => v,
+    }
+}
@@ -0,0 +1,63 @@
+use super::{Child, Expand, Node, Syntax, invalid};
+use crate::{DecodeError, JSONDecoder};
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// The format of the flatted library (https://github.com/WebReflection/flatted):
+/// one JSON array of entries, the first one the root. Arrays and objects hold
+/// the indexes of the strings, arrays and objects in them as decimal strings,
+/// and other values as is; entries that are strings are the strings
+/// themselves.
+pub(crate) struct Flatted;
+
+impl Syntax for Flatted {}
+
+impl Expand for Flatted {
+    fn expand<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        _location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError> {
+        match fragment {
+            Value::Array(arr) => Ok(Node::Array(
+                arr.iter().map(|item| reference(decoder, item)).collect(),
+            )),
+            Value::Object(obj) => {
+                let members = obj
+                    .iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), reference(decoder, value)))
+                    .collect();
+
+                Ok(Node::Object(members))
+            }
+            v => Ok(Node::Leaf(v.clone())),
+        }
+    }
+}
+
+// A string holding the index of an entry, or a value written inline
+fn reference<'a>(decoder: &JSONDecoder, value: &Value) -> Child<'a> {
+    let index = match value {
+        Value::String(index) => index,
+        Value::Array(_) | Value::Object(_) => {
+            let reason = "arrays and objects must be entries, not nested".to_string();
+            return Child::Broken(invalid(decoder, reason));
+        }
+        v => return Child::Value(v.clone()),
+    };
+
+    let Ok(i) = index.parse::<usize>() else {
+        return Child::Broken(DecodeError::InvalidIndex {
+            value: value.clone(),
+        });
+    };
+
+    match i < decoder.encoded_list.len() {
+        true => Child::Entry(i),
+        false => Child::Broken(DecodeError::IndexOutOfBounds {
+            index: i as i128,
+            len: decoder.encoded_list.len(),
+        }),
+    }
+}
@@ -0,0 +1,296 @@
+use super::{Child, Expand, Node, Syntax, big_int, invalid, map_value, non_finite};
+use crate::{DecodeError, DecodeOptions, JSONDecoder, parse_value};
+use serde_json::Value;
+use std::{borrow::Cow, collections::HashMap, convert};
+
+/// The Flight format React Server Components are streamed in, as Next.js
+/// fetches them: `<id>:<row>` rows with hexadecimal ids, the root chunk being
+/// `0`. A row holds JSON, or an `I` module import, `E` error or
+/// `T<length>,<text>` text chunk; hint and debug rows are skipped.
+///
+/// Strings starting with `$` reference other chunks, as `$1`, `$L1` (lazy),
+/// `$@1` (promise) or `$1:props:children` (a part of one), or stand for values
+/// JSON lacks, such as `$undefined`, `$D<date>` or `$n<bigint>`; `$$` escapes a
+/// literal `$`.
+#[derive(Default)]
+pub(crate) struct Flight {
+    // Entry holding each chunk, by id
+    chunks: HashMap<u64, usize>,
+}
+
+// Tags of the rows holding chunks of the tree: JSON models, module imports
+// and errors; text rows are read apart
+const CHUNK_TAGS: &[&str] = &["", "I", "E"];
+
+impl Syntax for Flight {
+    fn load(&mut self, text: &str, options: DecodeOptions) -> Result<JSONDecoder, DecodeError> {
+        let malformed = |line: usize, reason: &str| DecodeError::InvalidFragment {
+            dialect: options.dialect.name(),
+            reason: format!("line {line}: {reason}"),
+        };
+
+        let mut entries = Vec::new();
+        let mut entry_lines = Vec::new();
+        let mut pos = 0;
+        let mut line = 1;
+        let mut line_start = 0;
+
+        while pos < text.len() {
+            let rest = &text[pos..];
+
+            if let Some(blank) = ["\n", "\r\n"]
+                .into_iter()
+                .find(|&end| rest.starts_with(end))
+            {
+                pos += blank.len();
+                line += 1;
+                line_start = pos;
+                continue;
+            }
+
+            let row_end = rest.find('\n').map_or(text.len(), |end| pos + end);
+            let Some((id, after)) = text[pos..row_end].split_once(':') else {
+                return Err(malformed(line, "row without an `<id>:` prefix"));
+            };
+            let tag_len = after.bytes().take_while(u8::is_ascii_uppercase).count();
+            let (tag, body) = after.split_at(tag_len);
+            let body_start = pos + id.len() + 1 + tag_len;
+
+            let chunk = match tag {
+                "T" => {
+                    let Some((length, _)) = body.split_once(',') else {
+                        return Err(malformed(line, "text row without a length"));
+                    };
+                    let text_start = body_start + length.len() + 1;
+                    let chunk = usize::from_str_radix(length, 16)
+                        .ok()
+                        .and_then(|len| text.get(text_start..text_start.checked_add(len)?))
+                        .ok_or_else(|| malformed(line, "text row shorter than its length"))?;
+
+                    // Read as a string of the tree, whose `$` is escaped
+                    let value = match chunk.starts_with('$') {
+                        true => Value::from(format!("${chunk}")),
+                        false => Value::from(chunk),
+                    };
+                    let row_line = line;
+
+                    if let Some(last) = chunk.rfind('\n') {
+                        line += chunk.matches('\n').count();
+                        line_start = text_start + last + 1;
+                    }
+                    pos = text_start + chunk.len();
+
+                    Some((value, row_line))
+                }
+                tag if CHUNK_TAGS.contains(&tag) => {
+                    let context = format!("chunk {id}");
+                    let value =
+                        parse_value(body, body_start - line_start, line, &context, &options)?;
+
+                    Some((value, line))
+                }
+                _ => None,
+            };
+
+            if tag != "T" {
+                pos = (row_end + 1).min(text.len());
+                line += 1;
+                line_start = pos;
+            }
+
+            if let Some((value, row_line)) = chunk {
+                let Ok(id) = u64::from_str_radix(id, 16) else {
+                    return Err(malformed(row_line, &format!("invalid chunk id {id:?}")));
+                };
+
+                self.chunks.insert(id, entries.len());
+                entry_lines.push((entries.len(), row_line));
+                entries.push(value);
+            }
+        }
+
+        let mut decoder = JSONDecoder::new(entries, 1, options);
+
+        if !entry_lines.is_empty() {
+            decoder.entry_lines = entry_lines;
+        }
+
+        Ok(decoder)
+    }
+
+    fn root<'a>(&self, decoder: &'a JSONDecoder) -> Result<Child<'a>, DecodeError> {
+        decoder.root()?;
+        self.chunk(0).map(Child::Entry)
+    }
+}
+
+impl Expand for Flight {
+    fn expand<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        _location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError> {
+        match fragment {
+            Value::Array(arr) => Ok(Node::Array(
+                arr.iter().map(|item| self.child(decoder, item)).collect(),
+            )),
+            Value::Object(obj) => {
+                let members = obj
+                    .iter()
+                    .map(|(key, value)| (Cow::Borrowed(key.as_str()), self.child(decoder, value)))
+                    .collect();
+
+                Ok(Node::Object(members))
+            }
+            Value::String(s) => match self.string(decoder, s) {
+                Child::Value(value) => Ok(Node::Leaf(value)),
+                child => Ok(Node::Apply(child, convert::identity)),
+            },
+            v => Ok(Node::Leaf(v.clone())),
+        }
+    }
+}
+
+impl Flight {
+    fn chunk(&self, id: u64) -> Result<usize, DecodeError> {
+        self.chunks
+            .get(&id)
+            .copied()
+            .ok_or_else(|| DecodeError::MissingChunk {
+                id: format!("{id:x}"),
+            })
+    }
+
+    fn child<'a>(&self, decoder: &'a JSONDecoder, value: &'a Value) -> Child<'a> {
+        match value {
+            Value::String(s) => self.string(decoder, s),
+            Value::Array(_) | Value::Object(_) => Child::Inline(value),
+            v => Child::Value(v.clone()),
+        }
+    }
+
+    // A string of the tree, or what its `$` prefix makes it stand for
+    fn string<'a>(&self, decoder: &'a JSONDecoder, s: &str) -> Child<'a> {
+        let Some(rest) = s.strip_prefix('$') else {
+            return Child::Value(Value::from(s));
+        };
+        let reference = |target| {
+            self.reference(decoder, s, target)
+                .unwrap_or_else(Child::Broken)
+        };
+
+        match rest {
+            "undefined" => return Child::Undefined,
+            "NaN" => return non_finite(decoder, "NaN"),
+            "Infinity" => return non_finite(decoder, "Infinity"),
+            "-Infinity" => return non_finite(decoder, "-Infinity"),
+            "-0" => return Child::Value(Value::from(0)),
+            _ => {}
+        }
+
+        let mut chars = rest.chars();
+        let value = match chars.next() {
+            Some('$') => rest,
+            // Dates, and symbols by the name they are registered under
+            Some('D' | 'S') => chars.as_str(),
+            Some('n') => return Child::Value(big_int(chars.as_str())),
+            // Maps and form data reference a chunk of `[key, value]` pairs
+            Some('Q' | 'K') => return Child::Apply(Box::new(reference(chars.as_str())), pairs),
+            // Promises, lazy components, sets, server functions and blobs
+            // reference the chunk holding their value
+            Some('@' | 'L' | 'W' | 'F' | 'B') => return reference(chars.as_str()),
+            Some('0'..='9' | 'a'..='f') => return reference(rest),
+            // Such as the `$` marking React elements
+            _ => s,
+        };
+
+        Child::Value(Value::from(value))
+    }
+
+    // A reference to the chunk `target` names, `<id>` or `<id>:<key>:...` for
+    // a part of it
+    fn reference<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        s: &str,
+        target: &str,
+    ) -> Result<Child<'a>, DecodeError> {
+        let (id, path) = match target.split_once(':') {
+            Some((id, path)) => (id, Some(path)),
+            None => (target, None),
+        };
+        let Ok(id) = u64::from_str_radix(id, 16) else {
+            return Err(DecodeError::InvalidIndex {
+                value: Value::from(s),
+            });
+        };
+        let index = self.chunk(id)?;
+
+        let Some(path) = path else {
+            return Ok(Child::Entry(index));
+        };
+
+        let mut fragment = &decoder.encoded_list[index];
+
+        for key in path.split(':') {
+            fragment = self.follow(decoder, fragment);
+
+            let part = match fragment {
+                Value::Object(obj) => obj.get(key),
+                Value::Array(arr) => key.parse().ok().and_then(|i: usize| arr.get(i)),
+                _ => None,
+            };
+
+            fragment = part.ok_or_else(|| invalid(decoder, format!("{s:?} points at nothing")))?;
+        }
+
+        Ok(self.child(decoder, fragment))
+    }
+
+    // The chunk a fragment that references a whole chunk stands for, through
+    // chains of such references
+    fn follow<'a>(&self, decoder: &'a JSONDecoder, mut fragment: &'a Value) -> &'a Value {
+        // Bounded, as references may form a cycle
+        for _ in 0..decoder.encoded_list.len() {
+            let target = fragment
+                .as_str()
+                .and_then(|s| s.strip_prefix('$'))
+                .map(|rest| rest.strip_prefix(['@', 'L']).unwrap_or(rest))
+                .filter(|id| {
+                    !id.is_empty() && id.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+                })
+                .and_then(|id| u64::from_str_radix(id, 16).ok())
+                .and_then(|id| self.chunks.get(&id));
+
+            match target {
+                Some(&index) => fragment = &decoder.encoded_list[index],
+                None => break,
+            }
+        }
+
+        fragment
+    }
+}
+
+// Maps and form data decode to an object, or to `[key, value]` pairs when a
+// key is not a string
+fn pairs(value: Value) -> Value {
+    let Value::Array(pairs) = value else {
+        return value;
+    };
+
+    if !pairs
+        .iter()
+        .all(|pair| pair.as_array().is_some_and(|kv| kv.len() == 2))
+    {
+        return Value::Array(pairs);
+    }
+
+    let items = pairs.into_iter().flat_map(|pair| match pair {
+        Value::Array(kv) => kv,
+        _ => unreachable!("checked to be a pair"),
+    });
+
+    map_value(items.collect())
+}
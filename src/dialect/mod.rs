@@ -0,0 +1,440 @@
+use crate::{
+    DecodeError, DecodeOptions, Dialect, JSONDecoder, NonFinite,
+    lines::Lines,
+    parse_entries,
+    resolve::{Child, Expand, Node, Resolver, map_value},
+};
+use regex::Regex;
+use serde_json::Value;
+use std::{collections::BTreeSet, sync::LazyLock};
+use tracing::{Span, instrument};
+
+mod devalue;
+mod flatted;
+mod flight;
+mod plain;
+
+use devalue::Devalue;
+use flatted::Flatted;
+use flight::Flight;
+use plain::Plain;
+
+// How a dialect lays out its entries: where decoding starts and what each of
+// them holds
+pub(crate) trait Syntax: Expand {
+    // Parses the whole input into the entry list, by default a single JSON
+    // array of entries
+    fn load(&mut self, text: &str, options: DecodeOptions) -> Result<JSONDecoder, DecodeError> {
+        let context = format!("{} payload", options.dialect.name());
+        let entries = parse_entries(text, 0, 1, &context, &options)?;
+
+        Ok(JSONDecoder::new(entries, 1, options))
+    }
+
+    // The reference decoding starts from, by default the first entry
+    fn root<'a>(&self, decoder: &'a JSONDecoder) -> Result<Child<'a>, DecodeError> {
+        decoder.root()?;
+        Ok(Child::Entry(0))
+    }
+}
+
+// How much of the input is looked at to tell its dialect
+const SNIFFED_BYTES: usize = 64 * 1024;
+
+// Typed values of devalue, and those Nuxt adds
+const DEVALUE_TYPES: &[&str] = &[
+    "Date",
+    "Set",
+    "Map",
+    "RegExp",
+    "BigInt",
+    "null",
+    "URL",
+    "URLSearchParams",
+];
+const NUXT_TYPES: &[&str] = &[
+    "Ref",
+    "ShallowRef",
+    "Reactive",
+    "ShallowReactive",
+    "EmptyRef",
+    "EmptyShallowRef",
+    "NuxtError",
+    "Island",
+];
+// The lowest of the negative indexes devalue gives values JSON lacks
+const DEVALUE_MIN_INDEX: i64 = -6;
+
+static FLIGHT_ROW_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[0-9a-f]*:").expect("valid row regex"));
+
+impl Dialect {
+    /// Tells the dialect of a payload from the structure of its first bytes,
+    /// such as the buffer of a reader: the entries they hold, the references
+    /// between them and the native format's keys and promises.
+    ///
+    /// Payloads showing no sign of another dialect are taken as native ones,
+    /// whose errors are then reported when decoding. Fails with
+    /// [`DecodeError::AmbiguousDialect`] when signs of several dialects are
+    /// found. `options` gives the prefixes of the native format.
+    pub fn detect(head: &[u8], options: &DecodeOptions) -> Result<Self, DecodeError> {
+        let head = &head[..head.len().min(SNIFFED_BYTES)];
+        // The end of the head may split a character
+        let head = match str::from_utf8(head) {
+            Ok(head) => head,
+            Err(error) => str::from_utf8(&head[..error.valid_up_to()]).unwrap_or_default(),
+        };
+        let head = head.strip_prefix('\u{feff}').unwrap_or(head).trim_start();
+
+        if FLIGHT_ROW_RE.is_match(head) {
+            return Ok(Dialect::Flight);
+        }
+
+        let is_key_index = |key: &str| {
+            key.strip_prefix(options.key_prefix.as_str())
+                .is_some_and(|digits| {
+                    !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+                })
+        };
+        let is_continuation = |line: &str| {
+            line.split_once(':').is_some_and(|(p_index, _)| {
+                let p_index = p_index.trim().to_lowercase();
+                p_index
+                    .strip_prefix(&options.promise_prefix.to_lowercase())
+                    .is_some_and(|digits| {
+                        !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit())
+                    })
+            })
+        };
+
+        let Some(table) = Table::of(head) else {
+            return Ok(Dialect::Native);
+        };
+        let mut candidates = BTreeSet::new();
+
+        // Keys of the native format are references too, and so are promises
+        let native_object = |entry: &Value| {
+            entry
+                .as_object()
+                .is_some_and(|obj| !obj.is_empty() && obj.keys().all(|key| is_key_index(key)))
+        };
+        if table.entries.iter().any(native_object)
+            || table.entries.iter().any(has_promise)
+            || head.lines().skip(1).any(is_continuation)
+        {
+            candidates.insert(Dialect::Native);
+        }
+
+        // A devalue root with literal keys, or typed values, tell it apart
+        // from a native payload using no key or promise
+        let typed = |types: &[&str]| table.entries.iter().any(|entry| type_name(entry, types));
+        let literal_root = table.entries[0]
+            .as_object()
+            .is_some_and(|obj| !obj.keys().all(|key| is_key_index(key)));
+
+        if (literal_root || typed(DEVALUE_TYPES) || typed(NUXT_TYPES)) && table.is_devalue() {
+            // Nuxt payloads are devalue ones with more types
+            match typed(NUXT_TYPES) {
+                true => candidates.insert(Dialect::Nuxt),
+                false => candidates.insert(Dialect::Devalue),
+            };
+        }
+
+        if table.is_flatted() {
+            candidates.insert(Dialect::Flatted);
+        }
+
+        let mut candidates = candidates.into_iter();
+
+        match (candidates.next(), candidates.len()) {
+            (None, _) => Ok(Dialect::Native),
+            (Some(dialect), 0) => Ok(dialect),
+            (Some(first), _) => Err(DecodeError::AmbiguousDialect {
+                candidates: [first]
+                    .into_iter()
+                    .chain(candidates)
+                    .map(Dialect::name)
+                    .collect(),
+            }),
+        }
+    }
+
+    /// Whether a payload is JSON that is already decoded rather than a
+    /// payload of any dialect, telling from its first bytes: its document
+    /// starts with an object, which those of no dialect do.
+    pub fn is_plain(head: &[u8]) -> bool {
+        let head = head.strip_prefix("\u{feff}".as_bytes()).unwrap_or(head);
+
+        head.trim_ascii_start().starts_with(b"{")
+    }
+}
+
+// The entries of the array a payload starts with, as far as the sniffed
+// bytes hold complete ones
+struct Table {
+    entries: Vec<Value>,
+    // Whether the array ends within the sniffed bytes, so that the number of
+    // entries is known
+    complete: bool,
+}
+
+impl Table {
+    // `None` when the head starts with no array of at least one JSON entry
+    fn of(head: &str) -> Option<Self> {
+        let body = head.strip_prefix('[')?;
+        let mut entries = Vec::new();
+        let (mut depth, mut in_string, mut escaped) = (0_usize, false, false);
+        let mut start = 0;
+
+        for (i, c) in body.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if in_string => escaped = true,
+                '"' => in_string = !in_string,
+                _ if in_string => {}
+                '[' | '{' => depth += 1,
+                ']' | '}' if depth > 0 => depth -= 1,
+                ',' | ']' if depth == 0 => {
+                    let text = body[start..i].trim();
+
+                    if !text.is_empty() {
+                        entries.push(serde_json::from_str(text).ok()?);
+                    }
+                    if c == ']' {
+                        return Some(Table {
+                            entries,
+                            complete: true,
+                        })
+                        .filter(|table| !table.entries.is_empty());
+                    }
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+
+        Some(Table {
+            entries,
+            complete: false,
+        })
+        .filter(|table| !table.entries.is_empty())
+    }
+
+    // Whether `index` can be the one of an entry
+    fn holds(&self, index: u64) -> bool {
+        !self.complete || index < self.entries.len() as u64
+    }
+
+    // Every array and object holds devalue indexes of entries, and the root
+    // references the entry after it, as devalue numbers entries in the order it
+    // meets their values
+    fn is_devalue(&self) -> bool {
+        let references = |entry: &Value| -> Option<Vec<i64>> {
+            let members: Vec<&Value> = match entry {
+                Value::Array(arr) => match arr.split_first() {
+                    // Only the integer arguments of typed values are indexes
+                    Some((Value::String(_), args)) => {
+                        return Some(args.iter().filter_map(Value::as_i64).collect());
+                    }
+                    _ => arr.iter().collect(),
+                },
+                Value::Object(obj) => obj.values().collect(),
+                _ => Vec::new(),
+            };
+
+            members.into_iter().map(Value::as_i64).collect()
+        };
+
+        let all_valid = self.entries.iter().all(|entry| {
+            references(entry).is_some_and(|indexes| {
+                indexes.iter().all(|&index| {
+                    index >= DEVALUE_MIN_INDEX && (index < 0 || self.holds(index as u64))
+                })
+            })
+        });
+        let first = references(&self.entries[0])
+            .and_then(|indexes| indexes.into_iter().filter(|&index| index >= 0).min());
+
+        all_valid && first == Some(1)
+    }
+
+    // Strings in arrays and objects are all decimal indexes of entries, and
+    // the root references the entry after it
+    fn is_flatted(&self) -> bool {
+        let references = |entry: &Value| -> Vec<Option<u64>> {
+            let members: Vec<&Value> = match entry {
+                Value::Array(arr) => arr.iter().collect(),
+                Value::Object(obj) => obj.values().collect(),
+                _ => Vec::new(),
+            };
+
+            members
+                .into_iter()
+                .filter_map(Value::as_str)
+                .map(|s| {
+                    s.bytes()
+                        .all(|b| b.is_ascii_digit())
+                        .then(|| s.parse().ok())
+                        .flatten()
+                })
+                .collect()
+        };
+
+        let all_valid = self.entries.iter().all(|entry| {
+            references(entry)
+                .into_iter()
+                .all(|index| index.is_some_and(|index| self.holds(index)))
+        });
+        let first = references(&self.entries[0]).into_iter().flatten().min();
+
+        all_valid && first == Some(1)
+    }
+}
+
+// Promises of the native format, `["P", index]`, anywhere in `value`
+fn has_promise(value: &Value) -> bool {
+    match value {
+        Value::Array(arr) => match arr.as_slice() {
+            [Value::String(p), index] if p == "P" && index.is_i64() => true,
+            items => items.iter().any(has_promise),
+        },
+        Value::Object(obj) => obj.values().any(has_promise),
+        _ => false,
+    }
+}
+
+// Whether `entry` is a typed value, `[name, ...]`, of one of `types`
+fn type_name(entry: &Value, types: &[&str]) -> bool {
+    entry
+        .as_array()
+        .and_then(|arr| arr.first())
+        .and_then(Value::as_str)
+        .is_some_and(|name| types.contains(&name))
+}
+
+/// Reads a whole payload of a dialect other than the native one and decodes
+/// it into a tree.
+pub(crate) fn decode<L: Lines>(
+    mut lines: L,
+    options: DecodeOptions,
+) -> Result<JSONDecoder, DecodeError> {
+    let mut syntax: Box<dyn Syntax> = match options.dialect {
+        Dialect::Native => unreachable!("native payloads are decoded line by line"),
+        Dialect::Devalue => Box::new(Devalue { nuxt: false }),
+        Dialect::Nuxt => Box::new(Devalue { nuxt: true }),
+        Dialect::Flatted => Box::new(Flatted),
+        Dialect::Flight => Box::new(Flight::default()),
+        Dialect::Plain => Box::new(Plain),
+    };
+
+    let mut decoder = load(&mut lines, syntax.as_mut(), options)?;
+    decoder.decoded_data = evaluate(&decoder, syntax.as_ref())?;
+
+    Ok(decoder)
+}
+
+#[instrument(name = "load", skip_all, fields(lines, entries))]
+fn load<L: Lines>(
+    lines: &mut L,
+    syntax: &mut dyn Syntax,
+    options: DecodeOptions,
+) -> Result<JSONDecoder, DecodeError> {
+    let mut text = String::new();
+    let mut line_number = 1;
+
+    loop {
+        let line = lines.next_line(&mut line_number, &options)?;
+
+        if line.is_empty() {
+            break;
+        }
+
+        text.push_str(line);
+        line_number += 1;
+    }
+
+    let text = text.strip_prefix('\u{feff}').unwrap_or(&text);
+    let decoder = syntax
+        .load(text, options)
+        .map_err(|error| relocate(error, text))?;
+
+    Span::current()
+        .record("lines", line_number - 1)
+        .record("entries", decoder.encoded_list.len());
+
+    Ok(decoder)
+}
+
+// Points an error parsing a payload spread over several lines at the line
+// holding it
+fn relocate(mut error: DecodeError, text: &str) -> DecodeError {
+    match &mut error {
+        DecodeError::InvalidJson {
+            line,
+            offset,
+            source,
+            ..
+        } if source.line() > 1 => {
+            *line += source.line() - 1;
+            *offset = source.column().saturating_sub(1);
+        }
+        DecodeError::InvalidRelaxedJson {
+            line,
+            offset,
+            source,
+            ..
+        } => {
+            // JSON5 positions count lines and characters from 0
+            if let Some(position) = source.position()
+                && position.line > 0
+            {
+                let text = text.trim_start().lines().nth(position.line);
+                let text = text.unwrap_or_default();
+
+                *line += position.line;
+                *offset = text
+                    .char_indices()
+                    .nth(position.column)
+                    .map_or(text.len(), |(i, _)| i);
+            }
+        }
+        _ => {}
+    }
+
+    error
+}
+
+#[instrument(name = "decode", skip_all)]
+fn evaluate(decoder: &JSONDecoder, syntax: &dyn Syntax) -> Result<Value, DecodeError> {
+    let root = syntax.root(decoder)?;
+
+    Resolver::new(decoder, syntax).decode(root)
+}
+
+// A non-finite number as configured by `DecodeOptions::non_finite`, `null` by
+// default as `JSON.stringify` writes them
+pub(crate) fn non_finite<'a>(decoder: &JSONDecoder, number: &'static str) -> Child<'a> {
+    match decoder.options.non_finite.unwrap_or_default() {
+        NonFinite::Null => Child::Value(Value::Null),
+        NonFinite::String => Child::Value(Value::from(number)),
+        NonFinite::Error => Child::Broken(DecodeError::NonFiniteNumber { number }),
+    }
+}
+
+// BigInts written as their decimal digits become numbers when they fit in 64
+// bits and strings otherwise
+pub(crate) fn big_int(digits: &str) -> Value {
+    digits
+        .parse::<i64>()
+        .map(Value::from)
+        .or_else(|_| digits.parse::<u64>().map(Value::from))
+        .unwrap_or_else(|_| Value::from(digits))
+}
+
+// Malformed fragment of the dialect being decoded
+pub(crate) fn invalid(decoder: &JSONDecoder, reason: String) -> DecodeError {
+    DecodeError::InvalidFragment {
+        dialect: decoder.options.dialect.name(),
+        reason,
+    }
+}
@@ -0,0 +1,26 @@
+use super::{Expand, Node, Syntax};
+use crate::{DecodeError, DecodeOptions, JSONDecoder, parse_value};
+use serde_json::Value;
+
+/// JSON that is already decoded: one document, its own only entry, which
+/// decodes to itself.
+pub(crate) struct Plain;
+
+impl Syntax for Plain {
+    fn load(&mut self, text: &str, options: DecodeOptions) -> Result<JSONDecoder, DecodeError> {
+        let document = parse_value(text, 0, 1, "JSON document", &options)?;
+
+        Ok(JSONDecoder::new(vec![document], 1, options))
+    }
+}
+
+impl Expand for Plain {
+    fn expand<'a>(
+        &self,
+        _decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        _location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError> {
+        Ok(Node::Leaf(fragment.clone()))
+    }
+}
@@ -0,0 +1,164 @@
+use crate::EncodeError;
+use serde_json::{Map, Number, Value};
+use std::{collections::HashMap, io::Write};
+
+/// Index-encoded representation of a plain JSON value.
+///
+/// Every distinct subtree is stored once: repeated objects, arrays, strings
+/// and scalars all share a single entry, and object keys reuse the same
+/// string entries as values.
+pub struct JSONEncoder {
+    encoded_list: Vec<Value>,
+    // (promise entry index, first entry of its continuation line)
+    continuations: Vec<(usize, usize)>,
+}
+
+struct EncodeState<'a> {
+    encoded_list: Vec<Value>,
+    dedup: HashMap<String, usize>,
+    defer: &'a [&'a str],
+    pending: Vec<(usize, String, &'a Value)>,
+}
+
+impl JSONEncoder {
+    /// Encodes `value` into a single entry list without continuation lines.
+    pub fn from_value(value: &Value) -> Result<Self, EncodeError> {
+        Self::with_deferred(value, &[])
+    }
+
+    /// Encodes `value`, emitting the subtrees located at the `defer` JSON
+    /// Pointers as `["P", N]` promises resolved by `P<N>:` continuation lines.
+    pub fn with_deferred(value: &Value, defer: &[&str]) -> Result<Self, EncodeError> {
+        if defer.contains(&"") {
+            return Err(EncodeError::DeferredRoot);
+        }
+
+        for path in defer {
+            if value.pointer(path).is_none() {
+                return Err(EncodeError::DeferredPathNotFound {
+                    path: path.to_string(),
+                });
+            }
+        }
+
+        let mut state = EncodeState {
+            encoded_list: Vec::new(),
+            dedup: HashMap::new(),
+            defer,
+            pending: Vec::new(),
+        };
+
+        // The root always lives at index 0
+        state.encode_root(value, "");
+
+        let mut continuations = Vec::new();
+        let mut next = 0;
+
+        // Deferred values may contain further deferred values, so keep going
+        // until every promise has its own continuation line
+        while next < state.pending.len() {
+            let (promise, path, deferred) = state.pending[next].clone();
+            continuations.push((promise, state.encoded_list.len()));
+            state.encode_root(deferred, &path);
+            next += 1;
+        }
+
+        Ok(JSONEncoder {
+            encoded_list: state.encoded_list,
+            continuations,
+        })
+    }
+
+    /// Returns the flat entry list, including the continuation entries.
+    pub fn encoded_list(&self) -> &[Value] {
+        &self.encoded_list
+    }
+
+    /// Writes the encoded payload: the entry array on the first line followed
+    /// by one `P<N>:` line per deferred value.
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), EncodeError> {
+        let main_end = self
+            .continuations
+            .first()
+            .map_or(self.encoded_list.len(), |&(_, start)| start);
+
+        serde_json::to_writer(&mut writer, &self.encoded_list[..main_end])?;
+        writeln!(writer)?;
+
+        for (i, &(promise, start)) in self.continuations.iter().enumerate() {
+            let end = self
+                .continuations
+                .get(i + 1)
+                .map_or(self.encoded_list.len(), |&(_, next)| next);
+
+            write!(writer, "P{promise}:")?;
+            serde_json::to_writer(&mut writer, &self.encoded_list[start..end])?;
+            writeln!(writer)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'a> EncodeState<'a> {
+    fn encode_root(&mut self, value: &'a Value, path: &str) {
+        // Reserve the first slot so the value is at the start of its line
+        let slot = self.encoded_list.len();
+        self.encoded_list.push(Value::Null);
+        let entry = self.encode_children(value, path);
+        self.encoded_list[slot] = entry;
+    }
+
+    fn encode_children(&mut self, value: &'a Value, path: &str) -> Value {
+        match value {
+            Value::Array(arr) => Value::Array(
+                arr.iter()
+                    .enumerate()
+                    .map(|(i, item)| self.encode_value(item, &format!("{path}/{i}")))
+                    .map(|index| Value::Number(Number::from(index)))
+                    .collect(),
+            ),
+            Value::Object(obj) => {
+                let mut entry = Map::new();
+
+                for (key, item) in obj {
+                    let key_index = self.intern(Value::String(key.clone()));
+                    let pointer = format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"));
+                    let index = self.encode_value(item, &pointer);
+                    entry.insert(format!("_{key_index}"), Value::Number(Number::from(index)));
+                }
+
+                Value::Object(entry)
+            }
+            v => v.clone(),
+        }
+    }
+
+    fn encode_value(&mut self, value: &'a Value, path: &str) -> usize {
+        if self.defer.contains(&path) {
+            let promise = self.encoded_list.len();
+            self.encoded_list
+                .push(Value::Array(vec![Value::from("P"), Value::from(promise)]));
+            self.pending.push((promise, path.to_string(), value));
+
+            return promise;
+        }
+
+        let entry = self.encode_children(value, path);
+        self.intern(entry)
+    }
+
+    fn intern(&mut self, entry: Value) -> usize {
+        let key = entry.to_string();
+
+        if let Some(&index) = self.dedup.get(&key) {
+            return index;
+        }
+
+        let index = self.encoded_list.len();
+        self.encoded_list.push(entry);
+        self.dedup.insert(key, index);
+
+        index
+    }
+}
@@ -0,0 +1,282 @@
+use crate::path::abbreviate_path;
+use serde::de;
+use serde_json::Value;
+use std::{fmt::Display, io};
+use thiserror::Error;
+
+/// Errors produced while reading or decoding an encoded payload.
+#[derive(Debug, Error)]
+pub enum DecodeError {
+    /// Reading an input line failed.
+    #[error("Failed to read line {line}")]
+    Io {
+        line: usize,
+        #[source]
+        source: io::Error,
+    },
+
+    /// The entry array or a continuation line is not valid JSON.
+    ///
+    /// `context` names the part of the payload on the line, such as
+    /// `entry array` or `P17 line`, and `offset` is the byte offset of the
+    /// error in the input line.
+    #[error("Invalid JSON array on line {line} ({context}), byte offset {offset}")]
+    InvalidJson {
+        line: usize,
+        context: String,
+        offset: usize,
+        #[source]
+        source: serde_json::Error,
+    },
+
+    /// Like [`DecodeError::InvalidJson`], for input parsed in relaxed mode.
+    #[error("Invalid JSON5 array on line {line} ({context}), byte offset {offset}")]
+    InvalidRelaxedJson {
+        line: usize,
+        context: String,
+        offset: usize,
+        #[source]
+        source: json5::Error,
+    },
+
+    /// A `P<index>:` continuation line is malformed or targets an invalid entry.
+    #[error("Invalid extra line {line}: {reason}")]
+    InvalidContinuation { line: usize, reason: String },
+
+    /// An index points outside of the entry list.
+    #[error("Index {index} out of bounds for {len} entries")]
+    IndexOutOfBounds { index: i128, len: usize },
+
+    /// A value used as an index is not an integer.
+    #[error("Invalid number format: {value}")]
+    InvalidIndex { value: Value },
+
+    /// An object key does not match the `_<index>` format.
+    #[error("Invalid K-index format: {key:?}")]
+    InvalidKey { key: String },
+
+    /// An object key points at an entry that is not a string.
+    #[error("Entry {index} is not a string key")]
+    InvalidKeyEntry { index: usize },
+
+    /// Two keys of an object resolve to the same string under
+    /// [`DuplicateKeys::Error`](crate::DuplicateKeys::Error).
+    #[error("duplicate key {key:?}{}", format_location(.path))]
+    DuplicateKey { key: String, path: Option<String> },
+
+    /// A reference stands for a non-finite number under
+    /// [`NonFinite::Error`](crate::NonFinite::Error).
+    #[error("{number} has no JSON equivalent")]
+    NonFiniteNumber { number: &'static str },
+
+    /// A `["P", index]` promise has no index element.
+    #[error("Missing index in promise array")]
+    MissingPromiseIndex,
+
+    /// A `["P", index]` promise still points at its own entry, as encoders
+    /// leave the promises no `P<index>:` line resolves.
+    #[error("promise in entry {index} is never resolved")]
+    UnresolvedPromise { index: usize },
+
+    /// A reference of a React Server Components stream points at a chunk the
+    /// stream does not hold, such as a lazy chunk sent in a later response.
+    #[error("Reference to missing chunk {id}")]
+    MissingChunk { id: String },
+
+    /// Resolving an entry requires resolving that same entry again.
+    #[error("reference cycle: {}{}", format_cycle(.cycle), format_location(.path))]
+    ReferenceCycle {
+        cycle: Vec<usize>,
+        path: Option<String>,
+    },
+
+    /// The decoded document nests deeper than the configured limit.
+    #[error("maximum depth of {max_depth} exceeded{}", format_location(.path))]
+    DepthLimitExceeded {
+        max_depth: usize,
+        path: Option<String>,
+    },
+
+    /// Input that decodes but is not in the canonical form enforced by
+    /// [`DecodeOptions::strict`](crate::DecodeOptions::strict).
+    #[error("Non-canonical input{}: {reason}", format_line(.line))]
+    NonCanonical { line: Option<usize>, reason: String },
+
+    /// An error found while decoding the document, with the path decoding
+    /// had reached and the entry holding the offending reference or fragment,
+    /// read from input line `line`. The message shortens paths deeper than
+    /// a few levels to their ends; `path` holds the whole of it.
+    #[error("Failed to decode {} in entry {entry} (line {line})", abbreviate_path(.path))]
+    Located {
+        path: String,
+        entry: usize,
+        line: usize,
+        #[source]
+        source: Box<DecodeError>,
+    },
+
+    /// A fragment of a [`Dialect`](crate::Dialect) other than the native one
+    /// is malformed, such as a value of an unknown type.
+    #[error("Invalid {dialect} fragment: {reason}")]
+    InvalidFragment {
+        dialect: &'static str,
+        reason: String,
+    },
+
+    /// The start of a payload shows signs of several dialects.
+    #[error("Ambiguous payload dialect: could be {}", .candidates.join(", "))]
+    AmbiguousDialect { candidates: Vec<&'static str> },
+
+    /// An operation that only applies to the native format was requested for
+    /// a payload of another [`Dialect`](crate::Dialect).
+    #[error("{operation} is not supported for {dialect} payloads")]
+    UnsupportedDialect {
+        dialect: &'static str,
+        operation: &'static str,
+    },
+
+    /// The entry array holds no root entry.
+    #[error("Empty entry list")]
+    EmptyEntryList,
+
+    /// The decoded data does not match the requested Rust type.
+    #[error("{0}")]
+    Deserialize(String),
+}
+
+fn format_cycle(cycle: &[usize]) -> String {
+    let indexes: Vec<String> = cycle.iter().map(usize::to_string).collect();
+    indexes.join(" -> ")
+}
+
+fn format_line(line: &Option<usize>) -> String {
+    line.map(|line| format!(" on line {line}"))
+        .unwrap_or_default()
+}
+
+fn format_location(path: &Option<String>) -> String {
+    path.as_ref()
+        .map(|path| format!(" at path {}", abbreviate_path(path)))
+        .unwrap_or_default()
+}
+
+impl DecodeError {
+    /// The underlying error, without the location added by
+    /// [`DecodeError::Located`].
+    pub fn innermost(&self) -> &DecodeError {
+        match self {
+            DecodeError::Located { source, .. } => source.innermost(),
+            error => error,
+        }
+    }
+
+    /// A stable snake_case name of the kind of error, such as
+    /// `index_out_of_bounds`, looking through [`DecodeError::Located`].
+    pub fn kind(&self) -> &'static str {
+        match self {
+            DecodeError::Io { .. } => "io",
+            DecodeError::InvalidJson { .. } => "invalid_json",
+            DecodeError::InvalidRelaxedJson { .. } => "invalid_relaxed_json",
+            DecodeError::InvalidContinuation { .. } => "invalid_continuation",
+            DecodeError::IndexOutOfBounds { .. } => "index_out_of_bounds",
+            DecodeError::InvalidIndex { .. } => "invalid_index",
+            DecodeError::InvalidKey { .. } => "invalid_key",
+            DecodeError::InvalidKeyEntry { .. } => "invalid_key_entry",
+            DecodeError::DuplicateKey { .. } => "duplicate_key",
+            DecodeError::NonFiniteNumber { .. } => "non_finite_number",
+            DecodeError::MissingPromiseIndex => "missing_promise_index",
+            DecodeError::UnresolvedPromise { .. } => "unresolved_promise",
+            DecodeError::MissingChunk { .. } => "missing_chunk",
+            DecodeError::ReferenceCycle { .. } => "reference_cycle",
+            DecodeError::DepthLimitExceeded { .. } => "depth_limit_exceeded",
+            DecodeError::NonCanonical { .. } => "non_canonical",
+            DecodeError::Located { source, .. } => source.kind(),
+            DecodeError::InvalidFragment { .. } => "invalid_fragment",
+            DecodeError::AmbiguousDialect { .. } => "ambiguous_dialect",
+            DecodeError::UnsupportedDialect { .. } => "unsupported_dialect",
+            DecodeError::EmptyEntryList => "empty_entry_list",
+            DecodeError::Deserialize(_) => "deserialize",
+        }
+    }
+
+    /// Input line the error was found on, when known.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            DecodeError::Io { line, .. }
+            | DecodeError::InvalidJson { line, .. }
+            | DecodeError::InvalidRelaxedJson { line, .. }
+            | DecodeError::InvalidContinuation { line, .. }
+            | DecodeError::Located { line, .. } => Some(*line),
+            DecodeError::NonCanonical { line, .. } => *line,
+            _ => None,
+        }
+    }
+
+    /// Entry holding the offending reference or fragment, when known.
+    pub fn entry(&self) -> Option<usize> {
+        match self {
+            DecodeError::Located { entry, .. } => Some(*entry),
+            _ => None,
+        }
+    }
+
+    /// Location in the decoded document the error was found at, when known.
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            DecodeError::Located { path, .. } => Some(path),
+            DecodeError::DuplicateKey { path, .. }
+            | DecodeError::ReferenceCycle { path, .. }
+            | DecodeError::DepthLimitExceeded { path, .. } => path.as_deref(),
+            _ => None,
+        }
+    }
+
+    // Wraps the error in its location, which replaces any path it already has
+    pub(crate) fn located(self, path: String, entry: usize, line: usize) -> Self {
+        let source = match self {
+            DecodeError::Located { .. } => return self,
+            DecodeError::DuplicateKey { key, .. } => DecodeError::DuplicateKey { key, path: None },
+            DecodeError::ReferenceCycle { cycle, .. } => {
+                DecodeError::ReferenceCycle { cycle, path: None }
+            }
+            DecodeError::DepthLimitExceeded { max_depth, .. } => DecodeError::DepthLimitExceeded {
+                max_depth,
+                path: None,
+            },
+            error => error,
+        };
+
+        DecodeError::Located {
+            path,
+            entry,
+            line,
+            source: Box::new(source),
+        }
+    }
+}
+
+impl de::Error for DecodeError {
+    fn custom<T: Display>(msg: T) -> Self {
+        DecodeError::Deserialize(msg.to_string())
+    }
+}
+
+/// Errors produced while encoding a plain JSON value.
+#[derive(Debug, Error)]
+pub enum EncodeError {
+    /// The root value was requested as a deferred value.
+    #[error("The root value can not be deferred")]
+    DeferredRoot,
+
+    /// A deferred JSON Pointer does not match any value.
+    #[error("Deferred path not found: {path}")]
+    DeferredPathNotFound { path: String },
+
+    /// Writing the encoded payload failed.
+    #[error("Failed to write the encoded payload")]
+    Io(#[from] io::Error),
+
+    /// Serializing an entry line failed.
+    #[error("Failed to serialize the encoded payload")]
+    Json(#[from] serde_json::Error),
+}
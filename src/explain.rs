@@ -0,0 +1,228 @@
+use crate::{
+    DecodeError, DecodeOptions, JSONDecoder,
+    lines::ReadLines,
+    path::{PathSegment, format_path},
+};
+use serde_json::Value;
+use std::io::BufRead;
+use tracing::instrument;
+
+/// How one value of the decoded document was resolved.
+#[derive(Debug)]
+pub struct Resolution<'a> {
+    /// Location of the value in the decoded document.
+    pub path: &'a [PathSegment],
+    /// Entry holding the value; for an inline fragment, the entry it is
+    /// nested in.
+    pub entry: usize,
+    /// Whether the value is an inline fragment of `entry` rather than the
+    /// entry itself, as non-finite numbers are too.
+    pub inline: bool,
+    /// Entry holding the key, for object members.
+    pub key_entry: Option<usize>,
+    /// Promise entries followed, in order, to reach `entry`.
+    pub promises: &'a [usize],
+    /// Input line `entry` was read from, see [`JSONDecoder::entry_line`].
+    pub line: usize,
+}
+
+// A child waiting to be resolved and reported, below the value at `parent`
+struct Task<'a> {
+    parent: Vec<PathSegment>,
+    child: Child<'a>,
+    // Entry the child is written in
+    entry: usize,
+    // Entries on the way from the root, to detect cycles
+    ancestors: Vec<usize>,
+}
+
+enum Child<'a> {
+    Root,
+    Element(usize, &'a Value),
+    Member(&'a str, &'a Value),
+}
+
+/// Walks an encoded payload from `reader` in document order and calls `visit`
+/// for every value with the entry it was resolved from.
+///
+/// Values are reported before their children, so when the payload is
+/// malformed every value up to the broken one has been visited by the time
+/// the error is returned.
+///
+/// ```no_run
+/// use json_decoder::{DecodeOptions, explain, format_path};
+/// use std::{fs::File, io::BufReader};
+///
+/// let f = File::open("sample_in.txt")?;
+/// explain(BufReader::new(f), DecodeOptions::default(), |resolution| {
+///     println!("{} <- entry {}", format_path(resolution.path), resolution.entry);
+/// })?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[instrument(skip_all)]
+pub fn explain<R: BufRead>(
+    reader: R,
+    options: DecodeOptions,
+    mut visit: impl FnMut(&Resolution),
+) -> Result<(), DecodeError> {
+    options.native_only("Explaining")?;
+
+    let decoder = JSONDecoder::load(ReadLines::new(reader), options)?;
+    decoder.root()?;
+
+    let max_depth = decoder.options.max_depth;
+    let mut stack = vec![Task {
+        parent: Vec::new(),
+        child: Child::Root,
+        entry: 0,
+        ancestors: Vec::new(),
+    }];
+
+    while let Some(task) = stack.pop() {
+        let Task {
+            parent: mut path,
+            child,
+            mut entry,
+            mut ancestors,
+        } = task;
+        let mut key_entry = None;
+        let mut inline = false;
+        // Problems with a reference are reported in the entry holding it
+        let holder = entry;
+        let located = |error: DecodeError, path: &[PathSegment], entry| {
+            error.located(format_path(path), entry, decoder.entry_line(entry))
+        };
+
+        // Locate the child and what it points at
+        let mut fragment = match child {
+            Child::Root => {
+                enter(&decoder, entry, &mut ancestors).map_err(|e| located(e, &path, entry))?
+            }
+            Child::Element(i, item) => {
+                path.push(PathSegment::Index(i));
+
+                match item {
+                    Value::Number(_) => match decoder.non_finite(item) {
+                        Some(number) => {
+                            inline = true;
+                            number.map_err(|e| located(e, &path, holder))?
+                        }
+                        None => {
+                            entry = decoder
+                                .decode_index(item)
+                                .map_err(|e| located(e, &path, holder))?;
+                            enter(&decoder, entry, &mut ancestors)
+                                .map_err(|e| located(e, &path, holder))?
+                        }
+                    },
+                    f => {
+                        inline = true;
+                        f
+                    }
+                }
+            }
+            Child::Member(key, value) => {
+                let decoded = decoder
+                    .decode_key(key)
+                    .map_err(|e| located(e, &path, holder))?;
+                path.push(PathSegment::Key(String::from(decoded)));
+                key_entry = Some(
+                    decoder
+                        .key_entry(key)
+                        .map_err(|e| located(e, &path, holder))?,
+                );
+
+                match decoder.non_finite(value) {
+                    Some(number) => {
+                        inline = true;
+                        number.map_err(|e| located(e, &path, holder))?
+                    }
+                    None => {
+                        entry = decoder
+                            .decode_index(value)
+                            .map_err(|e| located(e, &path, holder))?;
+                        enter(&decoder, entry, &mut ancestors)
+                            .map_err(|e| located(e, &path, holder))?
+                    }
+                }
+            }
+        };
+
+        let mut promises = Vec::new();
+
+        while let Value::Array(arr) = fragment {
+            let Some(target) = decoder
+                .promise_index(arr)
+                .map_err(|e| located(e, &path, entry))?
+            else {
+                break;
+            };
+
+            promises.push(entry);
+            fragment =
+                enter(&decoder, target, &mut ancestors).map_err(|e| located(e, &path, entry))?;
+            entry = target;
+            inline = false;
+        }
+
+        visit(&Resolution {
+            path: &path,
+            entry,
+            inline,
+            key_entry,
+            promises: &promises,
+            line: decoder.entry_line(entry),
+        });
+
+        if (fragment.is_array() || fragment.is_object()) && path.len() >= max_depth {
+            let error = DecodeError::DepthLimitExceeded {
+                max_depth,
+                path: None,
+            };
+            return Err(located(error, &path, entry));
+        }
+
+        let task = |child| Task {
+            parent: path.clone(),
+            child,
+            entry,
+            ancestors: ancestors.clone(),
+        };
+
+        // Children are pushed in reverse so they are visited in order
+        match fragment {
+            Value::Array(arr) => {
+                for (i, item) in arr.iter().enumerate().rev() {
+                    stack.push(task(Child::Element(i, item)));
+                }
+            }
+            Value::Object(obj) => {
+                for (key, value) in obj.iter().rev() {
+                    stack.push(task(Child::Member(key, value)));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Follows a reference to the entry at `index`, which must not be one of the
+// entries being resolved already
+fn enter<'a>(
+    decoder: &'a JSONDecoder,
+    index: usize,
+    ancestors: &mut Vec<usize>,
+) -> Result<&'a Value, DecodeError> {
+    if let Some(start) = ancestors.iter().position(|&i| i == index) {
+        let mut cycle = ancestors.split_off(start);
+        cycle.push(index);
+
+        return Err(decoder.cycle_error(cycle));
+    }
+
+    ancestors.push(index);
+
+    Ok(&decoder.encoded_list[index])
+}
@@ -0,0 +1,160 @@
+use crate::{DecodeError, JSONDecoder, StreamingDecoder};
+use serde_json::Value;
+
+/// Where in an entry an index reference appears.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ReferenceKind {
+    /// A number inside an array.
+    Element,
+    /// An `_<index>` object key.
+    Key,
+    /// An object value.
+    Value,
+    /// The target of a `["P", index]` promise.
+    Promise,
+}
+
+/// One index reference from an entry to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Reference {
+    /// Entry holding the reference.
+    pub from: usize,
+    /// Entry being referenced.
+    pub to: usize,
+    pub kind: ReferenceKind,
+}
+
+impl JSONDecoder {
+    /// Returns the entry list, with the continuation lines merged in.
+    ///
+    /// Decoding on one thread moves the strings, numbers and booleans it
+    /// references into the decoded value, leaving `null` in their place;
+    /// strings naming keys stay, as does every entry of a [`StreamingDecoder`].
+    pub fn encoded_list(&self) -> &[Value] {
+        &self.encoded_list
+    }
+
+    /// Returns the input line the entry at `index` was read from: that of the
+    /// entry array, or the number of the `P<index>:` line that appended it.
+    pub fn entry_line(&self, index: usize) -> usize {
+        let line = self
+            .entry_lines
+            .partition_point(|&(start, _)| start <= index)
+            .saturating_sub(1);
+
+        self.entry_lines[line].1
+    }
+
+    /// Returns the number of input lines the payload was read from: the entry
+    /// array and its `P<index>:` lines.
+    pub fn line_count(&self) -> usize {
+        self.entry_lines.len()
+    }
+
+    /// Returns, for every entry, whether it is reachable from the root entry
+    /// through index references.
+    pub fn reachable(&self) -> Result<Vec<bool>, DecodeError> {
+        self.root()?;
+
+        let mut reachable = vec![false; self.encoded_list.len()];
+        reachable[0] = true;
+        let mut pending = vec![0];
+
+        while let Some(index) = pending.pop() {
+            for reference in self.references(index)? {
+                if !reachable[reference.to] {
+                    reachable[reference.to] = true;
+                    pending.push(reference.to);
+                }
+            }
+        }
+
+        Ok(reachable)
+    }
+
+    /// Lists the references held by the entry at `index`, including those
+    /// inside its inline fragments.
+    pub fn references(&self, index: usize) -> Result<Vec<Reference>, DecodeError> {
+        self.options.native_only("Inspecting references")?;
+
+        let mut references = Vec::new();
+        let reference = |to, kind| Reference {
+            from: index,
+            to,
+            kind,
+        };
+
+        let Some(entry) = self.encoded_list.get(index) else {
+            return Err(DecodeError::IndexOutOfBounds {
+                index: index as i128,
+                len: self.encoded_list.len(),
+            });
+        };
+
+        let mut stack = vec![entry];
+
+        while let Some(fragment) = stack.pop() {
+            match fragment {
+                Value::Array(arr) => match self.promise_index(arr)? {
+                    Some(target) => references.push(reference(target, ReferenceKind::Promise)),
+                    None => {
+                        for item in arr {
+                            match item {
+                                // Non-finite numbers are not references
+                                Value::Number(_) if self.non_finite(item).is_some() => {}
+                                Value::Number(_) => references.push(reference(
+                                    self.decode_index(item)?,
+                                    ReferenceKind::Element,
+                                )),
+                                // Inline fragments belong to the same entry
+                                f => stack.push(f),
+                            }
+                        }
+                    }
+                },
+                Value::Object(obj) => {
+                    for (key, value) in obj {
+                        references.push(reference(self.key_entry(key)?, ReferenceKind::Key));
+
+                        if self.non_finite(value).is_none() {
+                            references
+                                .push(reference(self.decode_index(value)?, ReferenceKind::Value));
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        Ok(references)
+    }
+}
+
+// The entry table is all a streaming decoder holds, so it can be inspected
+// even when the payload does not decode
+impl StreamingDecoder {
+    /// See [`JSONDecoder::encoded_list`].
+    pub fn encoded_list(&self) -> &[Value] {
+        self.decoder.encoded_list()
+    }
+
+    /// See [`JSONDecoder::entry_line`].
+    pub fn entry_line(&self, index: usize) -> usize {
+        self.decoder.entry_line(index)
+    }
+
+    /// See [`JSONDecoder::line_count`].
+    pub fn line_count(&self) -> usize {
+        self.decoder.line_count()
+    }
+
+    /// See [`JSONDecoder::reachable`].
+    pub fn reachable(&self) -> Result<Vec<bool>, DecodeError> {
+        self.decoder.reachable()
+    }
+
+    /// See [`JSONDecoder::references`].
+    pub fn references(&self, index: usize) -> Result<Vec<Reference>, DecodeError> {
+        self.decoder.references(index)
+    }
+}
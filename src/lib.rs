@@ -0,0 +1,767 @@
+//! Decoder for a proprietary, index-encoded JSON format.
+//!
+//! The encoded payload is a JSON array of entries followed by optional
+//! `P<index>:<json array>` continuation lines. Entry `0` is the decoding root;
+//! numbers inside arrays and object values are indexes into the entry list and
+//! object keys of the form `_<index>` point at the string entry holding the
+//! real key.
+//!
+//! ```no_run
+//! use json_decoder::JSONDecoder;
+//! use std::{fs::File, io::BufReader};
+//!
+//! let f = File::open("sample_in.txt")?;
+//! let decoder = JSONDecoder::from_reader(BufReader::new(f))?;
+//! println!("{}", decoder.decoded_data());
+//! # Ok::<(), Box<dyn std::error::Error>>(())
+//! ```
+//!
+//! [`from_reader`] deserializes a payload straight into any
+//! [`serde::Deserialize`] type without building an intermediate
+//! [`serde_json::Value`], [`StreamingDecoder`] resolves references on demand
+//! while the output is being written, and [`JSONEncoder`] performs the inverse
+//! transformation, packing plain JSON into the encoded format.
+
+mod canonical;
+mod de;
+mod dialect;
+mod encoder;
+mod error;
+mod explain;
+mod graph;
+mod lines;
+mod lint;
+mod number;
+mod options;
+mod path;
+mod resolve;
+mod stream;
+mod strict;
+
+pub use canonical::write_canonical;
+pub use de::{from_reader, from_reader_with_options};
+pub use encoder::JSONEncoder;
+pub use error::{DecodeError, EncodeError};
+pub use explain::{Resolution, explain};
+pub use graph::{Reference, ReferenceKind};
+pub use lint::{Problem, lint};
+pub use number::NativeNumbers;
+pub use options::{
+    DEFAULT_MAX_DEPTH, DecodeOptions, Dialect, DuplicateKeys, InvalidUtf8, NonFinite, Placeholder,
+};
+pub use path::{PathSegment, format_path, format_pointer};
+pub use stream::StreamingDecoder;
+
+use lines::{Lines, ReadLines, SliceLines};
+#[cfg(feature = "arbitrary_precision")]
+use number::NegativeZeros;
+use regex::Regex;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Number, Value, json};
+use std::{
+    collections::{HashMap, hash_map::Entry},
+    io::BufRead,
+    sync::LazyLock,
+};
+use stream::RawLiteral;
+use tracing::{Span, instrument};
+
+// Indexes turbo-stream reserves for non-finite numbers, `NAN`,
+// `NEGATIVE_INFINITY` and `POSITIVE_INFINITY` in its `src/utils.ts`
+const NAN: i64 = -2;
+const NEGATIVE_INFINITY: i64 = -3;
+const POSITIVE_INFINITY: i64 = -6;
+
+// Non-finite numbers as written by `NonFinite::String`
+static NON_FINITE: LazyLock<[Value; 3]> =
+    LazyLock::new(|| ["NaN", "-Infinity", "Infinity"].map(Value::from));
+
+// A `P<index>:` line parsed but not merged into the entry list yet
+struct Continuation {
+    line: usize,
+    p_index: Result<i128, String>,
+    entries: Vec<Value>,
+    raw_literals: Vec<Option<RawLiteral>>,
+    // Outcome of the strict checks of the line
+    canonical: Result<(), DecodeError>,
+}
+
+/// Decoded representation of an index-encoded JSON payload.
+pub struct JSONDecoder {
+    encoded_list: Vec<Value>,
+    // Entries written verbatim when streamed, with `raw_literals` only
+    raw_literals: Vec<Option<RawLiteral>>,
+    // First entry index contributed by each input line, with its line number
+    entry_lines: Vec<(usize, usize)>,
+    decoded_data: Value,
+    key_index_re: Regex,
+    p_index_re: Regex,
+    options: DecodeOptions,
+}
+
+impl JSONDecoder {
+    /// Reads an encoded payload from `reader` and decodes it.
+    ///
+    /// The first line must hold the JSON array of entries. Every following
+    /// non-empty line must be a `P<index>:<json array>` continuation line; reading
+    /// stops at the first blank line or at end of input.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, DecodeError> {
+        Self::from_reader_with_options(reader, DecodeOptions::default())
+    }
+
+    /// Reads an encoded payload from `reader` and decodes it with `options`.
+    ///
+    /// Loading, every continuation line and decoding are recorded as
+    /// [`tracing`] spans.
+    pub fn from_reader_with_options<R: BufRead>(
+        reader: R,
+        options: DecodeOptions,
+    ) -> Result<Self, DecodeError> {
+        Self::decode(ReadLines::new(reader), options)
+    }
+
+    /// Decodes an encoded payload held in memory, such as a memory-mapped
+    /// file.
+    ///
+    /// Lines are parsed in place instead of being copied out of a reader
+    /// first.
+    pub fn from_slice(input: &[u8]) -> Result<Self, DecodeError> {
+        Self::from_slice_with_options(input, DecodeOptions::default())
+    }
+
+    /// Decodes an encoded payload held in memory with `options`.
+    pub fn from_slice_with_options(
+        input: &[u8],
+        options: DecodeOptions,
+    ) -> Result<Self, DecodeError> {
+        Self::decode(SliceLines::new(input), options)
+    }
+
+    fn decode<L: Lines>(lines: L, options: DecodeOptions) -> Result<Self, DecodeError> {
+        if options.dialect != Dialect::Native {
+            return dialect::decode(lines, options);
+        }
+
+        let mut decoder = Self::load(lines, options)?;
+        decoder.root()?;
+        decoder.decoded_data = resolve::decode_root(&mut decoder)?;
+
+        Ok(decoder)
+    }
+
+    /// Returns the fully decoded JSON value.
+    pub fn decoded_data(&self) -> &Value {
+        &self.decoded_data
+    }
+
+    /// Consumes the decoder and returns the fully decoded JSON value.
+    pub fn into_value(self) -> Value {
+        self.decoded_data
+    }
+
+    // Reads the entry list and merges the extra lines without decoding
+    #[instrument(name = "load", skip_all, fields(lines, entries))]
+    fn load<L: Lines>(mut lines: L, options: DecodeOptions) -> Result<Self, DecodeError> {
+        // Read the first line
+        let mut line_number = 1;
+        let line = lines.first_line(&mut line_number, &options)?;
+        strict::check_line(line, line_number, &options)?;
+        let encoded_list = parse_entries(line, 0, line_number, "entry array", &options)?;
+        let raw_literals = stream::raw_literals(line, &encoded_list, &options);
+        let mut decoder = JSONDecoder::new(encoded_list, line_number, options);
+        decoder.raw_literals = raw_literals;
+
+        // Read extra lines
+        let mut continuations = Vec::new();
+
+        loop {
+            line_number += 1;
+            let line = lines.next_line(&mut line_number, &decoder.options)?;
+
+            if line.trim().is_empty() {
+                lines.check_end(line_number, &decoder.options)?;
+                break;
+            }
+
+            continuations.push(decoder.parse_continuation(line, line_number)?);
+        }
+
+        decoder.order_continuations(&mut continuations)?;
+
+        for continuation in continuations {
+            decoder.merge_continuation(continuation)?;
+        }
+
+        Span::current()
+            .record("lines", decoder.entry_lines.len())
+            .record("entries", decoder.encoded_list.len());
+
+        Ok(decoder)
+    }
+
+    fn new(encoded_list: Vec<Value>, line: usize, options: DecodeOptions) -> Self {
+        JSONDecoder {
+            encoded_list,
+            raw_literals: Vec::new(),
+            entry_lines: vec![(0, line)],
+            decoded_data: Value::Null,
+            // Regular expressions to match object indexes keys and extra lines keys
+            key_index_re: Regex::new(&format!(r"^{}(\d+)$", regex::escape(&options.key_prefix)))
+                .expect("valid K-index regex"),
+            p_index_re: Regex::new(&format!(
+                r"^(?i:{})(\d+)$",
+                regex::escape(&options.promise_prefix)
+            ))
+            .expect("valid P-index regex"),
+            options,
+        }
+    }
+
+    // Parses a `P<index>:` line, leaving the promise it targets to be
+    // resolved once every line is read
+    fn parse_continuation(
+        &self,
+        line: &str,
+        line_number: usize,
+    ) -> Result<Continuation, DecodeError> {
+        let (p_index, p_encoded_str) =
+            line.split_once(":")
+                .ok_or_else(|| DecodeError::InvalidContinuation {
+                    line: line_number,
+                    reason: String::from("missing ':' separator"),
+                })?;
+        let context = format!("{} line", p_index.trim());
+        let start = p_index.len() + 1;
+        let entries = parse_entries(p_encoded_str, start, line_number, &context, &self.options)?;
+        let raw_literals = stream::raw_literals(p_encoded_str, &entries, &self.options);
+        let canonical = strict::check_line(line, line_number, &self.options)
+            .and_then(|()| strict::check_prefix(p_index, line_number, &self.options));
+
+        // Ensure the P-index is valid
+        let p_index = self
+            .p_index_re
+            .captures(p_index.trim())
+            .and_then(|c| c.get(1))
+            .and_then(|m| m.as_str().parse::<i128>().ok())
+            .ok_or_else(|| format!("invalid P-index format {:?}", p_index.trim()));
+
+        Ok(Continuation {
+            line: line_number,
+            p_index,
+            entries,
+            raw_literals,
+            canonical,
+        })
+    }
+
+    // Sorts continuation lines by the promise they target, the order encoders
+    // write them in, so that reordered captures keep their indexes. Lines
+    // with a malformed P-index go last.
+    //
+    // Of several lines with the same P-index, the last one written is merged
+    // last and wins; they are rejected in strict mode and reported otherwise.
+    fn order_continuations(&self, continuations: &mut [Continuation]) -> Result<(), DecodeError> {
+        let key = |c: &Continuation| c.p_index.as_ref().map_or(i128::MAX, |&index| index);
+        let misplaced = continuations
+            .windows(2)
+            .find(|pair| key(&pair[0]) > key(&pair[1]))
+            .map(|pair| pair[1].line);
+
+        continuations.sort_by_key(key);
+
+        if let (Some(line), true) = (misplaced, self.options.strict) {
+            return Err(DecodeError::NonCanonical {
+                line: Some(line),
+                reason: String::from("continuation line out of P-index order"),
+            });
+        }
+
+        for pair in continuations.windows(2) {
+            let (Ok(index), Ok(again)) = (&pair[0].p_index, &pair[1].p_index) else {
+                continue;
+            };
+            if index != again {
+                continue;
+            }
+
+            let prefix = &self.options.promise_prefix;
+            let (first, last) = (pair[0].line, pair[1].line);
+
+            match self.options.strict {
+                true => {
+                    return Err(DecodeError::NonCanonical {
+                        line: Some(last),
+                        reason: format!("{prefix}{index} already given on line {first}"),
+                    });
+                }
+                false => self.options.warn(format_args!(
+                    "line {last} gives {prefix}{index} again, replacing line {first}"
+                )),
+            }
+        }
+
+        Ok(())
+    }
+
+    // Points the promise targeted by a continuation line at the entries it
+    // appends. They are appended even when the target is invalid, so that
+    // later lines keep their indexes.
+    #[instrument(level = "debug", skip_all, fields(line = continuation.line, entries))]
+    fn merge_continuation(&mut self, continuation: Continuation) -> Result<(), DecodeError> {
+        let Continuation {
+            line: line_number,
+            p_index,
+            entries: mut encoded_extra,
+            mut raw_literals,
+            canonical,
+        } = continuation;
+        let invalid = |reason: String| DecodeError::InvalidContinuation {
+            line: line_number,
+            reason,
+        };
+
+        let target = p_index.map_err(invalid).and_then(|index| {
+            self.checked_index(index)
+                .map_err(|e| invalid(e.to_string()))
+        });
+
+        // Update the index in the corresponding array
+        let len = self.encoded_list.len();
+        let updated = target.and_then(|index| {
+            let arr = self.encoded_list[index]
+                .as_array_mut()
+                .ok_or_else(|| invalid(format!("entry {index} is not an array")))?;
+
+            let slot = match promise_slot(arr) {
+                Some(Some(slot)) => slot,
+                Some(None) => return Err(invalid(format!("entry {index} promise has no index"))),
+                None if arr.len() == 2 => 1,
+                None => return Err(invalid(format!("entry {index} array length is not 2"))),
+            };
+
+            if self.options.strict && (arr.len() != 2 || arr[0] != "P" || !arr[1].is_number()) {
+                return Err(invalid(format!(
+                    "entry {index} is not a [\"P\", index] promise"
+                )));
+            }
+
+            arr[slot] = Value::Number(Number::from(len as u64));
+            Ok(())
+        });
+
+        // Extend encoded_list with the parsed extra line
+        Span::current().record("entries", encoded_extra.len());
+        self.entry_lines.push((len, line_number));
+        self.encoded_list.append(&mut encoded_extra);
+        self.raw_literals.append(&mut raw_literals);
+
+        canonical.and(updated)
+    }
+
+    fn root(&self) -> Result<&Value, DecodeError> {
+        self.encoded_list.first().ok_or(DecodeError::EmptyEntryList)
+    }
+
+    fn decode_index(&self, value: &Value) -> Result<usize, DecodeError> {
+        match value.as_number().and_then(Number::as_i128) {
+            Some(index) => self.checked_index(index),
+            None => Err(DecodeError::InvalidIndex {
+                value: value.clone(),
+            }),
+        }
+    }
+
+    // The value a reference to a non-finite number stands for, when
+    // `non_finite` recognizes `index` as one
+    fn non_finite(&self, index: &Value) -> Option<Result<&'static Value, DecodeError>> {
+        let mode = self.options.non_finite?;
+        let (number, i) = match index.as_i64()? {
+            NAN => ("NaN", 0),
+            NEGATIVE_INFINITY => ("-Infinity", 1),
+            POSITIVE_INFINITY => ("Infinity", 2),
+            _ => return None,
+        };
+
+        Some(match mode {
+            NonFinite::Null => Ok(&Value::Null),
+            NonFinite::String => Ok(&NON_FINITE[i]),
+            NonFinite::Error => Err(DecodeError::NonFiniteNumber { number }),
+        })
+    }
+
+    // Entry `index` points at, counting from the end when negative
+    fn checked_index(&self, index: i128) -> Result<usize, DecodeError> {
+        let len = self.encoded_list.len();
+
+        if index < 0 && self.options.strict {
+            return Err(DecodeError::NonCanonical {
+                line: None,
+                reason: format!("negative index {index}"),
+            });
+        }
+
+        let r = match usize::try_from(index.unsigned_abs()) {
+            Ok(u) if index >= 0 && u < len => u,
+            Ok(u) if index < 0 && u <= len => len - u,
+            _ => return Err(DecodeError::IndexOutOfBounds { index, len }),
+        };
+
+        Ok(r)
+    }
+
+    // Arrays holding a "P" marker are `["P", index]` promises
+    fn promise_index(&self, arr: &[Value]) -> Result<Option<usize>, DecodeError> {
+        if self.options.strict {
+            strict::check_markers(arr)?;
+        }
+
+        match promise_slot(arr) {
+            Some(Some(slot)) => self.decode_index(&arr[slot]).map(Some),
+            Some(None) => Err(DecodeError::MissingPromiseIndex),
+            None => Ok(None),
+        }
+    }
+
+    // Follows chained promises up to the first entry that is not a promise,
+    // also returning the entries followed
+    fn follow_promises<'a>(
+        &'a self,
+        mut fragment: &'a Value,
+    ) -> Result<(&'a Value, Vec<usize>), DecodeError> {
+        let mut chain = Vec::new();
+
+        while let Value::Array(arr) = fragment {
+            let Some(index) = self.promise_index(arr)? else {
+                break;
+            };
+
+            if let Some(start) = chain.iter().position(|&i| i == index) {
+                let mut cycle = chain.split_off(start);
+                cycle.push(index);
+
+                return Err(self.cycle_error(cycle));
+            }
+
+            chain.push(index);
+            fragment = &self.encoded_list[index];
+        }
+
+        Ok((fragment, chain))
+    }
+
+    // The error for resolving the entries of `cycle`, first and last being
+    // the same; a promise pointing at itself was never resolved instead
+    fn cycle_error(&self, cycle: Vec<usize>) -> DecodeError {
+        if let [index, target] = cycle[..]
+            && index == target
+            && let Some(arr) = self.encoded_list[index].as_array()
+            && let Ok(Some(_)) = self.promise_index(arr)
+        {
+            return DecodeError::UnresolvedPromise { index };
+        }
+
+        DecodeError::ReferenceCycle { cycle, path: None }
+    }
+
+    fn decode_key(&self, key: &str) -> Result<&str, DecodeError> {
+        let index = self.key_entry(key)?;
+
+        self.encoded_list[index]
+            .as_str()
+            .ok_or(DecodeError::InvalidKeyEntry { index })
+    }
+
+    // Decodes the keys of an object and resolves duplicates according to the
+    // options; `location` names the object in errors and warnings
+    fn object_members<'a>(
+        &'a self,
+        obj: &'a Map<String, Value>,
+        location: impl Fn() -> Option<String>,
+    ) -> Result<Vec<(&'a str, &'a Value)>, DecodeError> {
+        let mut members: Vec<(&str, &Value)> = Vec::with_capacity(obj.len());
+        let mut positions = HashMap::with_capacity(obj.len());
+
+        for (key, value) in obj {
+            let key = match self.decode_key(key) {
+                Ok(decoded) => decoded,
+                Err(error) => {
+                    self.tolerate(error, &location, "kept the raw key")?;
+                    key.as_str()
+                }
+            };
+
+            let position = match positions.entry(key) {
+                Entry::Vacant(e) => {
+                    e.insert(members.len());
+                    members.push((key, value));
+                    continue;
+                }
+                Entry::Occupied(e) => *e.get(),
+            };
+
+            match self.options.duplicate_keys {
+                DuplicateKeys::Error => {
+                    return Err(DecodeError::DuplicateKey {
+                        key: key.to_string(),
+                        path: location(),
+                    });
+                }
+                DuplicateKeys::First => {}
+                DuplicateKeys::Last => members[position].1 = value,
+                DuplicateKeys::Warn => {
+                    let at = location().map(|path| format!(" at path {path}"));
+                    self.options.warn(format_args!(
+                        "duplicate key {key:?}{}, keeping the last value",
+                        at.unwrap_or_default()
+                    ));
+                    members[position].1 = value;
+                }
+            }
+        }
+
+        Ok(members)
+    }
+
+    // Stands in for a broken reference in lenient mode
+    fn placeholder(
+        &self,
+        error: DecodeError,
+        location: impl Fn() -> Option<String>,
+    ) -> Result<Value, DecodeError> {
+        let Some(placeholder) = self.options.lenient else {
+            return Err(error);
+        };
+
+        let replacement = match placeholder {
+            Placeholder::Null => "replaced with null",
+            Placeholder::Marker => "replaced with an error marker",
+        };
+        let message = self.tolerate(error, &location, replacement)?;
+
+        Ok(match placeholder {
+            Placeholder::Null => Value::Null,
+            Placeholder::Marker => json!({ "$error": message }),
+        })
+    }
+
+    // Reports a broken reference in lenient mode and returns its description;
+    // any other error is passed on
+    fn tolerate(
+        &self,
+        error: DecodeError,
+        location: &impl Fn() -> Option<String>,
+        replacement: &str,
+    ) -> Result<String, DecodeError> {
+        let (inner, location) = match &error {
+            DecodeError::Located {
+                path,
+                entry,
+                source,
+                ..
+            } => (source.innermost(), Some(format!("{path} in entry {entry}"))),
+            error => (error, location()),
+        };
+
+        let recoverable = matches!(
+            inner,
+            DecodeError::IndexOutOfBounds { .. }
+                | DecodeError::InvalidIndex { .. }
+                | DecodeError::InvalidKey { .. }
+                | DecodeError::InvalidKeyEntry { .. }
+                | DecodeError::MissingPromiseIndex
+                | DecodeError::UnresolvedPromise { .. }
+                | DecodeError::MissingChunk { .. }
+                | DecodeError::ReferenceCycle { .. }
+        );
+
+        if self.options.lenient.is_none() || !recoverable {
+            return Err(error);
+        }
+
+        let message = match location {
+            Some(location) => format!("{inner} at path {location}"),
+            None => inner.to_string(),
+        };
+
+        self.options.warn(format_args!("{message}, {replacement}"));
+
+        Ok(message)
+    }
+
+    // Index of the entry an `_<index>` key points at
+    fn key_entry(&self, key: &str) -> Result<usize, DecodeError> {
+        // Ensure the K-index is valid
+        let index = self
+            .key_index_re
+            .captures(key)
+            .and_then(|c| c.get(1))
+            .and_then(|m| m.as_str().parse::<i128>().ok())
+            .ok_or_else(|| DecodeError::InvalidKey {
+                key: key.to_string(),
+            })?;
+
+        self.checked_index(index)
+    }
+}
+
+// Position of the index in a promise array, `None` when `arr` holds no "P"
+// marker. Besides `["P", index]`, variant encodings put the marker elsewhere
+// or add metadata elements: the index is the first number after the marker,
+// or the nearest one before it, falling back to the element after the marker
+// to report it as malformed.
+fn promise_slot(arr: &[Value]) -> Option<Option<usize>> {
+    let marker = arr.iter().position(|item| item == "P")?;
+    let next = marker + 1;
+
+    let slot = (next..arr.len())
+        .find(|&i| arr[i].is_number())
+        .or_else(|| (0..marker).rev().find(|&i| arr[i].is_number()))
+        .or((next < arr.len()).then_some(next));
+
+    Some(slot)
+}
+
+// Parses one line of entries, as JSON5 in relaxed mode. `text` starts `start`
+// bytes into input line `line`, which holds the `context` part of the payload.
+#[instrument(level = "debug", skip(text, start, options), fields(bytes = text.len()))]
+fn parse_entries(
+    text: &str,
+    start: usize,
+    line: usize,
+    context: &str,
+    options: &DecodeOptions,
+) -> Result<Vec<Value>, DecodeError> {
+    #[cfg(not(feature = "arbitrary_precision"))]
+    let entries: Vec<Value> = parse_json(text, start, line, context, options)?;
+
+    #[cfg(feature = "arbitrary_precision")]
+    let entries = match NegativeZeros::mark(text) {
+        Some(zeros) => {
+            let mut entries: Vec<Value> =
+                parse_marked(&zeros, text, start, line, context, options)?;
+            entries.iter_mut().for_each(|entry| zeros.restore(entry));
+            entries
+        }
+        None => parse_json(text, start, line, context, options)?,
+    };
+
+    #[cfg(feature = "arbitrary_precision")]
+    let entries = match options.arbitrary_precision {
+        true => entries,
+        false => entries.into_iter().map(normalize_numbers).collect(),
+    };
+
+    Ok(entries)
+}
+
+// Parses a single value, like `parse_entries`
+fn parse_value(
+    text: &str,
+    start: usize,
+    line: usize,
+    context: &str,
+    options: &DecodeOptions,
+) -> Result<Value, DecodeError> {
+    #[cfg(not(feature = "arbitrary_precision"))]
+    let value = parse_json(text, start, line, context, options)?;
+
+    #[cfg(feature = "arbitrary_precision")]
+    let value = match NegativeZeros::mark(text) {
+        Some(zeros) => {
+            let mut value = parse_marked(&zeros, text, start, line, context, options)?;
+            zeros.restore(&mut value);
+            value
+        }
+        None => parse_json(text, start, line, context, options)?,
+    };
+
+    #[cfg(feature = "arbitrary_precision")]
+    let value = match options.arbitrary_precision {
+        true => value,
+        false => normalize_numbers(value),
+    };
+
+    Ok(value)
+}
+
+// Parses the text with its `-0` literals marked, reporting errors at their
+// place in the text as written
+#[cfg(feature = "arbitrary_precision")]
+fn parse_marked<T: DeserializeOwned>(
+    zeros: &NegativeZeros,
+    text: &str,
+    start: usize,
+    line: usize,
+    context: &str,
+    options: &DecodeOptions,
+) -> Result<T, DecodeError> {
+    parse_json(&zeros.marked, start, line, context, options)
+        .or_else(|_| parse_json(text, start, line, context, options))
+}
+
+fn parse_json<T: DeserializeOwned>(
+    text: &str,
+    start: usize,
+    line: usize,
+    context: &str,
+    options: &DecodeOptions,
+) -> Result<T, DecodeError> {
+    let trimmed = text.trim();
+    let start = start + (text.len() - text.trim_start().len());
+    let context = context.to_string();
+
+    match options.relaxed {
+        true => json5::from_str(trimmed).map_err(|source| {
+            // JSON5 positions count characters
+            let column = source.position().map_or(0, |position| position.column);
+            let offset = trimmed
+                .char_indices()
+                .nth(column)
+                .map_or(trimmed.len(), |(i, _)| i);
+
+            DecodeError::InvalidRelaxedJson {
+                line,
+                context,
+                offset: start + offset,
+                source,
+            }
+        }),
+        false => serde_json::from_str(trimmed).map_err(|source| DecodeError::InvalidJson {
+            line,
+            context,
+            // serde_json columns count bytes from 1
+            offset: start + source.column().saturating_sub(1),
+            source,
+        }),
+    }
+}
+
+// Numbers keep their literal with arbitrary precision. Only integers beyond
+// 64 bits need it, so every other number is written as it would be without
+#[cfg(feature = "arbitrary_precision")]
+fn normalize_numbers(mut value: Value) -> Value {
+    let mut stack = vec![&mut value];
+
+    while let Some(value) = stack.pop() {
+        match value {
+            Value::Number(n) => {
+                let literal = n.as_str();
+                let digits = literal.strip_prefix('-').unwrap_or(literal);
+
+                // `-0` is read as a float without arbitrary precision
+                if literal != "-0" && digits.bytes().all(|b| b.is_ascii_digit()) {
+                    continue;
+                }
+
+                if let Some(f) = n.as_f64().and_then(Number::from_f64) {
+                    *n = f;
+                }
+            }
+            Value::Array(arr) => stack.extend(arr),
+            Value::Object(obj) => stack.extend(obj.values_mut()),
+            _ => {}
+        }
+    }
+
+    value
+}
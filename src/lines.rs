@@ -0,0 +1,195 @@
+use crate::{DecodeError, DecodeOptions, InvalidUtf8, strict};
+use std::{
+    borrow::Cow,
+    io::{self, BufRead},
+    mem,
+    str::Utf8Error,
+};
+
+// Input lines of a payload, each with its terminator and empty at the end of
+// input
+pub(crate) trait Lines {
+    // Returns the next line, as line `line`; lines skipped for invalid UTF-8
+    // advance `line` past them
+    fn next_line(&mut self, line: &mut usize, options: &DecodeOptions)
+    -> Result<&str, DecodeError>;
+
+    // The last line returned
+    fn last(&self) -> &str;
+
+    // Checks what follows the last line returned, which terminated the payload
+    fn check_end(&mut self, line: usize, options: &DecodeOptions) -> Result<(), DecodeError>;
+
+    // Returns the line holding the entry array, skipping the byte order mark
+    // and blank lines Windows editors may save before it
+    fn first_line(
+        &mut self,
+        line: &mut usize,
+        options: &DecodeOptions,
+    ) -> Result<&str, DecodeError> {
+        let mut first = true;
+
+        loop {
+            let text = self.next_line(line, options)?;
+            let text = match text.strip_prefix(BOM) {
+                Some(text) if first => {
+                    strict::check_leading(*line, "byte order mark", options)?;
+                    text
+                }
+                _ => text,
+            };
+
+            // The end of input is reported as a missing entry array
+            if text.is_empty() || !text.trim().is_empty() {
+                break;
+            }
+
+            strict::check_leading(*line, "blank line", options)?;
+            *line += 1;
+            first = false;
+        }
+
+        let text = self.last();
+        Ok(match first {
+            true => text.strip_prefix(BOM).unwrap_or(text),
+            false => text,
+        })
+    }
+}
+
+const BOM: char = '\u{feff}';
+
+// Lines read one at a time into a reused buffer
+pub(crate) struct ReadLines<R> {
+    reader: R,
+    buffer: String,
+}
+
+impl<R: BufRead> ReadLines<R> {
+    pub(crate) fn new(reader: R) -> Self {
+        ReadLines {
+            reader,
+            buffer: String::new(),
+        }
+    }
+}
+
+impl<R: BufRead> Lines for ReadLines<R> {
+    fn next_line(
+        &mut self,
+        line: &mut usize,
+        options: &DecodeOptions,
+    ) -> Result<&str, DecodeError> {
+        loop {
+            let mut bytes = mem::take(&mut self.buffer).into_bytes();
+            bytes.clear();
+            self.reader
+                .read_until(b'\n', &mut bytes)
+                .map_err(|source| DecodeError::Io {
+                    line: *line,
+                    source,
+                })?;
+
+            let replaced = match String::from_utf8(bytes) {
+                Ok(text) => Some(text),
+                Err(error) => invalid(error.as_bytes(), error.utf8_error(), *line, options)?,
+            };
+
+            match replaced {
+                Some(text) => {
+                    self.buffer = text;
+                    return Ok(&self.buffer);
+                }
+                None => *line += 1,
+            }
+        }
+    }
+
+    fn last(&self) -> &str {
+        &self.buffer
+    }
+
+    fn check_end(&mut self, line: usize, options: &DecodeOptions) -> Result<(), DecodeError> {
+        strict::check_end(&mut self.reader, &self.buffer, line, options)
+    }
+}
+
+// Lines borrowed from an input held in memory, without copying them
+pub(crate) struct SliceLines<'a> {
+    rest: &'a [u8],
+    last: Cow<'a, str>,
+}
+
+impl<'a> SliceLines<'a> {
+    pub(crate) fn new(input: &'a [u8]) -> Self {
+        SliceLines {
+            rest: input,
+            last: Cow::Borrowed(""),
+        }
+    }
+}
+
+impl Lines for SliceLines<'_> {
+    fn next_line(
+        &mut self,
+        line: &mut usize,
+        options: &DecodeOptions,
+    ) -> Result<&str, DecodeError> {
+        loop {
+            let len = match self.rest.iter().position(|&b| b == b'\n') {
+                Some(end) => end + 1,
+                None => self.rest.len(),
+            };
+            let (bytes, rest) = self.rest.split_at(len);
+            self.rest = rest;
+
+            let text = match str::from_utf8(bytes) {
+                Ok(text) => Some(Cow::Borrowed(text)),
+                Err(error) => invalid(bytes, error, *line, options)?.map(Cow::Owned),
+            };
+
+            match text {
+                Some(text) => {
+                    self.last = text;
+                    return Ok(&self.last);
+                }
+                None => *line += 1,
+            }
+        }
+    }
+
+    fn last(&self) -> &str {
+        &self.last
+    }
+
+    fn check_end(&mut self, line: usize, options: &DecodeOptions) -> Result<(), DecodeError> {
+        strict::check_end(&mut self.rest, &self.last, line, options)
+    }
+}
+
+// Handles input line `line` not being valid UTF-8 as configured, returning
+// its replacement or `None` to skip it
+fn invalid(
+    bytes: &[u8],
+    error: Utf8Error,
+    line: usize,
+    options: &DecodeOptions,
+) -> Result<Option<String>, DecodeError> {
+    match options.invalid_utf8 {
+        // Same error as reading the line with `BufRead::read_line`
+        InvalidUtf8::Error => Err(DecodeError::Io {
+            line,
+            source: io::Error::new(io::ErrorKind::InvalidData, error),
+        }),
+        InvalidUtf8::Lossy => {
+            options.warn(format_args!(
+                "line {line} is not valid UTF-8, invalid bytes replaced with U+FFFD"
+            ));
+            Ok(Some(String::from_utf8_lossy(bytes).into_owned()))
+        }
+        InvalidUtf8::SkipLine => {
+            options.warn(format_args!("line {line} is not valid UTF-8, skipped"));
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use crate::{
+    DecodeError, DecodeOptions, JSONDecoder,
+    lines::{Lines, ReadLines},
+    parse_entries, strict,
+};
+use serde_json::Value;
+use std::io::BufRead;
+use tracing::instrument;
+
+/// One problem found while checking an encoded payload.
+#[derive(Debug)]
+pub struct Problem {
+    /// Input line the problem is on.
+    pub line: usize,
+    /// Entry holding the problem, for problems inside an entry.
+    pub entry: Option<usize>,
+    pub error: DecodeError,
+}
+
+/// Checks an encoded payload from `reader` without decoding it and returns
+/// every problem found, in input order.
+///
+/// Besides the checks decoding performs on the way, every entry is inspected,
+/// including those never reached from the root: index bounds, key formats,
+/// continuation lines, promise targets, unresolved promises and reference
+/// cycles.
+#[instrument(skip_all)]
+pub fn lint<R: BufRead>(reader: R, options: DecodeOptions) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    if let Err(error) = options.native_only("Linting") {
+        problems.push(line_problem(1, error));
+        return problems;
+    }
+
+    let mut lines = ReadLines::new(reader);
+    let mut line_number = 1;
+
+    let line = match lines.first_line(&mut line_number, &options) {
+        Ok(line) => line,
+        Err(error) => {
+            problems.push(line_problem(line_number, error));
+            return problems;
+        }
+    };
+
+    if let Err(error) = strict::check_line(line, line_number, &options) {
+        problems.push(line_problem(line_number, error));
+    }
+
+    // Without the entry array there is nothing to check the rest against
+    let encoded_list = match parse_entries(line, 0, line_number, "entry array", &options) {
+        Ok(encoded_list) => encoded_list,
+        Err(error) => {
+            problems.push(line_problem(line_number, error));
+            return problems;
+        }
+    };
+
+    let mut decoder = JSONDecoder::new(encoded_list, line_number, options);
+    let mut continuations = Vec::new();
+
+    loop {
+        line_number += 1;
+
+        match lines.next_line(&mut line_number, &decoder.options) {
+            Ok(line) if line.trim().is_empty() => {
+                if let Err(error) = lines.check_end(line_number, &decoder.options) {
+                    // Trailing data is reported on the line it starts on
+                    let line = match error {
+                        DecodeError::NonCanonical {
+                            line: Some(line), ..
+                        } => line,
+                        _ => line_number,
+                    };
+                    problems.push(line_problem(line, error));
+                }
+                break;
+            }
+            Ok(line) => match decoder.parse_continuation(line, line_number) {
+                Ok(continuation) => continuations.push(continuation),
+                Err(error) => problems.push(line_problem(line_number, error)),
+            },
+            Err(error) => {
+                problems.push(line_problem(line_number, error));
+                break;
+            }
+        }
+    }
+
+    if let Err(error) = decoder.order_continuations(&mut continuations) {
+        let line = match error {
+            DecodeError::NonCanonical {
+                line: Some(line), ..
+            } => line,
+            _ => line_number,
+        };
+        problems.push(line_problem(line, error));
+    }
+
+    for continuation in continuations {
+        let line_number = continuation.line;
+
+        if let Err(error) = decoder.merge_continuation(continuation) {
+            problems.push(line_problem(line_number, error));
+        }
+    }
+
+    if let Err(error) = decoder.root() {
+        problems.push(line_problem(decoder.entry_line(0), error));
+        return problems;
+    }
+
+    let mut edges = Vec::with_capacity(decoder.encoded_list.len());
+
+    for (index, entry) in decoder.encoded_list.iter().enumerate() {
+        let problem = |error| Problem {
+            line: decoder.entry_line(index),
+            entry: Some(index),
+            error,
+        };
+        let mut targets = Vec::new();
+
+        for result in check_entry(&decoder, entry) {
+            match result {
+                Ok(target) => targets.push(target),
+                Err(error) => problems.push(problem(error)),
+            }
+        }
+
+        edges.push(targets);
+    }
+
+    for cycle in cycles(&edges) {
+        let index = cycle[0];
+        problems.push(Problem {
+            line: decoder.entry_line(index),
+            entry: Some(index),
+            error: decoder.cycle_error(cycle),
+        });
+    }
+
+    problems.sort_by_key(|problem| (problem.line, problem.entry));
+    problems
+}
+
+fn line_problem(line: usize, error: DecodeError) -> Problem {
+    Problem {
+        line,
+        entry: None,
+        error,
+    }
+}
+
+// Every value reference of an entry, or the problem with it; keys are only
+// checked since they can not take part in a cycle
+fn check_entry(decoder: &JSONDecoder, entry: &Value) -> Vec<Result<usize, DecodeError>> {
+    let mut results = Vec::new();
+    let mut stack = vec![entry];
+
+    while let Some(fragment) = stack.pop() {
+        match fragment {
+            Value::Array(arr) => match decoder.promise_index(arr) {
+                Ok(Some(target)) => results.push(Ok(target)),
+                Err(error) => results.push(Err(error)),
+                Ok(None) => {
+                    for item in arr {
+                        match item {
+                            Value::Number(_) => results.extend(reference(decoder, item)),
+                            f => stack.push(f),
+                        }
+                    }
+                }
+            },
+            Value::Object(obj) => {
+                for (key, value) in obj {
+                    if let Err(error) = decoder.decode_key(key) {
+                        results.push(Err(error));
+                    }
+
+                    results.extend(reference(decoder, value));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    results
+}
+
+// Reports the cycle closed by every back edge of an iterative depth-first
+// search over the value references
+fn cycles(edges: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    #[derive(Clone, Copy, PartialEq)]
+    enum State {
+        New,
+        Open,
+        Done,
+    }
+
+    let mut state = vec![State::New; edges.len()];
+    let mut cycles = Vec::new();
+
+    for start in 0..edges.len() {
+        if state[start] != State::New {
+            continue;
+        }
+
+        // Entries being visited with the position of their next edge
+        let mut path: Vec<(usize, usize)> = vec![(start, 0)];
+        state[start] = State::Open;
+
+        while let Some((index, next)) = path.last_mut() {
+            let index = *index;
+
+            let Some(&target) = edges[index].get(*next) else {
+                state[index] = State::Done;
+                path.pop();
+                continue;
+            };
+
+            *next += 1;
+
+            match state[target] {
+                State::New => {
+                    state[target] = State::Open;
+                    path.push((target, 0));
+                }
+                State::Open => {
+                    let start = path.iter().position(|&(i, _)| i == target).unwrap_or(0);
+                    let mut cycle: Vec<usize> = path[start..].iter().map(|&(i, _)| i).collect();
+                    cycle.push(target);
+                    cycles.push(cycle);
+                }
+                State::Done => {}
+            }
+        }
+    }
+
+    cycles
+}
+
+// The entry a value reference points at; non-finite numbers point at none
+fn reference(decoder: &JSONDecoder, index: &Value) -> Option<Result<usize, DecodeError>> {
+    match decoder.non_finite(index) {
+        Some(number) => number.err().map(Err),
+        None => Some(decoder.decode_index(index)),
+    }
+}
@@ -1,227 +1,993 @@
-use anyhow::{Context, Result, anyhow};
-use clap::Parser;
-use regex::Regex;
-use serde::Serialize;
-use serde_json::{Map, Number, Serializer, Value, ser::PrettyFormatter};
+mod cli;
+
+use anyhow::{Context, Result, bail};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use cli::{
+    browse::browse,
+    codegen::write_rust,
+    config,
+    destination::DestinationArgs,
+    diagnostic::{self, ErrorFormat, Failure, WriteFailure, diagnose},
+    diff::{diff, to_patch, write_diff},
+    dot::write_dot,
+    input::{InputArgs, Payload, Source},
+    man::generate_man,
+    merge::{MergeStrategy, merge},
+    output::FormatArgs,
+    query::QueryArgs,
+    repl::{evaluate, repl},
+    schema::infer_schema,
+    stats::stats,
+    timings::{self, Counted},
+    transform::TransformArgs,
+    unused::write_unused,
+    validate::validate,
+    warnings::WarningPrinter,
+    watch::watch,
+};
+use json_decoder::{
+    DEFAULT_MAX_DEPTH, DecodeError, DecodeOptions, Dialect, DuplicateKeys, InvalidUtf8,
+    JSONEncoder, NonFinite, Placeholder, Resolution, explain, format_path, lint,
+};
+use rayon::{ThreadPool, ThreadPoolBuilder, prelude::*};
+use serde::Deserialize;
+use serde_json::Value;
 use std::{
-    fs::File,
-    io::{self, BufRead, BufReader},
+    io::{BufRead, Read, Write},
+    panic,
     path::PathBuf,
+    process::ExitCode,
+    thread,
+    time::Instant,
 };
+use tracing::Level;
+use tracing_subscriber::{Layer, filter::Targets, layer::SubscriberExt, util::SubscriberInitExt};
+
+// Stack of the threads processing inputs: serde_json and the output formats
+// write and drop decoded values recursively, one frame per nesting level
+const STACK_SIZE: usize = 256 * 1024 * 1024;
 
+// Deepest documents every output format writes within `STACK_SIZE`
+const MAX_DEPTH_LIMIT: usize = 20_000;
+
+// Deepest documents streamed: streaming takes one nested call per level and
+// looks for cycles along every open fragment, so deeper ones are decoded into
+// a tree first
+const STREAM_MAX_DEPTH: usize = 4096;
+
+/// Decodes index-encoded JSON payloads, and those of other frameworks
+/// deduplicating values into a table of entries, into plain JSON
 #[derive(Parser, Debug)]
+#[command(version)]
 struct Args {
-    /// Encoded JSON file (defaults to stdin)
-    #[arg(short, long)]
-    input: Option<PathBuf>,
-
-    /// Decoded JSON file (defaults to stdout)
-    #[arg(short, long)]
-    output: Option<PathBuf>,
-}
-
-struct JSONDecoder {
-    encoded_list: Vec<Value>,
-    decoded_data: Value,
-    key_index_re: Regex,
-}
-
-impl JSONDecoder {
-    fn new<R: BufRead>(mut reader: R) -> Result<Self> {
-        // Read the first line
-        let mut line = String::new();
-        reader
-            .read_line(&mut line)
-            .with_context(|| "Failed to read the first line")?;
-        let encoded_list: Vec<Value> =
-            serde_json::from_str(&line.trim()).with_context(|| "Invalid JSON array")?;
-        let decoded_data: Value = Value::Null;
-
-        // Regular expression to match object indexes keys
-        let key_index_re = Regex::new(r"^_(\d+)$").with_context(|| "Failed to compile regex")?;
-
-        let mut decoder = JSONDecoder {
-            encoded_list,
-            decoded_data,
-            key_index_re,
-        };
-
-        // Regular expression to match extra lines keys
-        let p_index_re = Regex::new(r"^P(\d+)$").with_context(|| "Failed to compile regex")?;
-
-        // Read extra lines
-        loop {
-            line.clear();
-            reader
-                .read_line(&mut line)
-                .with_context(|| "Failed to read the extra line")?;
-
-            if line.trim().is_empty() {
-                break;
-            }
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    #[command(flatten)]
+    decode: DecodeArgs,
+
+    #[command(flatten)]
+    input: InputArgs,
+
+    #[command(flatten)]
+    destination: DestinationArgs,
+
+    /// Resolve references while writing instead of building the decoded tree,
+    /// failing when the output needs the tree; this is already the default for
+    /// output written as decoded
+    #[arg(long, global = true)]
+    stream: bool,
+
+    /// Copy entries holding no reference, such as strings, to streamed JSON
+    /// output as the input writes them, keeping its spacing and escapes
+    #[arg(long, conflicts_with_all = ["relaxed", "strict"], global = true)]
+    raw_literals: bool,
+
+    /// Encoding of the input
+    #[arg(long, value_enum, default_value_t, global = true)]
+    dialect: DialectKind,
+
+    /// Write input that is already plain JSON, a document starting with an
+    /// object rather than a payload, reformatted instead of failing
+    #[arg(long, global = true)]
+    passthrough: bool,
+
+    /// Maximum nesting depth of the decoded document, or of the JSON input of
+    /// `encode`, at most 20000
+    #[arg(long, default_value_t = DEFAULT_MAX_DEPTH, value_parser = parse_max_depth, global = true)]
+    max_depth: usize,
+
+    /// Accept JSON5 input: comments, trailing commas and single-quoted strings
+    #[arg(long, global = true)]
+    relaxed: bool,
+
+    /// Write numbers exactly as they appear in the input; needs a build with
+    /// the `arbitrary_precision` feature
+    #[arg(long, conflicts_with = "relaxed", global = true)]
+    arbitrary_precision: bool,
+
+    /// What to do with input lines that are not valid UTF-8
+    #[arg(long, value_enum, default_value_t, global = true)]
+    invalid_utf8: InvalidUtf8Policy,
+
+    /// What to do when two keys of an object resolve to the same string
+    #[arg(long, value_enum, default_value_t, global = true)]
+    duplicate_keys: DuplicateKeyPolicy,
+
+    /// Prefix of object keys pointing at a string entry, such as `$` for
+    /// `$<N>` keys
+    #[arg(long, value_name = "PREFIX", default_value = "_", global = true)]
+    key_prefix: String,
+
+    /// Prefix of continuation lines, such as `D` for `D<N>:` lines
+    #[arg(long, value_name = "PREFIX", default_value = "P", global = true)]
+    promise_prefix: String,
+
+    /// Replace broken references with a placeholder and print a warning for
+    /// each instead of failing
+    #[arg(long, value_enum, num_args = 0..=1, default_missing_value = "null", global = true)]
+    lenient: Option<PlaceholderKind>,
+
+    /// Read the indexes -2, -3 and -6 as NaN, -Infinity and Infinity, the way
+    /// JavaScript encoders write them, and decode them as given
+    #[arg(long, value_enum, global = true)]
+    non_finite: Option<NonFiniteKind>,
+
+    /// Reject input that is not in canonical form: stray whitespace, a byte
+    /// order mark, data after the terminating empty line, negative indexes,
+    /// unknown markers and malformed promises
+    #[arg(long, conflicts_with_all = ["relaxed", "lenient"], global = true)]
+    strict: bool,
+
+    /// Process the inputs again whenever one of the input files changes
+    #[arg(long, global = true)]
+    watch: bool,
+
+    /// Print the lines read, the entries loaded and the decode time of every
+    /// input to stderr
+    #[arg(short, long, global = true, conflicts_with = "quiet")]
+    verbose: bool,
+
+    /// Do not print warnings, such as those of --lenient
+    #[arg(short, long, global = true)]
+    quiet: bool,
+
+    /// Print the parse, decode and serialize times, the peak entry count and
+    /// the output size to stderr after the run
+    #[arg(long, global = true)]
+    timings: bool,
+
+    /// How errors are printed to stderr
+    #[arg(long, value_enum, default_value_t, global = true)]
+    error_format: ErrorFormat,
+
+    /// Number of inputs processed concurrently; 0 uses one per CPU
+    #[arg(short, long, default_value_t = 1, global = true)]
+    jobs: usize,
+
+    /// Threads decoding the elements of a root array in parallel; 0 uses one
+    /// per CPU. The decoded tree is built first, as with `--sort-keys`
+    #[arg(long, default_value_t = 1, global = true)]
+    threads: usize,
+
+    /// Check the decoded value against the JSON Schema in FILE, failing with
+    /// every violation and its path instead of writing a value that does not
+    /// match
+    #[arg(long, value_name = "FILE", conflicts_with = "stream", global = true)]
+    validate_schema: Option<PathBuf>,
+
+    /// Write a man page for json_decoder and each of its subcommands to DIR
+    /// instead of processing any input; needs a build with the `man` feature
+    #[arg(long, value_name = "DIR", exclusive = true)]
+    generate_man: Option<PathBuf>,
+
+    #[command(flatten)]
+    query: QueryArgs,
+
+    #[command(flatten)]
+    transform: TransformArgs,
+
+    #[command(flatten)]
+    format: FormatArgs,
+}
 
-            let (p_index, p_encoded_str) = line
-                .split_once(":")
-                .with_context(|| "Invalid extra line format")?;
-
-            // Ensure the P-index is valid
-            let index = decoder.decode_index(
-                p_index_re
-                    .captures(p_index.trim())
-                    .with_context(|| "Invalid P-index format")?
-                    .get(1)
-                    .with_context(|| "Invalid P-index format")?
-                    .as_str()
-                    .parse::<i64>()
-                    .ok(),
-            )?;
-
-            // Update the index in the corresponding array
-            let len = decoder.encoded_list.len();
-            let value = &mut decoder.encoded_list[index];
-            let arr = value
-                .as_array_mut()
-                .with_context(|| "Invalid array format")?;
-
-            if arr.len() != 2 {
-                return Err(anyhow!("Array length is not 2"));
-            }
+// What `decode` writes instead of the decoded document, given before or
+// after it
+#[derive(clap::Args, Clone, Copy, Debug)]
+struct DecodeArgs {
+    /// Print the entry and input line every decoded value was resolved from
+    /// instead of the decoded document
+    #[arg(long, conflicts_with = "stream")]
+    explain: bool,
+
+    /// Print the entry-reference graph in Graphviz DOT format instead of the
+    /// decoded document
+    #[arg(long, conflicts_with_all = ["stream", "explain"])]
+    dot: bool,
+
+    /// List the entries not reachable from the root, with their sizes,
+    /// instead of the decoded document
+    #[arg(long, conflicts_with_all = ["stream", "explain", "dot"])]
+    unused: bool,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum DuplicateKeyPolicy {
+    /// Fail decoding
+    Error,
+    /// Keep the first value
+    First,
+    /// Keep the last value
+    #[default]
+    Last,
+    /// Keep the last value and print a warning
+    Warn,
+}
+
+impl From<DuplicateKeyPolicy> for DuplicateKeys {
+    fn from(policy: DuplicateKeyPolicy) -> Self {
+        match policy {
+            DuplicateKeyPolicy::Error => DuplicateKeys::Error,
+            DuplicateKeyPolicy::First => DuplicateKeys::First,
+            DuplicateKeyPolicy::Last => DuplicateKeys::Last,
+            DuplicateKeyPolicy::Warn => DuplicateKeys::Warn,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum DialectKind {
+    /// Tell the dialect from the start of each input, failing when it could
+    /// be several
+    #[default]
+    Auto,
+    /// An entry array followed by `P<N>:` continuation lines
+    Native,
+    /// Payloads serialized with devalue, as SvelteKit does
+    Devalue,
+    /// Nuxt's `__NUXT_DATA__` payloads
+    Nuxt,
+    /// Payloads serialized with flatted
+    Flatted,
+    /// React Server Components streams, as Next.js fetches them
+    Flight,
+}
+
+impl From<DialectKind> for Dialect {
+    fn from(kind: DialectKind) -> Self {
+        match kind {
+            // Replaced once the input is opened
+            DialectKind::Auto | DialectKind::Native => Dialect::Native,
+            DialectKind::Devalue => Dialect::Devalue,
+            DialectKind::Nuxt => Dialect::Nuxt,
+            DialectKind::Flatted => Dialect::Flatted,
+            DialectKind::Flight => Dialect::Flight,
+        }
+    }
+}
 
-            arr[1] = Value::Number(Number::from(len as u64));
+#[derive(ValueEnum, Clone, Copy, Debug, Default)]
+enum InvalidUtf8Policy {
+    /// Fail reading the input
+    #[default]
+    Error,
+    /// Replace invalid bytes with U+FFFD and print a warning
+    Lossy,
+    /// Drop the line and print a warning
+    SkipLine,
+}
 
-            // Extend encoded_list with the parsed extra line
-            let mut encoded_extra: Vec<Value> =
-                serde_json::from_str(p_encoded_str.trim()).with_context(|| "Invalid JSON array")?;
-            decoder.encoded_list.append(&mut encoded_extra);
+impl From<InvalidUtf8Policy> for InvalidUtf8 {
+    fn from(policy: InvalidUtf8Policy) -> Self {
+        match policy {
+            InvalidUtf8Policy::Error => InvalidUtf8::Error,
+            InvalidUtf8Policy::Lossy => InvalidUtf8::Lossy,
+            InvalidUtf8Policy::SkipLine => InvalidUtf8::SkipLine,
         }
+    }
+}
 
-        decoder.decoded_data = decoder.decode_fragment(&decoder.encoded_list[0])?;
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum PlaceholderKind {
+    /// `null`
+    Null,
+    /// An `{"$error": "..."}` object describing the problem
+    Marker,
+}
 
-        Ok(decoder)
+impl From<PlaceholderKind> for Placeholder {
+    fn from(kind: PlaceholderKind) -> Self {
+        match kind {
+            PlaceholderKind::Null => Placeholder::Null,
+            PlaceholderKind::Marker => Placeholder::Marker,
+        }
     }
+}
 
-    fn decode_fragment(&self, fragment: &Value) -> Result<Value> {
-        match fragment {
-            Value::Array(arr) => self.decode_array(arr),
-            Value::Object(obj) => self.decode_object(obj),
-            v => Ok(v.clone()),
+#[derive(ValueEnum, Clone, Copy, Debug)]
+enum NonFiniteKind {
+    /// `null`
+    Null,
+    /// `"NaN"`, `"Infinity"` or `"-Infinity"`
+    String,
+    /// Fail decoding
+    Error,
+}
+
+impl From<NonFiniteKind> for NonFinite {
+    fn from(kind: NonFiniteKind) -> Self {
+        match kind {
+            NonFiniteKind::Null => NonFinite::Null,
+            NonFiniteKind::String => NonFinite::String,
+            NonFiniteKind::Error => NonFinite::Error,
         }
     }
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Decode every input into plain JSON, what running without a subcommand
+    /// does
+    Decode(DecodeArgs),
+    /// Encode plain JSON into the index-encoded format
+    Encode {
+        /// JSON Pointer of a value to emit as a `P<N>:` continuation line (repeatable)
+        #[arg(long)]
+        defer: Vec<String>,
+    },
+    /// Explore the decoded payload in an interactive terminal tree viewer
+    Browse,
+    /// Decode every input and print the result of an expression against it:
+    /// a JSON Pointer, a `$` JSONPath query, `:jmespath <EXPR>` or a jq filter
+    Query {
+        /// Expression to evaluate, as read by `repl`
+        expression: String,
+    },
+    /// Decode every input and print a draft 2020-12 JSON Schema inferred
+    /// from it: the types, required keys and array item shapes found
+    Schema,
+    /// Decode every input and print Rust structs and enums, with serde
+    /// derives, that it deserializes into
+    Codegen {
+        /// Name of the type of the root value
+        #[arg(long, default_value = "Root")]
+        name: String,
+    },
+    /// Decode once, then evaluate pointer, JSONPath, jq and JMESPath
+    /// expressions read from stdin against the decoded value
+    Repl,
+    /// Decode two inputs and print the paths added, removed or changed
+    /// between them
+    Diff {
+        /// Write the changes as an RFC 6902 JSON Patch document instead
+        #[arg(long)]
+        patch_output: bool,
+    },
+    /// Print structure metrics of every input: entry sharing, reference
+    /// depth and encoded vs decoded size
+    Stats,
+    /// Check every input for structural problems without writing any output,
+    /// reporting each one with its line number on stderr
+    Lint,
+    /// Decode every input and deep-merge them into one document
+    Merge {
+        /// How values at the same path of several inputs are combined
+        #[arg(long, value_enum, default_value_t)]
+        strategy: MergeStrategy,
+    },
+}
+
+fn main() -> ExitCode {
+    diagnostic::install_handler();
+
+    let command = match config::apply_defaults(config::with_env(Args::command())) {
+        Ok(command) => command,
+        Err(error) => {
+            diagnostic::report(&error, ErrorFormat::default());
+            return Failure::of(&error).into();
+        }
+    };
+    let mut args =
+        Args::from_arg_matches(&config::get_matches(command)).unwrap_or_else(|error| error.exit());
+    // Output written again on every change would wait for the pager
+    args.destination.no_pager |= args.watch;
+    args.format.colored = args.format.color.enabled(args.destination.is_terminal());
+
+    tracing_subscriber::registry()
+        .with(args.timings.then(timings::layer))
+        .with((!args.quiet).then_some(
+            WarningPrinter.with_filter(Targets::new().with_target("json_decoder", Level::WARN)),
+        ))
+        .init();
+
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || try_main(&args))
+            .map_or_else(
+                |error| Err(anyhow::Error::from(error).context("Failed to start main thread")),
+                |worker| worker.join().unwrap_or_else(|e| panic::resume_unwind(e)),
+            )
+    });
+
+    if args.timings {
+        timings::report();
+    }
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            diagnostic::report(&error, args.error_format);
+            Failure::of(&error).into()
+        }
+    }
+}
+
+fn try_main(args: &Args) -> Result<()> {
+    if let Some(dir) = &args.generate_man {
+        return generate_man(config::with_env(Args::command()), dir);
+    }
+
+    if args.arbitrary_precision && !cfg!(feature = "arbitrary_precision") {
+        bail!("--arbitrary-precision requires a build with the arbitrary_precision feature");
+    }
+
+    let pool = ThreadPoolBuilder::new()
+        .num_threads(args.jobs)
+        .stack_size(STACK_SIZE)
+        .build()
+        .with_context(|| "Failed to start worker threads")?;
+
+    match args.command {
+        Some(Command::Browse) => return browse(&decode_single(args)?),
+        Some(Command::Repl) => return run_repl(args),
+        _ => {}
+    }
+
+    let run_all = || match args.command {
+        Some(Command::Diff { patch_output }) => run_diff(args, patch_output),
+        Some(Command::Merge { strategy }) => run_merge(args, &pool, strategy),
+        _ => process_all(args, &pool),
+    };
+
+    match args.watch {
+        true => watch(
+            &args.input,
+            &decode_options(args),
+            args.error_format,
+            run_all,
+        ),
+        false => run_all(),
+    }
+}
 
-    fn decode_index(&self, index: Option<i64>) -> Result<usize> {
-        let r = match index {
-            Some(i) if i >= 0 => {
-                let u = i as usize;
+fn process_all(args: &Args, pool: &ThreadPool) -> Result<()> {
+    let sources = args.input.sources(&decode_options(args))?;
 
-                match u < self.encoded_list.len() {
-                    true => u,
-                    false => return Err(anyhow!("Index out of bounds")),
+    match args.destination.open_shared()? {
+        // Sequential results go straight to the output without collecting them
+        Some(mut writer) if pool.current_num_threads() == 1 || sources.len() == 1 => {
+            for (i, source) in sources.iter().enumerate() {
+                if i > 0 {
+                    write_separator(args, &mut writer)?;
                 }
+
+                process(args, &sources, source, &mut writer)?;
             }
-            Some(i) => {
-                let u = i.abs() as usize;
 
-                match u <= self.encoded_list.len() {
-                    true => self.encoded_list.len() - u,
-                    false => return Err(anyhow!("Index out of bounds")),
+            writer.commit()?;
+        }
+        // Concurrent results are buffered one batch at a time to keep them in order
+        Some(mut writer) => {
+            for (i, batch) in sources.chunks(pool.current_num_threads()).enumerate() {
+                let outputs: Vec<Result<Vec<u8>>> = pool.install(|| {
+                    batch
+                        .par_iter()
+                        .map(|source| {
+                            let mut output = Vec::new();
+                            process(args, &sources, source, &mut output).map(|()| output)
+                        })
+                        .collect()
+                });
+
+                for (j, output) in outputs.into_iter().enumerate() {
+                    if i > 0 || j > 0 {
+                        write_separator(args, &mut writer)?;
+                    }
+
+                    writer
+                        .write_all(&output?)
+                        .context(WriteFailure("Failed to write output"))?;
                 }
             }
-            None => return Err(anyhow!("Invalid number format")),
-        };
 
-        Ok(r)
+            writer.commit()?;
+        }
+        None => pool.install(|| {
+            sources
+                .par_iter()
+                .try_for_each(|source| match args.destination.in_place {
+                    // The input is read while decoding, so it is overwritten afterwards
+                    true => {
+                        let mut output = Vec::new();
+                        process(args, &sources, source, &mut output)?;
+                        args.destination.replace(source, &output)
+                    }
+                    false => {
+                        let mut writer = args.destination.open_for(source)?;
+                        process(args, &sources, source, &mut writer)?;
+                        writer.commit()
+                    }
+                })
+        })?,
     }
 
-    fn decode_array(&self, arr: &[Value]) -> Result<Value> {
-        let mut result = Vec::<Value>::new();
+    Ok(())
+}
+
+// Runs one input, naming it in errors when several are processed
+fn process(args: &Args, sources: &[Source], source: &Source, writer: &mut dyn Write) -> Result<()> {
+    let writer = &mut Counted { inner: writer };
+    let result = match sources.len() {
+        1 => run(args, source, writer),
+        _ => run(args, source, writer).with_context(|| format!("Failed to process {source}")),
+    };
 
-        for item in arr {
-            match item {
-                Value::Number(n) => {
-                    let index = self.decode_index(n.as_i64())?;
-                    result.push(self.decode_fragment(&self.encoded_list[index])?)
-                }
-                Value::String(s) if s == "P" => {
-                    let index = self.decode_index(
-                        arr.get(1)
-                            .with_context(|| "Missing index in array")?
-                            .as_i64(),
-                    )?;
-
-                    return self.decode_fragment(&self.encoded_list[index]);
-                }
-                f => result.push(self.decode_fragment(f)?),
-            };
+    result.map_err(|error| diagnose(&args.input, source, error))
+}
+
+fn write_separator(args: &Args, writer: &mut dyn Write) -> Result<()> {
+    writer
+        .write_all(args.destination.separator.as_bytes())
+        .context(WriteFailure("Failed to write output separator"))
+}
+
+// Processes one input according to the subcommand
+fn run(args: &Args, source: &Source, writer: &mut dyn Write) -> Result<()> {
+    let mut options = decode_options(args);
+    let reader = |options: &mut DecodeOptions| -> Result<Box<dyn BufRead>> {
+        let mut reader = args.input.open(source)?;
+        let head = reader.fill_buf().context("Failed to read input")?;
+        detect_dialect(args, options, head)?;
+        Ok(reader)
+    };
+    let payload = |options: &mut DecodeOptions| -> Result<Payload> {
+        let mut payload = args.input.payload(source)?;
+        let head = payload.head().context("Failed to read input")?;
+        detect_dialect(args, options, head)?;
+        Ok(payload)
+    };
+
+    let mode = match &args.command {
+        Some(Command::Encode { defer }) => {
+            return encode(args.input.open(source)?, writer, defer, args.max_depth);
+        }
+        Some(Command::Stats) => return stats(reader(&mut options)?, writer, options),
+        Some(Command::Lint) => {
+            let reader = reader(&mut options)?;
+            return lint_payload(reader, source, options, args.error_format);
+        }
+        // Expressions and schemas need the decoded tree
+        Some(Command::Query { .. } | Command::Schema | Command::Codegen { .. }) => {
+            return decode(payload(&mut options)?, writer, options, args, source);
         }
+        Some(Command::Browse | Command::Repl | Command::Diff { .. } | Command::Merge { .. }) => {
+            unreachable!("commands reading several inputs are run from main")
+        }
+        Some(Command::Decode(mode)) => DecodeArgs {
+            explain: mode.explain || args.decode.explain,
+            dot: mode.dot || args.decode.dot,
+            unused: mode.unused || args.decode.unused,
+        },
+        None => args.decode,
+    };
 
-        Ok(Value::Array(result))
+    match mode {
+        _ if args.stream && args.destination.split_keys.is_some() => {
+            bail!("--split-keys can not be used with --stream")
+        }
+        _ if args.stream && args.threads != 1 => {
+            bail!("--threads can not be used with --stream")
+        }
+        _ if args.stream && args.max_depth > STREAM_MAX_DEPTH => {
+            bail!("--stream can not be used with a --max-depth above {STREAM_MAX_DEPTH}")
+        }
+        DecodeArgs { unused: true, .. } => write_unused(reader(&mut options)?, writer, options),
+        DecodeArgs { dot: true, .. } => write_dot(reader(&mut options)?, writer, options),
+        DecodeArgs { explain: true, .. } => {
+            explain_resolutions(reader(&mut options)?, writer, options)
+        }
+        _ if args.stream || streams(args) => {
+            let payload = payload(&mut options)?;
+            decode_streaming(payload, writer, options, args, source)
+        }
+        _ => decode(payload(&mut options)?, writer, options, args, source),
     }
+}
 
-    fn decode_object(&self, obj: &Map<String, Value>) -> Result<Value> {
-        let mut result = Map::<String, Value>::new();
+// Output written as decoded needs no tree, so references are resolved while
+// it is written
+fn streams(args: &Args) -> bool {
+    args.query.is_empty()
+        && args.transform.is_empty()
+        && args.validate_schema.is_none()
+        && args.threads == 1
+        && args.max_depth <= STREAM_MAX_DEPTH
+        && !args.format.sort_keys
+        && args.destination.split_keys.is_none()
+        && args.format.streams()
+}
 
-        for (key, value) in obj {
-            // Ensure the K-index is valid
-            let mut index = self.decode_index(
-                self.key_index_re
-                    .captures(key)
-                    .with_context(|| "Invalid K-index format")?
-                    .get(1)
-                    .with_context(|| "Invalid K-index format")?
-                    .as_str()
-                    .parse::<i64>()
-                    .ok(),
-            )?;
+// `--dialect auto` reads the dialect off the start of the input, and
+// `--passthrough` whether it is plain JSON
+fn detect_dialect(
+    args: &Args,
+    options: &mut DecodeOptions,
+    head: &[u8],
+) -> Result<(), DecodeError> {
+    if args.passthrough && Dialect::is_plain(head) {
+        options.dialect = Dialect::Plain;
+    } else if let DialectKind::Auto = args.dialect {
+        options.dialect = Dialect::detect(head, options)?;
+    }
 
-            let obj_key = String::from(
-                self.encoded_list[index]
-                    .as_str()
-                    .with_context(|| "Invalid string format")?,
-            );
-            index = self.decode_index(value.as_i64())?;
-            let obj_value = self.decode_fragment(&self.encoded_list[index])?;
-            result.insert(obj_key, obj_value);
-        }
+    Ok(())
+}
+
+fn parse_max_depth(s: &str) -> Result<usize, String> {
+    match s.parse::<usize>().map_err(|e| e.to_string())? {
+        depth if depth > MAX_DEPTH_LIMIT => Err(format!(
+            "documents nested deeper than {MAX_DEPTH_LIMIT} levels can not be written"
+        )),
+        depth => Ok(depth),
+    }
+}
+
+fn decode_options(args: &Args) -> DecodeOptions {
+    DecodeOptions {
+        dialect: args.dialect.into(),
+        max_depth: args.max_depth,
+        relaxed: args.relaxed,
+        arbitrary_precision: args.arbitrary_precision,
+        invalid_utf8: args.invalid_utf8.into(),
+        duplicate_keys: args.duplicate_keys.into(),
+        key_prefix: args.key_prefix.clone(),
+        promise_prefix: args.promise_prefix.clone(),
+        lenient: args.lenient.map(Placeholder::from),
+        non_finite: args.non_finite.map(NonFinite::from),
+        strict: args.strict,
+        raw_literals: args.raw_literals,
+        threads: args.threads,
+    }
+}
+
+// Interactive commands explore exactly one decoded input
+fn decode_single(args: &Args) -> Result<Value> {
+    if args.watch {
+        bail!("--watch can not be used with interactive commands");
+    }
+
+    let sources = args.input.sources(&decode_options(args))?;
+    let [source] = sources.as_slice() else {
+        bail!("Interactive commands require exactly one input");
+    };
+
+    // The viewer reads keys from the terminal itself, so piped input still works
+    decode_source(args, source)
+}
+
+// Decodes a whole input, narrows it down to the queried parts and transforms
+// them
+fn decode_source(args: &Args, source: &Source) -> Result<Value> {
+    let mut options = decode_options(args);
+    let mut payload = args.input.payload(source)?;
+    let head = payload.head().context("Failed to read input")?;
+
+    let value = detect_dialect(args, &mut options, head)
+        .and_then(|()| payload.decode(options))
+        .map_err(|error| diagnose(&args.input, source, error.into()))?
+        .into_value();
+
+    let value = args.transform.unflattened(value)?;
+    let mut value = args.query.apply(value)?;
+    args.transform
+        .apply(&mut value, args.duplicate_keys.into())?;
+
+    Ok(value)
+}
+
+fn run_repl(args: &Args) -> Result<()> {
+    if args
+        .input
+        .sources(&decode_options(args))?
+        .iter()
+        .any(|s| matches!(s, Source::Stdin))
+    {
+        bail!("repl reads expressions from stdin, so it requires --input or --data");
+    }
+
+    repl(&decode_single(args)?, &args.format)
+}
+
+fn run_diff(args: &Args, patch_output: bool) -> Result<()> {
+    let sources = args.input.sources(&decode_options(args))?;
+    let [old, new] = sources.as_slice() else {
+        bail!("diff requires exactly two inputs");
+    };
+
+    let old_value = decode_source(args, old).with_context(|| format!("Failed to process {old}"))?;
+    let new_value = decode_source(args, new).with_context(|| format!("Failed to process {new}"))?;
+
+    let Some(mut writer) = args.destination.open_shared()? else {
+        bail!("diff writes a single output");
+    };
+
+    let changes = diff(&old_value, &new_value);
+
+    match patch_output {
+        true => args.format.write_value(&to_patch(&changes), &mut writer)?,
+        false => write_diff(&changes, &mut writer)?,
+    }
+
+    writer.commit()
+}
 
-        Ok(Value::Object(result))
+// Inputs are decoded concurrently and merged in command line order
+fn run_merge(args: &Args, pool: &ThreadPool, strategy: MergeStrategy) -> Result<()> {
+    let sources = args.input.sources(&decode_options(args))?;
+    let values: Vec<Value> = pool.install(|| {
+        sources
+            .par_iter()
+            .map(|source| {
+                decode_source(args, source).with_context(|| format!("Failed to process {source}"))
+            })
+            .collect::<Result<_>>()
+    })?;
+
+    let mut values = sources.iter().zip(values);
+    let mut merged = values.next().map(|(_, value)| value).unwrap_or_default();
+
+    for (source, value) in values {
+        merge(&mut merged, value, strategy).with_context(|| format!("Failed to merge {source}"))?;
     }
 
-    fn decoded_data(&self) -> &Value {
-        &self.decoded_data
+    if args.format.sort_keys {
+        merged.sort_all_objects();
     }
+
+    let Some(mut writer) = args.destination.open_shared()? else {
+        bail!("merge writes a single output");
+    };
+
+    args.format.write_value(&merged, &mut writer)?;
+    writer.commit()
 }
 
-fn main() -> Result<()> {
-    let args = Args::parse();
+fn decode(
+    payload: Payload,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+    args: &Args,
+    source: &Source,
+) -> Result<()> {
+    let start = Instant::now();
+    let decoder = payload.decode(options)?;
+
+    if args.verbose {
+        eprintln!(
+            "{source}: read {} lines, loaded {} entries, decoded in {:.2?}",
+            decoder.line_count(),
+            decoder.encoded_list().len(),
+            start.elapsed()
+        );
+    }
+
+    timings::add_entries(decoder.encoded_list().len());
+    let value = args.transform.unflattened(decoder.into_value())?;
+    let mut value = args.query.apply(value)?;
+
+    if let Some(Command::Query { expression }) = &args.command {
+        value = evaluate(expression, &value)?;
+    }
+
+    args.transform
+        .apply(&mut value, args.duplicate_keys.into())?;
+
+    if let Some(schema) = &args.validate_schema {
+        validate(&value, schema)?;
+    }
 
-    // Input: file or stdin
-    let decoder = match args.input {
-        Some(input_path) => {
-            let f = File::open(input_path).with_context(|| "Failed to open input file")?;
-            JSONDecoder::new(BufReader::new(f))?
+    match &args.command {
+        Some(Command::Schema) => value = infer_schema(&value),
+        Some(Command::Codegen { name }) => return write_rust(&value, name, writer),
+        _ => {}
+    }
+
+    if args.format.sort_keys {
+        value.sort_all_objects();
+    }
+
+    let start = Instant::now();
+    let written = match &args.destination.split_keys {
+        Some(dir) => {
+            let Value::Object(obj) = &value else {
+                bail!("--split-keys requires the decoded root to be an object");
+            };
+
+            args.destination.split(dir, obj, |item, writer| {
+                args.format.write_value(item, writer)
+            })
         }
-        None => JSONDecoder::new(io::stdin().lock())?,
+        None => args.format.write_value(&value, writer),
     };
+    timings::add_serialize(start.elapsed());
 
-    // Output: file or stdin
-    let formatter = PrettyFormatter::with_indent(b"    ");
-    match args.output {
-        Some(output_path) => {
-            let f = File::create(output_path).with_context(|| "Failed to create output file")?;
-            let mut ser = Serializer::with_formatter(f, formatter);
-            decoder
-                .decoded_data()
-                .serialize(&mut ser)
-                .with_context(|| "Failed to write JSON data")?
+    written
+}
+
+fn decode_streaming(
+    payload: Payload,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+    args: &Args,
+    source: &Source,
+) -> Result<()> {
+    let start = Instant::now();
+    let decoder = payload.stream(options)?;
+    let loaded = start.elapsed();
+    timings::add_entries(decoder.encoded_list().len());
+
+    // Output files of a failed run are discarded; serializers only keep the
+    // message of decoding errors
+    args.format
+        .write_streamed(&decoder, writer)
+        .map_err(|error| decoder.take_error().map_or(error, anyhow::Error::from))?;
+
+    // References are only resolved while writing
+    if args.verbose {
+        eprintln!(
+            "{source}: read {} lines, loaded {} entries in {loaded:.2?}, decoded and written in {:.2?}",
+            decoder.line_count(),
+            decoder.encoded_list().len(),
+            start.elapsed() - loaded
+        );
+    }
+
+    Ok(())
+}
+
+// One line per value; the trail stops right before the value that failed
+fn explain_resolutions(
+    reader: Box<dyn BufRead>,
+    writer: &mut dyn Write,
+    options: DecodeOptions,
+) -> Result<()> {
+    let mut written = Ok(());
+
+    let explained = explain(reader, options, |resolution| {
+        if written.is_ok() {
+            written = writeln!(writer, "{}", describe(resolution));
         }
-        None => {
-            let mut ser = Serializer::with_formatter(io::stdout().lock(), formatter);
-            decoder
-                .decoded_data()
-                .serialize(&mut ser)
-                .with_context(|| "Failed to write JSON data")?
+    });
+
+    written.context(WriteFailure("Failed to write resolution trail"))?;
+    Ok(explained?)
+}
+
+fn describe(resolution: &Resolution) -> String {
+    let path = format_path(resolution.path);
+    let (entry, line) = (resolution.entry, resolution.line);
+    let mut description = match resolution.inline {
+        true => format!("{path} <- inline in entry {entry} (line {line})"),
+        false => format!("{path} <- entry {entry} (line {line})"),
+    };
+
+    if let Some(key_entry) = resolution.key_entry {
+        description.push_str(&format!(", key entry {key_entry}"));
+    }
+
+    for promise in resolution.promises {
+        description.push_str(&format!(", via promise {promise}"));
+    }
+
+    description
+}
+
+fn lint_payload(
+    reader: Box<dyn BufRead>,
+    source: &Source,
+    options: DecodeOptions,
+    error_format: ErrorFormat,
+) -> Result<()> {
+    let problems = lint(reader, options);
+    let count = problems.len();
+
+    for problem in problems {
+        // Includes the underlying JSON error, if any
+        let error = anyhow::Error::from(problem.error);
+
+        match error_format {
+            ErrorFormat::Text => {
+                let location = match problem.entry {
+                    Some(entry) => format!("{source}:{}: entry {entry}", problem.line),
+                    None => format!("{source}:{}", problem.line),
+                };
+
+                eprintln!("{location}: {error:#}");
+            }
+            ErrorFormat::Json => {
+                let mut object = diagnostic::to_json(&error);
+                object["input"] = Value::from(source.to_string());
+                object["line"] = Value::from(problem.line);
+                object["entry"] = Value::from(problem.entry);
+
+                eprintln!("{object}");
+            }
         }
     }
 
-    Ok(())
+    match count {
+        0 => Ok(()),
+        1 => bail!("1 problem found in {source}"),
+        n => bail!("{n} problems found in {source}"),
+    }
+}
+
+fn encode(
+    mut reader: Box<dyn BufRead>,
+    writer: &mut dyn Write,
+    defer: &[String],
+    max_depth: usize,
+) -> Result<()> {
+    let mut input = Vec::new();
+    reader
+        .read_to_end(&mut input)
+        .context("Failed to read input")?;
+
+    // serde_json stops at 128 levels unless unbounded, and encoding recurses
+    // once per level, hence the depth checked up front
+    if nesting_depth(&input) > max_depth {
+        bail!("JSON input nests deeper than the maximum depth of {max_depth}");
+    }
+
+    let mut deserializer = serde_json::Deserializer::from_slice(&input);
+    deserializer.disable_recursion_limit();
+    let value = Value::deserialize(&mut deserializer)
+        .and_then(|value| deserializer.end().map(|()| value))
+        .with_context(|| "Invalid JSON input")?;
+    let defer: Vec<&str> = defer.iter().map(String::as_str).collect();
+    let encoder = JSONEncoder::with_deferred(&value, &defer)?;
+
+    encoder
+        .write_to(writer)
+        .context(WriteFailure("Failed to write encoded data"))
+}
+
+// Deepest nesting of the arrays and objects of JSON text, counting the
+// brackets outside strings
+fn nesting_depth(json: &[u8]) -> usize {
+    let (mut depth, mut deepest) = (0usize, 0);
+    let mut bytes = json.iter();
+
+    while let Some(&byte) = bytes.next() {
+        match byte {
+            b'[' | b'{' => {
+                depth += 1;
+                deepest = deepest.max(depth);
+            }
+            b']' | b'}' => depth = depth.saturating_sub(1),
+            b'"' => {
+                while let Some(&byte) = bytes.next() {
+                    match byte {
+                        b'\\' => _ = bytes.next(),
+                        b'"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    deepest
 }
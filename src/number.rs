@@ -0,0 +1,135 @@
+use serde::{
+    Serialize, Serializer,
+    ser::{Error, SerializeMap, SerializeSeq},
+};
+use serde_json::{Number, Value};
+
+/// A value serialized with its numbers written as native integers and floats.
+///
+/// With the `arbitrary_precision` feature, a serde_json number serializes as
+/// a private structure holding its literal that only serde_json understands.
+/// Values written in other formats, such as MessagePack, CBOR or TOML, are
+/// wrapped in this instead; without the feature it serializes like the value.
+pub struct NativeNumbers<'a>(pub &'a Value);
+
+impl Serialize for NativeNumbers<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self.0 {
+            Value::Number(n) => serialize_native(n, serializer),
+            Value::Array(arr) => {
+                let mut seq = serializer.serialize_seq(Some(arr.len()))?;
+
+                for item in arr {
+                    seq.serialize_element(&NativeNumbers(item))?;
+                }
+
+                seq.end()
+            }
+            Value::Object(obj) => {
+                let mut map = serializer.serialize_map(Some(obj.len()))?;
+
+                for (key, value) in obj {
+                    map.serialize_entry(key, &NativeNumbers(value))?;
+                }
+
+                map.end()
+            }
+            v => v.serialize(serializer),
+        }
+    }
+}
+
+// The narrowest native type holding `n`, integers beyond 128 bits becoming
+// floats
+pub(crate) fn serialize_native<S: Serializer>(
+    n: &Number,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    if let Some(u) = n.as_u64() {
+        serializer.serialize_u64(u)
+    } else if let Some(i) = n.as_i64() {
+        serializer.serialize_i64(i)
+    } else if let Some(u) = n.as_u128() {
+        serializer.serialize_u128(u)
+    } else if let Some(i) = n.as_i128() {
+        serializer.serialize_i128(i)
+    } else if let Some(f) = n.as_f64() {
+        serializer.serialize_f64(f)
+    } else {
+        Err(S::Error::custom(format!("number {n} is out of range")))
+    }
+}
+
+/// The `-0` literals of a JSON text, which serde_json reads as the integer
+/// `0` even with arbitrary precision.
+///
+/// They are parsed as a literal the text does not hold instead, then written
+/// back as `-0`.
+#[cfg(feature = "arbitrary_precision")]
+pub(crate) struct NegativeZeros {
+    pub marked: String,
+    sentinel: String,
+}
+
+#[cfg(feature = "arbitrary_precision")]
+impl NegativeZeros {
+    /// Finds the `-0` literals of `text`, `None` when it holds none.
+    pub fn mark(text: &str) -> Option<Self> {
+        let bytes = text.as_bytes();
+        let mut zeros = Vec::new();
+        let (mut in_string, mut escaped) = (false, false);
+
+        for (i, &b) in bytes.iter().enumerate() {
+            match b {
+                _ if escaped => escaped = false,
+                b'\\' if in_string => escaped = true,
+                b'"' => in_string = !in_string,
+                b'-' if !in_string
+                    && bytes.get(i + 1) == Some(&b'0')
+                    && !bytes
+                        .get(i + 2)
+                        .is_some_and(|b| b.is_ascii_digit() || matches!(b, b'.' | b'e' | b'E')) =>
+                {
+                    zeros.push(i);
+                }
+                _ => {}
+            }
+        }
+
+        if zeros.is_empty() {
+            return None;
+        }
+
+        let mut sentinel = String::from("-0e-0");
+        while text.contains(&sentinel) {
+            sentinel.push('0');
+        }
+
+        let mut marked = String::with_capacity(text.len() + zeros.len() * sentinel.len());
+        let mut copied = 0;
+        for i in zeros {
+            marked.push_str(&text[copied..i]);
+            marked.push_str(&sentinel);
+            copied = i + 2;
+        }
+        marked.push_str(&text[copied..]);
+
+        Some(NegativeZeros { marked, sentinel })
+    }
+
+    /// Writes the literals parsed from the marked text back as `-0`.
+    pub fn restore(&self, value: &mut Value) {
+        let mut stack = vec![value];
+
+        while let Some(value) = stack.pop() {
+            match value {
+                Value::Number(n) if n.as_str() == self.sentinel => {
+                    *n = Number::from_string_unchecked(String::from("-0"));
+                }
+                Value::Array(arr) => stack.extend(arr),
+                Value::Object(obj) => stack.extend(obj.values_mut()),
+                _ => {}
+            }
+        }
+    }
+}
@@ -0,0 +1,251 @@
+use crate::DecodeError;
+use std::fmt;
+
+/// Default for [`DecodeOptions::max_depth`].
+pub const DEFAULT_MAX_DEPTH: usize = 1024;
+
+/// Settings that control how an encoded payload is decoded.
+///
+/// Problems tolerated while decoding are reported as [`tracing`] warnings.
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// Encoding of the payload.
+    pub dialect: Dialect,
+
+    /// Maximum nesting depth of decoded arrays, objects and promises.
+    ///
+    /// Decoding aborts with [`DecodeError::DepthLimitExceeded`] past this
+    /// limit instead of exhausting the stack. Decoding itself holds no frame
+    /// per level, but serde_json writes and drops the decoded [`Value`]
+    /// recursively: documents thousands of levels deep need a thread with a
+    /// bigger stack than the default one, as the command line uses.
+    ///
+    /// [`Value`]: serde_json::Value
+    /// [`DecodeError::DepthLimitExceeded`]: crate::DecodeError::DepthLimitExceeded
+    pub max_depth: usize,
+
+    /// Parse the entry array and continuation lines as JSON5, accepting
+    /// comments, trailing commas and single-quoted strings.
+    pub relaxed: bool,
+
+    /// Keep numbers as written in the input, such as `1.0e2` or
+    /// `0.1000000000000000055`, instead of writing them as the 64-bit integer
+    /// or float they are closest to.
+    ///
+    /// Takes effect only when built with the `arbitrary_precision` feature,
+    /// which otherwise keeps just the integers beyond 64 bits as written.
+    /// Numbers in [`relaxed`](Self::relaxed) input are never kept as written.
+    pub arbitrary_precision: bool,
+
+    /// What to do with input lines that are not valid UTF-8.
+    pub invalid_utf8: InvalidUtf8,
+
+    /// What to do when two keys of an object resolve to the same string.
+    pub duplicate_keys: DuplicateKeys,
+
+    /// Prefix of the object keys that point at a string entry, `_` as in
+    /// `_<index>`; forks of the format use others, such as `$`.
+    pub key_prefix: String,
+
+    /// Prefix of continuation lines, `P` as in `P<index>:`, matched ignoring
+    /// case outside [`strict`](Self::strict) mode; forks of the format use
+    /// others, such as `D`.
+    pub promise_prefix: String,
+
+    /// Substitute broken references instead of failing, reporting each one
+    /// with its path as a warning.
+    ///
+    /// Out-of-bounds or malformed indexes, promises without an index or never
+    /// resolved and reference cycles are replaced with the placeholder; keys
+    /// that do not resolve are kept as written. Exceeding `max_depth` is still
+    /// an error. Only [`JSONDecoder`] and [`StreamingDecoder`] are lenient,
+    /// typed deserialization still fails.
+    ///
+    /// [`JSONDecoder`]: crate::JSONDecoder
+    /// [`StreamingDecoder`]: crate::StreamingDecoder
+    pub lenient: Option<Placeholder>,
+
+    /// Recognize the negative indexes that [turbo-stream], the JavaScript
+    /// encoder of the format used by Remix and React Router, reserves for
+    /// non-finite numbers, writing them as configured: `-2` for `NaN`, `-3`
+    /// for `-Infinity` and `-6` for `Infinity` (its `NAN`,
+    /// `NEGATIVE_INFINITY` and `POSITIVE_INFINITY`). devalue reserves others,
+    /// recognized by [`Dialect::Devalue`] whatever this says.
+    ///
+    /// When `None`, these are offsets from the end of the entry list like any
+    /// other negative index.
+    ///
+    /// [turbo-stream]: https://github.com/jacob-ebey/turbo-stream
+    pub non_finite: Option<NonFinite>,
+
+    /// Reject input that decodes but is not in canonical form: whitespace
+    /// outside strings, a byte order mark or blank lines before the entry
+    /// array, a lowercase `p` prefix, continuation lines out of P-index order
+    /// or repeating a P-index, data after the terminating empty line, negative
+    /// indexes, strings other than a leading `"P"` in arrays and promises that
+    /// are not `["P", <index>]`. Outside strict mode, the last of the lines
+    /// repeating a P-index wins, with a warning.
+    pub strict: bool,
+
+    /// Let [`StreamingDecoder`] copy the entries holding no reference, such as
+    /// strings or `["a", true, null]`, from the input to JSON output as they
+    /// are written instead of writing their parsed value.
+    ///
+    /// Faster on payloads made mostly of strings, at the cost of a copy of
+    /// those entries in memory, but they keep the spacing, escapes and number
+    /// spelling of the input, in pretty output too. Has no effect on [`relaxed`](Self::relaxed) or
+    /// [`strict`](Self::strict) decoding.
+    ///
+    /// [`StreamingDecoder`]: crate::StreamingDecoder
+    pub raw_literals: bool,
+
+    /// Threads [`JSONDecoder`] decodes the elements of a root array on; `1`
+    /// decodes on the calling thread and `0` uses one per CPU.
+    ///
+    /// Entries shared by elements on different threads are decoded once per
+    /// thread, and the warnings of different elements may interleave.
+    ///
+    /// [`JSONDecoder`]: crate::JSONDecoder
+    pub threads: usize,
+}
+
+/// Encoding of a payload: this crate's format, or that of another framework
+/// deduplicating values into a table of entries.
+///
+/// Other dialects are decoded into a tree whichever decoder reads them; once
+/// decoded, their entry list is the table of the payload. Linting, explaining
+/// and inspecting references only apply to [`Dialect::Native`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Dialect {
+    /// An entry array followed by `P<index>:` continuation lines.
+    #[default]
+    Native,
+    /// The format of the devalue library SvelteKit serializes data with: one
+    /// array of entries whose arrays and objects hold indexes of other
+    /// entries.
+    Devalue,
+    /// Nuxt's `__NUXT_DATA__` and `_payload.json` payloads: devalue with the
+    /// `Ref`, `Reactive`, `ShallowRef`, `ShallowReactive`, `EmptyRef`,
+    /// `NuxtError` and `Island` types of Nuxt's payload plugin.
+    Nuxt,
+    /// The format of the flatted library: one array of entries whose arrays
+    /// and objects hold indexes of other entries written as strings.
+    Flatted,
+    /// The Flight streams of React Server Components, as fetched by Next.js:
+    /// `<id>:<chunk>` rows whose `$`-prefixed strings reference other chunks.
+    Flight,
+    /// JSON that is already decoded, read and written back as is.
+    Plain,
+}
+
+impl Dialect {
+    /// Name of the dialect in messages.
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Native => "native",
+            Dialect::Devalue => "devalue",
+            Dialect::Nuxt => "Nuxt",
+            Dialect::Flatted => "flatted",
+            Dialect::Flight => "Flight",
+            Dialect::Plain => "plain JSON",
+        }
+    }
+}
+
+/// Value substituted for a broken reference in lenient mode.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Placeholder {
+    /// `null`.
+    #[default]
+    Null,
+    /// An `{"$error": "<message>"}` object describing the problem.
+    Marker,
+}
+
+/// How a reference standing for a non-finite number is decoded.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinite {
+    /// `null`, as `JSON.stringify` writes them.
+    #[default]
+    Null,
+    /// The JavaScript spelling of the number: `"NaN"`, `"Infinity"` or
+    /// `"-Infinity"`.
+    String,
+    /// Fail with [`DecodeError::NonFiniteNumber`], in lenient mode too.
+    ///
+    /// [`DecodeError::NonFiniteNumber`]: crate::DecodeError::NonFiniteNumber
+    Error,
+}
+
+/// Handling of input lines that are not valid UTF-8.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum InvalidUtf8 {
+    /// Fail with [`DecodeError::Io`].
+    ///
+    /// [`DecodeError::Io`]: crate::DecodeError::Io
+    #[default]
+    Error,
+    /// Replace invalid bytes with U+FFFD and report the line as a warning.
+    Lossy,
+    /// Drop the line and report it as a warning; the lines after it keep
+    /// their numbers.
+    SkipLine,
+}
+
+/// Handling of object keys that resolve to the same string.
+///
+/// The member keeps the position of the first occurrence of the key whichever
+/// value is kept.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateKeys {
+    /// Fail with [`DecodeError::DuplicateKey`].
+    ///
+    /// [`DecodeError::DuplicateKey`]: crate::DecodeError::DuplicateKey
+    Error,
+    /// Keep the value of the first occurrence.
+    First,
+    /// Keep the value of the last occurrence.
+    #[default]
+    Last,
+    /// Keep the value of the last occurrence and report the key as a warning.
+    Warn,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        DecodeOptions {
+            dialect: Dialect::default(),
+            max_depth: DEFAULT_MAX_DEPTH,
+            relaxed: false,
+            arbitrary_precision: false,
+            invalid_utf8: InvalidUtf8::default(),
+            duplicate_keys: DuplicateKeys::default(),
+            key_prefix: String::from("_"),
+            promise_prefix: String::from("P"),
+            lenient: None,
+            non_finite: None,
+            strict: false,
+            raw_literals: false,
+            threads: 1,
+        }
+    }
+}
+
+impl DecodeOptions {
+    // Reports a tolerated problem as a `tracing` event, for the subscriber
+    // of the application to print
+    pub(crate) fn warn(&self, message: fmt::Arguments) {
+        tracing::warn!("{message}");
+    }
+
+    // Fails `operation` on payloads of a dialect other than the native one
+    pub(crate) fn native_only(&self, operation: &'static str) -> Result<(), DecodeError> {
+        match self.dialect {
+            Dialect::Native => Ok(()),
+            dialect => Err(DecodeError::UnsupportedDialect {
+                dialect: dialect.name(),
+                operation,
+            }),
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use serde_json::Value;
+use std::{borrow::Cow, fmt::Write};
+
+/// One step from a value to one of its children in the decoded document.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum PathSegment {
+    /// Member of an object.
+    Key(String),
+    /// Element of an array.
+    Index(usize),
+}
+
+/// Formats `segments` as a JSONPath-like location such as `$.data.items[3]`.
+pub fn format_path(segments: &[PathSegment]) -> String {
+    let mut path = String::from("$");
+
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) if is_identifier(key) => {
+                let _ = write!(path, ".{key}");
+            }
+            PathSegment::Key(key) => {
+                let _ = write!(path, "[{}]", Value::from(key.as_str()));
+            }
+            PathSegment::Index(index) => {
+                let _ = write!(path, "[{index}]");
+            }
+        }
+    }
+
+    path
+}
+
+/// Formats `segments` as an RFC 6901 JSON Pointer such as `/data/items/3`.
+pub fn format_pointer(segments: &[PathSegment]) -> String {
+    let mut pointer = String::new();
+
+    for segment in segments {
+        match segment {
+            PathSegment::Key(key) => {
+                let _ = write!(pointer, "/{}", key.replace('~', "~0").replace('/', "~1"));
+            }
+            PathSegment::Index(index) => {
+                let _ = write!(pointer, "/{index}");
+            }
+        }
+    }
+
+    pointer
+}
+
+fn is_identifier(key: &str) -> bool {
+    let mut chars = key.chars();
+
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == '$')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+// Segments written at each end of an abbreviated path
+const PATH_ENDS: usize = 8;
+
+// Shortens a path written by `format_path` to its first and last segments,
+// such as `$[0][0]…[0][0]`, so that errors deep in a document stay readable
+pub(crate) fn abbreviate_path(path: &str) -> Cow<'_, str> {
+    let bytes = path.as_bytes();
+    let mut starts = Vec::new();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &b) in bytes.iter().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' if in_string => escaped = true,
+            b'"' => in_string = !in_string,
+            b'.' | b'[' if !in_string => starts.push(i),
+            _ => {}
+        }
+    }
+
+    if starts.len() <= 2 * PATH_ENDS + 1 {
+        return Cow::Borrowed(path);
+    }
+
+    let head = &path[..starts[PATH_ENDS]];
+    let tail = &path[starts[starts.len() - PATH_ENDS]..];
+    Cow::Owned(format!("{head}…{tail}"))
+}
@@ -0,0 +1,712 @@
+use crate::{
+    DecodeError, JSONDecoder,
+    path::{PathSegment, format_path},
+    promise_slot,
+};
+use rayon::{ThreadPoolBuilder, prelude::*};
+use serde_json::{Map, Value};
+use std::{
+    borrow::Cow,
+    collections::{HashMap, HashSet},
+    convert, mem, slice, vec,
+};
+use tracing::instrument;
+
+/// How the fragments of a format split into the values and references they
+/// are made of, which is all [`Resolver`] needs to know of it.
+pub(crate) trait Expand {
+    // Splits a fragment into its parts; `location` gives the path of the
+    // fragment, for warnings
+    fn expand<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError>;
+
+    // Counts the references to every entry, so that entries referenced more
+    // than once are decoded once; malformed fragments are left to decoding
+    fn use_counts(&self, decoder: &JSONDecoder) -> Vec<usize> {
+        let mut counts = vec![0; decoder.encoded_list.len()];
+
+        for entry in &decoder.encoded_list {
+            let mut pending = vec![entry];
+
+            while let Some(fragment) = pending.pop() {
+                let Ok(node) = self.expand(decoder, fragment, &|| None) else {
+                    continue;
+                };
+
+                for child in node.children() {
+                    count(child, &mut counts, &mut pending);
+                }
+            }
+        }
+
+        counts
+    }
+
+    // The error for resolving the entries of `cycle`, first and last being
+    // the same
+    fn cycle_error(&self, _decoder: &JSONDecoder, cycle: Vec<usize>) -> DecodeError {
+        DecodeError::ReferenceCycle { cycle, path: None }
+    }
+}
+
+// A fragment split into its parts
+pub(crate) enum Node<'a> {
+    // A value holding no references
+    Leaf(Value),
+    Array(Vec<Child<'a>>),
+    Object(Vec<(Cow<'a, str>, Child<'a>)>),
+    // A JavaScript `Map`, keys and values alternating
+    Map(Vec<Child<'a>>),
+    // A value computed from that of one child
+    Apply(Child<'a>, fn(Value) -> Value),
+}
+
+// A part of a fragment
+pub(crate) enum Child<'a> {
+    // A reference to an entry
+    Entry(usize),
+    // A fragment held inline, expanded in place
+    Inline(&'a Value),
+    // A value computed from that of another child
+    Apply(Box<Child<'a>>, fn(Value) -> Value),
+    // A value known without decoding anything
+    Value(Value),
+    // JavaScript's `undefined`: `null` in arrays, left out of objects
+    Undefined,
+    // A reference that can not be followed, reported once decoding reaches it
+    Broken(DecodeError),
+}
+
+impl<'a> Node<'a> {
+    fn children(&self) -> impl Iterator<Item = &Child<'a>> {
+        let (items, members): (&[Child<'a>], &[(Cow<'a, str>, Child<'a>)]) = match self {
+            Node::Leaf(_) => (&[], &[]),
+            Node::Array(items) | Node::Map(items) => (items, &[]),
+            Node::Apply(child, _) => (slice::from_ref(child), &[]),
+            Node::Object(members) => (&[], members),
+        };
+
+        items.iter().chain(members.iter().map(|(_, child)| child))
+    }
+}
+
+fn count<'a>(child: &Child<'a>, counts: &mut [usize], pending: &mut Vec<&'a Value>) {
+    match child {
+        Child::Entry(index) if *index < counts.len() => counts[*index] += 1,
+        Child::Inline(fragment) => pending.push(fragment),
+        Child::Apply(child, _) => count(child, counts, pending),
+        _ => {}
+    }
+}
+
+/// The native format: numbers in arrays and object values are the indexes
+/// of entries, `_<index>` keys those of string entries, and `["P", index]`
+/// arrays promises of the entry at `index`.
+pub(crate) struct Native;
+
+impl Expand for Native {
+    fn expand<'a>(
+        &self,
+        decoder: &'a JSONDecoder,
+        fragment: &'a Value,
+        location: &dyn Fn() -> Option<String>,
+    ) -> Result<Node<'a>, DecodeError> {
+        match fragment {
+            Value::Array(arr) => match decoder.promise_index(arr)? {
+                Some(target) => Ok(Node::Apply(Child::Entry(target), convert::identity)),
+                // Numbers are references, anything else is an inline fragment
+                None => Ok(Node::Array(
+                    arr.iter()
+                        .map(|item| match item {
+                            Value::Number(_) => reference(decoder, item),
+                            f => Child::Inline(f),
+                        })
+                        .collect(),
+                )),
+            },
+            Value::Object(obj) => {
+                let members = decoder
+                    .object_members(obj, location)?
+                    .into_iter()
+                    .map(|(key, value)| (Cow::Borrowed(key), reference(decoder, value)))
+                    .collect();
+
+                Ok(Node::Object(members))
+            }
+            v => Ok(Node::Leaf(v.clone())),
+        }
+    }
+
+    // Counts the references from arrays, object values and promises without
+    // decoding keys; broken references are left out and only cost
+    // memoization
+    fn use_counts(&self, decoder: &JSONDecoder) -> Vec<usize> {
+        let mut counts = vec![0; decoder.encoded_list.len()];
+        let mut count = |index: &Value| {
+            if let (None, Ok(index)) = (decoder.non_finite(index), decoder.decode_index(index)) {
+                counts[index] += 1;
+            }
+        };
+        let mut stack: Vec<&Value> = decoder.encoded_list.iter().collect();
+
+        while let Some(fragment) = stack.pop() {
+            match fragment {
+                Value::Array(arr) => match promise_slot(arr) {
+                    Some(slot) => slot.iter().for_each(|&slot| count(&arr[slot])),
+                    None => {
+                        for item in arr {
+                            match item {
+                                Value::Number(_) => count(item),
+                                f => stack.push(f),
+                            }
+                        }
+                    }
+                },
+                Value::Object(obj) => obj.values().for_each(&mut count),
+                _ => {}
+            }
+        }
+
+        counts
+    }
+
+    // A promise pointing at itself was never resolved
+    fn cycle_error(&self, decoder: &JSONDecoder, cycle: Vec<usize>) -> DecodeError {
+        decoder.cycle_error(cycle)
+    }
+}
+
+// The entry `index` points at, or the non-finite number it stands for
+fn reference<'a>(decoder: &JSONDecoder, index: &Value) -> Child<'a> {
+    let index = match decoder.non_finite(index) {
+        Some(number) => return number.map_or_else(Child::Broken, Child::Inline),
+        None => decoder.decode_index(index),
+    };
+
+    index.map_or_else(Child::Broken, Child::Entry)
+}
+
+/// Decodes fragments with an explicit work stack instead of recursion, so the
+/// nesting depth of a document is bounded by `max_depth` rather than by the
+/// size of the thread stack. `syntax` tells how the fragments of the format
+/// being decoded are split into nodes.
+pub(crate) struct Resolver<'a> {
+    decoder: &'a JSONDecoder,
+    syntax: &'a dyn Expand,
+    stack: Vec<Frame<'a>>,
+    // Entries with an open frame on the stack
+    on_stack: HashSet<usize>,
+    // References to every entry that are still to be decoded
+    pending_uses: Vec<usize>,
+    // Decoded entries still referenced later, handed out by clone and moved
+    // out by their last reference
+    memo: HashMap<usize, Value>,
+    // Entries holding no reference taken out of the entry list, handed out
+    // the same way
+    leaves: Vec<Option<Value>>,
+}
+
+// A node whose children are being decoded
+struct Frame<'a> {
+    entry: Option<usize>,
+    building: Building<'a>,
+}
+
+enum Building<'a> {
+    Sequence {
+        items: vec::IntoIter<Child<'a>>,
+        // Position of the first item, past 0 for the part of the root array
+        // one of several resolvers decodes
+        first: usize,
+        result: Vec<Value>,
+        build: Build,
+    },
+    Object {
+        members: vec::IntoIter<(Cow<'a, str>, Child<'a>)>,
+        current: Option<String>,
+        result: Map<String, Value>,
+    },
+}
+
+// What the values of a sequence make up
+enum Build {
+    Array,
+    Map,
+    Apply(fn(Value) -> Value),
+}
+
+impl<'a> Resolver<'a> {
+    pub(crate) fn new(decoder: &'a JSONDecoder, syntax: &'a dyn Expand) -> Self {
+        Resolver {
+            decoder,
+            syntax,
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            pending_uses: syntax.use_counts(decoder),
+            memo: HashMap::new(),
+            leaves: Vec::new(),
+        }
+    }
+
+    /// Decodes `root` and everything it references.
+    pub(crate) fn decode(&mut self, root: Child<'a>) -> Result<Value, DecodeError> {
+        let started = self.start(root);
+        self.run(0, started)
+    }
+
+    // Opens a resolver handing out `leaves`, the entries holding no reference
+    // taken out of the entry list
+    fn with_leaves(
+        decoder: &'a JSONDecoder,
+        syntax: &'a dyn Expand,
+        pending_uses: Vec<usize>,
+        leaves: Vec<Option<Value>>,
+    ) -> Self {
+        Resolver {
+            decoder,
+            syntax,
+            stack: Vec::new(),
+            on_stack: HashSet::new(),
+            pending_uses,
+            memo: HashMap::new(),
+            leaves,
+        }
+    }
+
+    // Opens a resolver below the frame of the root array, to decode some of
+    // its elements
+    fn below_root(
+        decoder: &'a JSONDecoder,
+        syntax: &'a dyn Expand,
+        pending_uses: Vec<usize>,
+    ) -> Self {
+        Resolver {
+            decoder,
+            syntax,
+            stack: vec![Frame::new(Some(0), Node::Array(Vec::new()))],
+            on_stack: HashSet::from([0]),
+            pending_uses,
+            memo: HashMap::new(),
+            leaves: Vec::new(),
+        }
+    }
+
+    // Decodes the element at `position` of the root array
+    fn decode_element(
+        &mut self,
+        position: usize,
+        element: Child<'a>,
+    ) -> Result<Value, DecodeError> {
+        if let Some(Frame {
+            building: Building::Sequence { first, .. },
+            ..
+        }) = self.stack.last_mut()
+        {
+            *first = position;
+        }
+
+        let started = self.start(element);
+        self.run(1, started)
+    }
+
+    // Works through the frames opened above the first `base` ones, returning
+    // the value of the child they decode
+    fn run(
+        &mut self,
+        base: usize,
+        started: Result<Option<Value>, DecodeError>,
+    ) -> Result<Value, DecodeError> {
+        let mut completed = self.or_placeholder(started)?;
+
+        loop {
+            if let Some(value) = completed.take() {
+                match self.stack.len() > base {
+                    true => self.stack.last_mut().expect("open frame").push(value),
+                    false => return Ok(value),
+                }
+                continue;
+            }
+
+            let frame = self.stack.last_mut().expect("non-empty work stack");
+
+            let started = match frame.next() {
+                Some(child) => self.start(child),
+                None => {
+                    let frame = self.stack.pop().expect("non-empty work stack");
+                    let entry = frame.entry;
+                    let value = frame.finish();
+                    self.finish(entry, &value);
+                    Ok(Some(value))
+                }
+            };
+            completed = self.or_placeholder(started)?;
+        }
+    }
+
+    // In lenient mode a broken reference completes the child being decoded
+    // with a placeholder
+    fn or_placeholder(
+        &self,
+        started: Result<Option<Value>, DecodeError>,
+    ) -> Result<Option<Value>, DecodeError> {
+        match started {
+            Err(error) => self.decoder.placeholder(error, || None).map(Some),
+            started => started,
+        }
+    }
+
+    // Adds the current path and the entry holding the problem to an error;
+    // `entry` defaults to the innermost entry being decoded
+    fn locate(&self, error: DecodeError, entry: Option<usize>) -> DecodeError {
+        let entry = entry
+            .or_else(|| self.stack.iter().rev().find_map(|f| f.entry))
+            .unwrap_or(0);
+
+        error.located(
+            format_path(&self.path()),
+            entry,
+            self.decoder.entry_line(entry),
+        )
+    }
+
+    // Returns the value of a child, or opens a frame to decode it
+    fn start(&mut self, child: Child<'a>) -> Result<Option<Value>, DecodeError> {
+        match child {
+            Child::Entry(index) => self.start_entry(index),
+            Child::Inline(fragment) => self.open(fragment, None),
+            Child::Apply(child, apply) => self.enter(Node::Apply(*child, apply), None),
+            Child::Value(value) => Ok(Some(value)),
+            Child::Undefined => Ok(Some(Value::Null)),
+            Child::Broken(error) => Err(self.locate(error, None)),
+        }
+    }
+
+    fn start_entry(&mut self, index: usize) -> Result<Option<Value>, DecodeError> {
+        let Some(fragment) = self.decoder.encoded_list.get(index) else {
+            let error = DecodeError::IndexOutOfBounds {
+                index: index as i128,
+                len: self.decoder.encoded_list.len(),
+            };
+            return Err(self.locate(error, None));
+        };
+
+        let uses = &mut self.pending_uses[index];
+        *uses = uses.saturating_sub(1);
+
+        let memoized = match *uses {
+            0 => self.memo.remove(&index),
+            _ => self.memo.get(&index).cloned(),
+        };
+
+        if let Some(value) = memoized {
+            return Ok(Some(value));
+        }
+
+        if let Some(leaf) = self.leaves.get_mut(index).filter(|leaf| leaf.is_some()) {
+            return Ok(match self.pending_uses[index] {
+                0 => leaf.take(),
+                _ => leaf.clone(),
+            });
+        }
+
+        if self.on_stack.contains(&index) {
+            return Err(self.locate(self.cycle_error(index), None));
+        }
+
+        self.open(fragment, Some(index))
+    }
+
+    fn open(
+        &mut self,
+        fragment: &'a Value,
+        entry: Option<usize>,
+    ) -> Result<Option<Value>, DecodeError> {
+        let location = || Some(format_path(&self.path()));
+        let node = self
+            .syntax
+            .expand(self.decoder, fragment, &location)
+            .map_err(|error| self.locate(error, entry))?;
+
+        self.enter(node, entry)
+    }
+
+    fn enter(
+        &mut self,
+        node: Node<'a>,
+        entry: Option<usize>,
+    ) -> Result<Option<Value>, DecodeError> {
+        if let Node::Leaf(value) = node {
+            return Ok(Some(value));
+        }
+
+        let max_depth = self.decoder.options.max_depth;
+
+        if self.stack.len() >= max_depth {
+            let error = DecodeError::DepthLimitExceeded {
+                max_depth,
+                path: None,
+            };
+            return Err(self.locate(error, entry));
+        }
+
+        if let Some(index) = entry {
+            self.on_stack.insert(index);
+        }
+
+        self.stack.push(Frame::new(entry, node));
+
+        Ok(None)
+    }
+
+    fn finish(&mut self, entry: Option<usize>, value: &Value) {
+        let Some(index) = entry else {
+            return;
+        };
+
+        self.on_stack.remove(&index);
+
+        if self.pending_uses[index] > 0 {
+            self.memo.insert(index, value.clone());
+        }
+    }
+
+    fn path(&self) -> Vec<PathSegment> {
+        self.stack.iter().filter_map(Frame::segment).collect()
+    }
+
+    fn cycle_error(&self, index: usize) -> DecodeError {
+        let mut cycle: Vec<usize> = self.stack.iter().filter_map(|f| f.entry).collect();
+        let start = cycle.iter().rposition(|&i| i == index).unwrap_or(0);
+        cycle.drain(..start);
+        cycle.push(index);
+
+        self.syntax.cycle_error(self.decoder, cycle)
+    }
+}
+
+/// Decodes the root entry, spreading the elements of a root array over
+/// [`DecodeOptions::threads`] threads when more than one is configured.
+///
+/// [`DecodeOptions::threads`]: crate::DecodeOptions::threads
+#[instrument(name = "decode", skip_all)]
+pub(crate) fn decode_root(decoder: &mut JSONDecoder) -> Result<Value, DecodeError> {
+    let threads = decoder.options.threads;
+
+    let elements = match decoder.root()? {
+        Value::Array(arr)
+            if threads != 1 && arr.len() > 1 && matches!(decoder.promise_index(arr), Ok(None)) =>
+        {
+            arr
+        }
+        _ => return decode_sequentially(decoder),
+    };
+    let Ok(pool) = ThreadPoolBuilder::new().num_threads(threads).build() else {
+        return decode_sequentially(decoder);
+    };
+
+    let mut pending_uses = Native.use_counts(decoder);
+    // Taken by the root frame every resolver starts from
+    pending_uses[0] = pending_uses[0].saturating_sub(1);
+
+    // A few chunks per thread balance the work, and each chunk gets one
+    // resolver to share entries referenced by several of its elements
+    let chunk_len = elements.len().div_ceil(pool.current_num_threads() * 4);
+    let chunks: Vec<Vec<Result<Value, DecodeError>>> = pool.install(|| {
+        elements
+            .par_chunks(chunk_len)
+            .enumerate()
+            .map(|(chunk, items)| {
+                let mut resolver = Resolver::below_root(decoder, &Native, pending_uses.clone());
+                let start = chunk * chunk_len;
+
+                items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let element = match item {
+                            Value::Number(_) => reference(decoder, item),
+                            f => Child::Inline(f),
+                        };
+                        resolver.decode_element(start + i, element)
+                    })
+                    .collect()
+            })
+            .collect()
+    });
+
+    // The first error in document order is the one decoding in order reports
+    let values = chunks.into_iter().flatten().collect::<Result<_, _>>()?;
+
+    Ok(Value::Array(values))
+}
+
+// Decodes the root entry on the calling thread, moving the entries holding no
+// reference into the value
+fn decode_sequentially(decoder: &mut JSONDecoder) -> Result<Value, DecodeError> {
+    let pending_uses = Native.use_counts(decoder);
+    let leaves = take_leaves(decoder, &pending_uses);
+
+    Resolver::with_leaves(decoder, &Native, pending_uses, leaves).decode(Child::Entry(0))
+}
+
+// Takes the referenced entries holding no reference out of the entry list,
+// leaving `null`, so that the last reference to each moves it instead of
+// copying it; strings named by `_<index>` keys stay, keys borrowing them
+fn take_leaves(decoder: &mut JSONDecoder, pending_uses: &[usize]) -> Vec<Option<Value>> {
+    let len = decoder.encoded_list.len();
+    let prefix = decoder.options.key_prefix.as_str();
+    let mut keys = vec![false; len];
+    let mut stack: Vec<&Value> = decoder.encoded_list.iter().collect();
+
+    // Any key that may name an entry counts, which is cheaper than matching
+    // keys the way decoding does
+    while let Some(fragment) = stack.pop() {
+        match fragment {
+            Value::Array(arr) => stack.extend(arr.iter().filter(|item| !item.is_number())),
+            Value::Object(obj) => {
+                for key in obj.keys() {
+                    if let Some(index) = key
+                        .strip_prefix(prefix)
+                        .and_then(|i| i.parse::<usize>().ok())
+                        && index < len
+                    {
+                        keys[index] = true;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    decoder
+        .encoded_list
+        .iter_mut()
+        .zip(pending_uses)
+        .zip(keys)
+        .map(|((entry, &uses), key)| match entry {
+            Value::Array(_) | Value::Object(_) => None,
+            _ if uses == 0 || key => None,
+            entry => Some(mem::take(entry)),
+        })
+        .collect()
+}
+
+impl<'a> Frame<'a> {
+    fn new(entry: Option<usize>, node: Node<'a>) -> Self {
+        let sequence = |items: Vec<Child<'a>>, build| Building::Sequence {
+            result: Vec::with_capacity(items.len()),
+            items: items.into_iter(),
+            first: 0,
+            build,
+        };
+
+        let building = match node {
+            Node::Leaf(_) => unreachable!("leaves are not built"),
+            Node::Array(items) => sequence(items, Build::Array),
+            Node::Map(items) => sequence(items, Build::Map),
+            Node::Apply(child, apply) => sequence(vec![child], Build::Apply(apply)),
+            Node::Object(members) => Building::Object {
+                current: None,
+                result: Map::with_capacity(members.len()),
+                members: members.into_iter(),
+            },
+        };
+
+        Frame { entry, building }
+    }
+
+    // The next child to decode, `None` once all of them are
+    fn next(&mut self) -> Option<Child<'a>> {
+        match &mut self.building {
+            Building::Sequence { items, result, .. } => loop {
+                match items.next()? {
+                    Child::Undefined => result.push(Value::Null),
+                    child => return Some(child),
+                }
+            },
+            Building::Object {
+                members, current, ..
+            } => loop {
+                match members.next()? {
+                    (_, Child::Undefined) => {}
+                    (key, child) => {
+                        *current = Some(key.into_owned());
+                        return Some(child);
+                    }
+                }
+            },
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        match &mut self.building {
+            Building::Sequence { result, .. } => result.push(value),
+            Building::Object {
+                current, result, ..
+            } => {
+                if let Some(key) = current.take() {
+                    result.insert(key, value);
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Value {
+        match self.building {
+            Building::Sequence { result, build, .. } => match build {
+                Build::Array => Value::Array(result),
+                Build::Map => map_value(result),
+                Build::Apply(apply) => apply(result.into_iter().next().unwrap_or_default()),
+            },
+            Building::Object { result, .. } => Value::Object(result),
+        }
+    }
+
+    // Location of the child being decoded
+    fn segment(&self) -> Option<PathSegment> {
+        match &self.building {
+            Building::Sequence {
+                first,
+                result,
+                build: Build::Array,
+                ..
+            } => Some(PathSegment::Index(first + result.len())),
+            Building::Sequence {
+                first,
+                result,
+                build: Build::Map,
+                ..
+            } => Some(PathSegment::Index((first + result.len()) / 2)),
+            Building::Sequence { .. } => None,
+            Building::Object { current, .. } => current.clone().map(PathSegment::Key),
+        }
+    }
+}
+
+// A `Map` is written as an object when every key is a string, and as an array
+// of `[key, value]` pairs otherwise
+pub(crate) fn map_value(items: Vec<Value>) -> Value {
+    if items.iter().step_by(2).all(Value::is_string) {
+        let mut obj = Map::with_capacity(items.len() / 2);
+        let mut items = items.into_iter();
+
+        while let (Some(Value::String(key)), Some(value)) = (items.next(), items.next()) {
+            obj.insert(key, value);
+        }
+
+        return Value::Object(obj);
+    }
+
+    let mut pairs = Vec::with_capacity(items.len() / 2);
+    let mut items = items.into_iter();
+
+    while let (Some(key), Some(value)) = (items.next(), items.next()) {
+        pairs.push(Value::Array(vec![key, value]));
+    }
+
+    Value::Array(pairs)
+}
@@ -0,0 +1,376 @@
+use crate::{
+    DecodeError, DecodeOptions, Dialect, JSONDecoder, NativeNumbers, dialect,
+    lines::{Lines, ReadLines, SliceLines},
+    number::serialize_native,
+    path::{PathSegment, format_path},
+};
+use serde::{
+    Serialize, Serializer,
+    ser::{self, SerializeMap, SerializeSeq},
+};
+use serde_json::{Value, value::RawValue};
+use std::{
+    io::BufRead,
+    iter,
+    sync::{Mutex, PoisonError},
+};
+use tracing::instrument;
+
+/// Encoded payload that is decoded on demand while being serialized.
+///
+/// Unlike [`JSONDecoder`], only the entry table is kept in memory: references
+/// are resolved as the output is written, so the decoded tree is never
+/// materialized. Reference cycles are detected and errors located as by
+/// [`JSONDecoder`], so both decode a payload to the same output.
+///
+/// ```no_run
+/// use json_decoder::StreamingDecoder;
+/// use std::{fs::File, io::{self, BufReader}};
+///
+/// let f = File::open("sample_in.txt")?;
+/// let decoder = StreamingDecoder::from_reader(BufReader::new(f))?;
+/// serde_json::to_writer(io::stdout().lock(), &decoder)?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub struct StreamingDecoder {
+    pub(crate) decoder: JSONDecoder,
+    // The decoding error that ended the last serialization
+    error: Mutex<Option<DecodeError>>,
+}
+
+impl StreamingDecoder {
+    /// Reads the entry table of an encoded payload from `reader`.
+    ///
+    /// Structural errors inside entries are only reported once the payload is
+    /// serialized.
+    pub fn from_reader<R: BufRead>(reader: R) -> Result<Self, DecodeError> {
+        Self::from_reader_with_options(reader, DecodeOptions::default())
+    }
+
+    /// Reads the entry table of an encoded payload from `reader`, decoding it
+    /// with `options` once serialized.
+    pub fn from_reader_with_options<R: BufRead>(
+        reader: R,
+        options: DecodeOptions,
+    ) -> Result<Self, DecodeError> {
+        Self::load(ReadLines::new(reader), options)
+    }
+
+    /// Reads the entry table of an encoded payload held in memory, such as a
+    /// memory-mapped file.
+    pub fn from_slice(input: &[u8]) -> Result<Self, DecodeError> {
+        Self::from_slice_with_options(input, DecodeOptions::default())
+    }
+
+    /// Reads the entry table of an encoded payload held in memory, decoding it
+    /// with `options` once serialized.
+    pub fn from_slice_with_options(
+        input: &[u8],
+        options: DecodeOptions,
+    ) -> Result<Self, DecodeError> {
+        Self::load(SliceLines::new(input), options)
+    }
+
+    fn load<L: Lines>(lines: L, options: DecodeOptions) -> Result<Self, DecodeError> {
+        // Other dialects are decoded as a whole, then written out
+        let decoder = match options.dialect {
+            Dialect::Native => JSONDecoder::load(lines, options)?,
+            _ => dialect::decode(lines, options)?,
+        };
+        decoder.root()?;
+
+        Ok(StreamingDecoder {
+            decoder,
+            error: Mutex::new(None),
+        })
+    }
+
+    /// Takes the decoding error that made the last serialization fail.
+    ///
+    /// Serializers only keep the message of an error; this returns the
+    /// [`DecodeError`] itself, located by path, entry and input line like the
+    /// errors of [`JSONDecoder`].
+    pub fn take_error(&self) -> Option<DecodeError> {
+        self.error
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take()
+    }
+
+    // Keeps `error` for `take_error` and hands its message to the serializer
+    fn fail<E: ser::Error>(&self, error: DecodeError) -> E {
+        let message = match &error {
+            DecodeError::Located { source, .. } => format!("{error}: {}", source.innermost()),
+            error => error.to_string(),
+        };
+        *self.error.lock().unwrap_or_else(PoisonError::into_inner) = Some(error);
+
+        E::custom(message)
+    }
+}
+
+impl Serialize for StreamingDecoder {
+    #[instrument(name = "stream", skip_all)]
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.decoder.options.dialect != Dialect::Native {
+            let value = &self.decoder.decoded_data;
+
+            return match serializer.is_human_readable() {
+                true => value.serialize(serializer),
+                false => NativeNumbers(value).serialize(serializer),
+            };
+        }
+
+        let fragment = self.decoder.root().map_err(|error| self.fail(error))?;
+
+        Fragment {
+            stream: self,
+            fragment,
+            entry: Some(0),
+            followed: Vec::new(),
+            segment: None,
+            parent: None,
+            depth: 0,
+        }
+        .serialize(serializer)
+    }
+}
+
+/// An entry holding no reference, as written in the input, with the number
+/// of levels it nests.
+pub(crate) struct RawLiteral {
+    raw: Box<RawValue>,
+    depth: usize,
+}
+
+// The entries of `text`, parsed into `entries`, that hold no reference, as
+// written, when `options` asks for them; `None` for the others
+pub(crate) fn raw_literals(
+    text: &str,
+    entries: &[Value],
+    options: &DecodeOptions,
+) -> Vec<Option<RawLiteral>> {
+    // Strict mode rejects strings in arrays while decoding them
+    if !options.raw_literals || options.relaxed || options.strict {
+        return Vec::new();
+    }
+
+    let raws: Vec<&RawValue> = match serde_json::from_str(text.trim()) {
+        Ok(raws) => raws,
+        Err(_) => return Vec::new(),
+    };
+
+    entries
+        .iter()
+        .zip(raws)
+        .map(|(entry, raw)| {
+            literal_depth(entry).map(|depth| RawLiteral {
+                raw: raw.to_owned(),
+                depth,
+            })
+        })
+        .collect()
+}
+
+// Levels `value` nests when it holds no reference: scalars, and arrays of
+// literals holding no number, which would be an index, nor the marker of a
+// promise
+fn literal_depth(value: &Value) -> Option<usize> {
+    match value {
+        Value::Array(arr) => arr.iter().try_fold(0, |depth, item| match item {
+            Value::Number(_) => None,
+            Value::String(s) if s == "P" => None,
+            item => Some(depth.max(literal_depth(item)? + 1)),
+        }),
+        Value::Object(obj) => obj.is_empty().then_some(0),
+        _ => Some(0),
+    }
+}
+
+// A fragment being serialized, linked to the fragments it is nested in
+struct Fragment<'a> {
+    stream: &'a StreamingDecoder,
+    fragment: &'a Value,
+    // Entry the fragment was referenced as, `None` when inline
+    entry: Option<usize>,
+    // Entries followed from the promise it stands for, the last one holding
+    // the fragment
+    followed: Vec<usize>,
+    // Location in the parent fragment
+    segment: Option<PathSegment>,
+    parent: Option<&'a Fragment<'a>>,
+    depth: usize,
+}
+
+impl Fragment<'_> {
+    fn decoder(&self) -> &JSONDecoder {
+        &self.stream.decoder
+    }
+
+    fn entry(&self, index: &Value, segment: PathSegment) -> Result<Fragment<'_>, DecodeError> {
+        if let Some(number) = self.decoder().non_finite(index) {
+            return self.child(number?, None, segment);
+        }
+
+        let index = self.decoder().decode_index(index)?;
+
+        // Entries open from this fragment up to the root
+        let open = || {
+            self.ancestors()
+                .flat_map(|f| f.followed.iter().rev().copied().chain(f.entry))
+        };
+
+        if open().any(|i| i == index) {
+            let mut cycle: Vec<usize> = open().take_while(|&i| i != index).collect();
+            cycle.push(index);
+            cycle.reverse();
+            cycle.push(index);
+
+            return Err(self.decoder().cycle_error(cycle));
+        }
+
+        self.child(&self.decoder().encoded_list[index], Some(index), segment)
+    }
+
+    fn child<'b>(
+        &'b self,
+        fragment: &'b Value,
+        entry: Option<usize>,
+        segment: PathSegment,
+    ) -> Result<Fragment<'b>, DecodeError> {
+        let max_depth = self.decoder().options.max_depth;
+
+        if self.depth >= max_depth {
+            return Err(DecodeError::DepthLimitExceeded {
+                max_depth,
+                path: None,
+            });
+        }
+
+        Ok(Fragment {
+            stream: self.stream,
+            fragment,
+            entry,
+            followed: Vec::new(),
+            segment: Some(segment),
+            parent: Some(self),
+            depth: self.depth + 1,
+        })
+    }
+
+    fn ancestors(&self) -> impl Iterator<Item = &Fragment<'_>> {
+        iter::successors(Some(self), |f| f.parent)
+    }
+
+    fn path(&self, child: Option<PathSegment>) -> Vec<PathSegment> {
+        let mut path: Vec<PathSegment> = child
+            .into_iter()
+            .chain(self.ancestors().filter_map(|f| f.segment.clone()))
+            .collect();
+        path.reverse();
+        path
+    }
+
+    // Adds the path of `child`, or of this fragment, and the innermost entry
+    // to an error
+    fn locate(&self, error: DecodeError, child: Option<PathSegment>) -> DecodeError {
+        let entry = self
+            .ancestors()
+            .find_map(|f| f.followed.last().copied().or(f.entry))
+            .expect("fragments are nested in an entry");
+
+        error.located(
+            format_path(&self.path(child)),
+            entry,
+            self.decoder().entry_line(entry),
+        )
+    }
+
+    // Lenient stand-in for a reference that could not be resolved
+    fn placeholder<E: ser::Error>(
+        &self,
+        error: DecodeError,
+        child: Option<PathSegment>,
+    ) -> Result<Value, E> {
+        self.decoder()
+            .placeholder(self.locate(error, child), || None)
+            .map_err(|error| self.stream.fail(error))
+    }
+}
+
+impl Serialize for Fragment<'_> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        // Only JSON serializers write raw values as they are
+        if let Some(literal) = self
+            .entry
+            .and_then(|i| self.decoder().raw_literals.get(i)?.as_ref())
+            && self.depth + literal.depth <= self.decoder().options.max_depth
+            && serializer.is_human_readable()
+        {
+            return literal.raw.serialize(serializer);
+        }
+
+        let fragment = match self.decoder().follow_promises(self.fragment) {
+            Ok((fragment, followed)) if !followed.is_empty() => {
+                let resolved = Fragment {
+                    stream: self.stream,
+                    fragment,
+                    entry: None,
+                    followed,
+                    segment: None,
+                    parent: Some(self),
+                    depth: self.depth,
+                };
+                return resolved.serialize(serializer);
+            }
+            Ok((fragment, _)) => fragment,
+            Err(error) => return self.placeholder(error, None)?.serialize(serializer),
+        };
+
+        match fragment {
+            Value::Array(arr) => {
+                let mut seq = serializer.serialize_seq(Some(arr.len()))?;
+
+                for (i, item) in arr.iter().enumerate() {
+                    let segment = PathSegment::Index(i);
+                    let child = match item {
+                        Value::Number(_) => self.entry(item, segment.clone()),
+                        f => self.child(f, None, segment.clone()),
+                    };
+
+                    match child {
+                        Ok(child) => seq.serialize_element(&child)?,
+                        Err(error) => {
+                            seq.serialize_element(&self.placeholder(error, Some(segment))?)?
+                        }
+                    }
+                }
+
+                seq.end()
+            }
+            Value::Object(obj) => {
+                let members = self
+                    .decoder()
+                    .object_members(obj, || Some(format_path(&self.path(None))))
+                    .map_err(|error| self.stream.fail(self.locate(error, None)))?;
+                let mut map = serializer.serialize_map(Some(members.len()))?;
+
+                for (key, value) in members {
+                    let segment = PathSegment::Key(String::from(key));
+
+                    match self.entry(value, segment.clone()) {
+                        Ok(child) => map.serialize_entry(key, &child)?,
+                        Err(error) => {
+                            map.serialize_entry(key, &self.placeholder(error, Some(segment))?)?
+                        }
+                    }
+                }
+
+                map.end()
+            }
+            // Binary formats cannot read serde_json's arbitrary precision numbers
+            Value::Number(n) if !serializer.is_human_readable() => serialize_native(n, serializer),
+            v => v.serialize(serializer),
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{DecodeError, DecodeOptions};
+use serde_json::Value;
+use std::io::BufRead;
+
+// Input lines hold compact JSON, so whitespace is only allowed inside strings
+// and in the line terminator
+pub(crate) fn check_line(
+    text: &str,
+    line: usize,
+    options: &DecodeOptions,
+) -> Result<(), DecodeError> {
+    if !options.strict {
+        return Ok(());
+    }
+
+    let content = text.strip_suffix('\n').unwrap_or(text);
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (offset, c) in content.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            c if c.is_whitespace() && !in_string => {
+                return Err(DecodeError::NonCanonical {
+                    line: Some(line),
+                    reason: format!("whitespace {c:?} at byte {offset}"),
+                });
+            }
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+// Continuation lines start with the promise prefix in its case, such as an
+// uppercase `P`
+pub(crate) fn check_prefix(
+    p_index: &str,
+    line: usize,
+    options: &DecodeOptions,
+) -> Result<(), DecodeError> {
+    if !options.strict {
+        return Ok(());
+    }
+
+    let prefix = &options.promise_prefix;
+
+    match p_index.trim_start().get(..prefix.len()) {
+        Some(written) if written != prefix => Err(DecodeError::NonCanonical {
+            line: Some(line),
+            reason: format!("prefix {written:?} instead of {prefix:?}"),
+        }),
+        _ => Ok(()),
+    }
+}
+
+// The entry array is the first line of the input
+pub(crate) fn check_leading(
+    line: usize,
+    what: &str,
+    options: &DecodeOptions,
+) -> Result<(), DecodeError> {
+    match options.strict {
+        true => Err(DecodeError::NonCanonical {
+            line: Some(line),
+            reason: format!("{what} before the entry array"),
+        }),
+        false => Ok(()),
+    }
+}
+
+// The payload ends with an empty line, or at the end of the input
+pub(crate) fn check_end<R: BufRead>(
+    reader: &mut R,
+    text: &str,
+    line: usize,
+    options: &DecodeOptions,
+) -> Result<(), DecodeError> {
+    if !options.strict {
+        return Ok(());
+    }
+
+    if !matches!(text, "" | "\n") {
+        return Err(DecodeError::NonCanonical {
+            line: Some(line),
+            reason: String::from("the terminating line is not empty"),
+        });
+    }
+
+    let mut rest = Vec::new();
+    reader
+        .read_to_end(&mut rest)
+        .map_err(|source| DecodeError::Io {
+            line: line + 1,
+            source,
+        })?;
+
+    match rest.is_empty() {
+        true => Ok(()),
+        false => Err(DecodeError::NonCanonical {
+            line: Some(line + 1),
+            reason: String::from("trailing data after the terminating line"),
+        }),
+    }
+}
+
+// Arrays hold indexes and inline fragments; the only marker is the "P" heading
+// a `["P", index]` promise
+pub(crate) fn check_markers(arr: &[Value]) -> Result<(), DecodeError> {
+    let non_canonical = |reason| DecodeError::NonCanonical { line: None, reason };
+
+    match arr {
+        // Reported as a missing index
+        [marker] if marker == "P" => Ok(()),
+        [marker, Value::Number(_)] if marker == "P" => Ok(()),
+        [marker, index] if marker == "P" => Err(non_canonical(format!(
+            "promise index {index} is not a number"
+        ))),
+        [marker, ..] if marker == "P" => Err(non_canonical(format!(
+            "promise array has {} elements instead of 2",
+            arr.len()
+        ))),
+        _ => match arr.iter().enumerate().find(|(_, item)| item.is_string()) {
+            Some((i, marker)) if marker == "P" => Err(non_canonical(format!(
+                "\"P\" marker at position {i} instead of 0"
+            ))),
+            Some((_, marker)) => Err(non_canonical(format!("unknown marker {marker}"))),
+            None => Ok(()),
+        },
+    }
+}
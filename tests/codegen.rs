@@ -0,0 +1,79 @@
+use serde_json::Value;
+use std::process::{Command, Stdio};
+
+mod large_integers {
+    include!("codegen/large_integers.rs");
+}
+
+mod integers_and_strings {
+    include!("codegen/integers_and_strings.rs");
+}
+
+const LARGE_INTEGERS: &str = include_str!("codegen/large_integers.json");
+const INTEGERS_AND_STRINGS: &str = include_str!("codegen/integers_and_strings.json");
+
+// The types the codegen command writes for a checked-in input
+fn generate(input: &str, name: &str) -> String {
+    let output = Command::new(env!("CARGO_BIN_EXE_json_decoder"))
+        .args(["--passthrough", "codegen", "--name", name])
+        .args(["--input", input])
+        .stdin(Stdio::null())
+        .output()
+        .expect("runs json_decoder");
+
+    assert!(output.status.success(), "{output:?}");
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+// The checked-in types are those the codegen command writes for the input
+#[test]
+fn generates_checked_in_types() {
+    assert_eq!(
+        generate("tests/codegen/large_integers.json", "LargeIntegers"),
+        include_str!("codegen/large_integers.rs")
+    );
+    assert_eq!(
+        generate(
+            "tests/codegen/integers_and_strings.json",
+            "IntegersAndStrings"
+        ),
+        include_str!("codegen/integers_and_strings.rs")
+    );
+}
+
+// Integers beyond `i64`, alone or mixed with negative ones and floats, are
+// read and written back unchanged
+#[test]
+fn round_trips_large_integers() {
+    let decoded: large_integers::LargeIntegers =
+        serde_json::from_str(LARGE_INTEGERS).expect("deserializes");
+
+    assert_eq!(decoded.id, u64::MAX);
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::from_str::<Value>(LARGE_INTEGERS).unwrap()
+    );
+}
+
+// Integers mixed with strings get an integer variant and no float one, which
+// only floats found with them add
+#[test]
+#[cfg_attr(
+    feature = "arbitrary_precision",
+    ignore = "serde_json reads no floats into untagged enums with arbitrary_precision"
+)]
+fn round_trips_integers_and_strings() {
+    use integers_and_strings::{Id, IntegersAndStrings, Ratio, Size};
+
+    let decoded: IntegersAndStrings =
+        serde_json::from_str(INTEGERS_AND_STRINGS).expect("deserializes");
+
+    assert_eq!(decoded.ids[0], Id::Integer(1));
+    assert_eq!(decoded.ids[1], Id::String("two".to_string()));
+    assert_eq!(decoded.sizes[0], Size::Integer(u64::MAX));
+    assert_eq!(decoded.ratios[..2], [Ratio::Integer(1), Ratio::Number(0.5)]);
+    assert_eq!(
+        serde_json::to_value(&decoded).unwrap(),
+        serde_json::from_str::<Value>(INTEGERS_AND_STRINGS).unwrap()
+    );
+}
@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LargeIntegers {
+    pub id: u64,
+    pub offset: i64,
+    pub counts: Vec<i64>,
+    pub mixed: Vec<serde_json::Number>,
+    pub ratios: Vec<serde_json::Number>,
+}
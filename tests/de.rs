@@ -0,0 +1,84 @@
+use json_decoder::{DecodeError, JSONDecoder};
+use serde::{Deserialize, de::DeserializeOwned};
+use serde_json::Value;
+
+fn deserialize<T: DeserializeOwned>(payload: &str) -> Result<T, DecodeError> {
+    json_decoder::from_reader(payload.as_bytes())
+}
+
+// Deserializing reports a reference cycle as decoding does, not as the depth
+// limit it would otherwise run into
+#[test]
+fn reports_reference_cycles() {
+    let payload = "[{\"_1\":2},\"a\",[3],[0]]\n";
+    let error = deserialize::<Value>(payload).unwrap_err();
+
+    assert!(
+        matches!(
+            &error,
+            DecodeError::Located { path, entry: 3, .. } if path == "$.a[0][0]"
+        ),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::ReferenceCycle { cycle, .. } if cycle == &[0, 2, 3, 0]
+    ));
+
+    let decoded = JSONDecoder::from_slice(payload.as_bytes()).err().unwrap();
+    assert_eq!(error.to_string(), decoded.to_string());
+}
+
+// A promise chained back to itself is a cycle too
+#[test]
+fn reports_promise_cycles() {
+    let payload = "[[1],[\"P\",2],[\"P\",1]]\n";
+    let error = deserialize::<Value>(payload).unwrap_err();
+
+    assert!(
+        matches!(error.innermost(), DecodeError::ReferenceCycle { cycle, .. } if cycle == &[2, 1, 2]),
+        "{error:?}"
+    );
+}
+
+#[derive(Debug, Deserialize)]
+#[allow(dead_code)]
+struct User {
+    name: String,
+    age: u32,
+}
+
+// Errors of the target type name the path and the entry of the value
+#[test]
+fn locates_type_errors() {
+    let payload = "[[1],{\"_2\":3,\"_4\":5},\"name\",\"Ada\",\"age\",\"old\"]\n";
+    let error = deserialize::<Vec<User>>(payload).unwrap_err();
+
+    assert!(
+        matches!(
+            &error,
+            DecodeError::Located { path, entry: 5, line: 1, .. } if path == "$[0].age"
+        ),
+        "{error:?}"
+    );
+    assert!(matches!(error.innermost(), DecodeError::Deserialize(_)));
+}
+
+// Broken references are located in the entry holding them
+#[test]
+fn locates_broken_references() {
+    let payload = "[{\"_1\":2},\"a\",[9]]\n";
+    let error = deserialize::<Value>(payload).unwrap_err();
+
+    assert!(
+        matches!(
+            &error,
+            DecodeError::Located { path, entry: 2, .. } if path == "$.a[0]"
+        ),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::IndexOutOfBounds { index: 9, len: 3 }
+    ));
+}
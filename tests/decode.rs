@@ -0,0 +1,404 @@
+use json_decoder::{
+    DecodeError, DecodeOptions, DuplicateKeys, JSONDecoder, NonFinite, Placeholder,
+    StreamingDecoder,
+};
+use serde_json::{Value, json};
+
+fn decode(payload: &str, options: DecodeOptions) -> Result<Value, DecodeError> {
+    JSONDecoder::from_slice_with_options(payload.as_bytes(), options).map(JSONDecoder::into_value)
+}
+
+// The error writing a payload out with the streaming decoder stopped at
+fn stream_error(payload: &str, options: DecodeOptions) -> DecodeError {
+    let decoder = StreamingDecoder::from_slice_with_options(payload.as_bytes(), options)
+        .expect("parses the entry list");
+
+    assert!(serde_json::to_string(&decoder).is_err());
+    decoder.take_error().expect("keeps the error")
+}
+
+fn strict() -> DecodeOptions {
+    DecodeOptions {
+        strict: true,
+        ..DecodeOptions::default()
+    }
+}
+
+// The line and reason of a strict-mode rejection
+fn non_canonical(error: &DecodeError) -> (Option<usize>, &str) {
+    match error.innermost() {
+        DecodeError::NonCanonical { line, reason } => (*line, reason),
+        _ => panic!("not a strict-mode rejection: {error:?}"),
+    }
+}
+
+// The indexes turbo-stream reserves for non-finite numbers are offsets from
+// the end like any other negative index unless asked for
+#[test]
+fn reads_negative_indexes_as_offsets() {
+    let payload = "[[-2,-3,-6,-1],\"a\",\"b\",\"c\",\"d\",\"e\",\"f\"]\n";
+
+    assert_eq!(
+        decode(payload, DecodeOptions::default()).unwrap(),
+        json!(["e", "d", "a", "f"])
+    );
+}
+
+#[test]
+fn reads_non_finite_numbers() {
+    let payload = "[[-2,-3,-6,-1],\"a\",\"b\",\"c\",\"d\",\"e\",\"f\"]\n";
+    let options = |non_finite| DecodeOptions {
+        non_finite: Some(non_finite),
+        ..DecodeOptions::default()
+    };
+
+    assert_eq!(
+        decode(payload, options(NonFinite::String)).unwrap(),
+        json!(["NaN", "-Infinity", "Infinity", "f"])
+    );
+    assert_eq!(
+        decode(payload, options(NonFinite::Null)).unwrap(),
+        json!([null, null, null, "f"])
+    );
+    assert!(matches!(
+        decode(payload, options(NonFinite::Error))
+            .unwrap_err()
+            .innermost(),
+        DecodeError::NonFiniteNumber { number: "NaN" }
+    ));
+}
+
+// The last reference to a primitive entry takes it instead of copying it
+#[test]
+fn moves_primitive_entries_into_the_value() {
+    let payload = "[{\"_1\":2,\"_3\":2},\"a\",\"shared\",\"b\",\"unused\"]\n";
+    let decoder = JSONDecoder::from_slice(payload.as_bytes()).unwrap();
+
+    assert_eq!(
+        decoder.decoded_data(),
+        &json!({ "a": "shared", "b": "shared" })
+    );
+    assert_eq!(
+        decoder.encoded_list(),
+        [
+            json!({ "_1": 2, "_3": 2 }),
+            json!("a"),
+            Value::Null,
+            json!("b"),
+            json!("unused"),
+        ]
+    );
+}
+
+// Both decoders report the entries of a cycle and locate it at the reference
+// closing it, rather than running into the depth limit
+#[test]
+fn reports_reference_cycles() {
+    let payload = "[{\"_1\":2},\"data\",{\"_3\":4},\"items\",[5,6],\"x\",[2]]\n";
+    let errors = [
+        decode(payload, DecodeOptions::default()).unwrap_err(),
+        stream_error(payload, DecodeOptions::default()),
+    ];
+
+    for error in errors {
+        assert!(
+            matches!(
+                &error,
+                DecodeError::Located { path, entry: 6, line: 1, .. }
+                    if path == "$.data.items[1][0]"
+            ),
+            "{error:?}"
+        );
+        assert!(matches!(
+            error.innermost(),
+            DecodeError::ReferenceCycle { cycle, .. } if cycle == &[2, 4, 6, 2]
+        ));
+    }
+}
+
+// A key given twice keeps the position of its first occurrence and the value
+// the policy picks, or fails the object holding it
+#[test]
+fn applies_the_duplicate_key_policy() {
+    let payload = "[{\"_1\":2,\"_5\":6,\"_3\":4},\"a\",1,\"a\",2,\"b\",true]\n";
+    let options = |duplicate_keys| DecodeOptions {
+        duplicate_keys,
+        ..DecodeOptions::default()
+    };
+
+    for (policy, expected) in [
+        (DuplicateKeys::First, r#"{"a":1,"b":true}"#),
+        (DuplicateKeys::Last, r#"{"a":2,"b":true}"#),
+        (DuplicateKeys::Warn, r#"{"a":2,"b":true}"#),
+    ] {
+        let decoded = decode(payload, options(policy)).unwrap();
+        let streamed =
+            StreamingDecoder::from_slice_with_options(payload.as_bytes(), options(policy)).unwrap();
+
+        assert_eq!(
+            serde_json::to_string(&decoded).unwrap(),
+            expected,
+            "{policy:?}"
+        );
+        assert_eq!(
+            serde_json::to_string(&streamed).unwrap(),
+            expected,
+            "{policy:?}"
+        );
+    }
+
+    let errors = [
+        decode(payload, options(DuplicateKeys::Error)).unwrap_err(),
+        stream_error(payload, options(DuplicateKeys::Error)),
+    ];
+
+    for error in errors {
+        assert!(
+            matches!(&error, DecodeError::Located { path, entry: 0, .. } if path == "$"),
+            "{error:?}"
+        );
+        assert!(matches!(
+            error.innermost(),
+            DecodeError::DuplicateKey { key, .. } if key == "a"
+        ));
+    }
+}
+
+// Lenient decoding substitutes the placeholder for broken references and
+// keeps the keys that do not resolve as written
+#[test]
+fn substitutes_placeholders_when_lenient() {
+    let payload = "[{\"_1\":9,\"x\":2},\"a\",\"b\"]\n";
+    let options = |placeholder| DecodeOptions {
+        lenient: Some(placeholder),
+        ..DecodeOptions::default()
+    };
+
+    assert_eq!(
+        decode(payload, options(Placeholder::Null)).unwrap(),
+        json!({ "a": null, "x": "b" })
+    );
+    assert_eq!(
+        decode(payload, options(Placeholder::Marker)).unwrap(),
+        json!({
+            "a": { "$error": "Index 9 out of bounds for 3 entries at path $.a in entry 0" },
+            "x": "b"
+        })
+    );
+    // An index past the end and a reference back to the root
+    assert_eq!(
+        decode("[[1,[0]]]\n", options(Placeholder::Null)).unwrap(),
+        json!([null, [null]])
+    );
+}
+
+// Exceeding the depth limit is no broken reference and fails lenient decoding
+#[test]
+fn keeps_the_depth_limit_when_lenient() {
+    let options = DecodeOptions {
+        lenient: Some(Placeholder::Null),
+        max_depth: 2,
+        ..DecodeOptions::default()
+    };
+    let error = decode("[[1],[2],[3],[]]\n", options).unwrap_err();
+
+    assert!(
+        matches!(
+            error.innermost(),
+            DecodeError::DepthLimitExceeded { max_depth: 2, .. }
+        ),
+        "{error:?}"
+    );
+}
+
+// Strict mode rejects what decodes but is not canonical, naming the line when
+// found while reading one and the entry when found while resolving
+#[test]
+fn rejects_non_canonical_input_when_strict() {
+    for (payload, line, reason) in [
+        ("[[1], 2,3]\n", Some(1), "whitespace ' ' at byte 5"),
+        ("[[1],2]\r\n", Some(1), "whitespace '\\r' at byte 7"),
+        (
+            "[[1],2]\n\nP1:[3]\n",
+            Some(3),
+            "trailing data after the terminating line",
+        ),
+    ] {
+        let error = decode(payload, strict()).unwrap_err();
+        assert_eq!(non_canonical(&error), (line, reason), "{payload:?}");
+    }
+
+    for (payload, path, entry, reason) in [
+        ("[[1],[-1],\"x\"]\n", "$[0][0]", 1, "negative index -1"),
+        ("[[\"x\",1],2]\n", "$", 0, "unknown marker \"x\""),
+        (
+            "[[\"P\",1,2],2,3]\n",
+            "$",
+            0,
+            "promise array has 3 elements instead of 2",
+        ),
+    ] {
+        let error = decode(payload, strict()).unwrap_err();
+
+        assert!(
+            matches!(
+                &error,
+                DecodeError::Located { path: at, entry: e, .. } if at == path && *e == entry
+            ),
+            "{error:?}"
+        );
+        assert_eq!(non_canonical(&error), (None, reason), "{payload:?}");
+        assert!(
+            decode(payload, DecodeOptions::default()).is_ok(),
+            "{payload:?}"
+        );
+    }
+}
+
+// Continuation lines may end in CRLF, space their prefix and use a lowercase
+// prefix, which strict mode rejects
+#[test]
+fn reads_continuation_line_variations() {
+    assert_eq!(
+        decode(
+            "[[1],[\"P\",1]]\r\nP1 : [\"a\"]\r\n\r\n",
+            DecodeOptions::default()
+        )
+        .unwrap(),
+        json!(["a"])
+    );
+
+    let lowercase = "[[1],[\"P\",1]]\np1:[5]\n\n";
+    assert_eq!(
+        decode(lowercase, DecodeOptions::default()).unwrap(),
+        json!([5])
+    );
+    assert_eq!(
+        non_canonical(&decode(lowercase, strict()).unwrap_err()),
+        (Some(2), "prefix \"p\" instead of \"P\"")
+    );
+}
+
+// Continuation lines resolve whatever their order, the last of those repeating
+// a P-index winning; strict mode rejects both
+#[test]
+fn reads_continuation_lines_in_any_order() {
+    for (payload, expected, line, reason) in [
+        (
+            "[[1,2],[\"P\",1],[\"P\",2]]\nP2:[\"b\"]\nP1:[\"a\"]\n\n",
+            json!(["a", "b"]),
+            3,
+            "continuation line out of P-index order",
+        ),
+        (
+            "[[1],[\"P\",1]]\nP1:[\"a\"]\nP1:[\"b\"]\n\n",
+            json!(["b"]),
+            3,
+            "P1 already given on line 2",
+        ),
+    ] {
+        assert_eq!(
+            decode(payload, DecodeOptions::default()).unwrap(),
+            expected,
+            "{payload:?}"
+        );
+        assert_eq!(
+            non_canonical(&decode(payload, strict()).unwrap_err()),
+            (Some(line), reason),
+            "{payload:?}"
+        );
+    }
+}
+
+// A continuation line may resolve a promise to another promise, itself
+// resolved by a later line
+#[test]
+fn follows_chained_promises() {
+    let payload = "[[1],[\"P\",1]]\nP1:[[\"P\",0]]\nP2:[\"deep\"]\n\n";
+    let streamed = StreamingDecoder::from_slice(payload.as_bytes()).unwrap();
+
+    assert_eq!(
+        decode(payload, DecodeOptions::default()).unwrap(),
+        json!(["deep"])
+    );
+    assert_eq!(serde_json::to_value(&streamed).unwrap(), json!(["deep"]));
+}
+
+// A promise no continuation line resolves fails at the promise entry
+#[test]
+fn reports_unresolved_promises() {
+    let payload = "[[1],[\"P\",1]]\n\n";
+    let errors = [
+        decode(payload, DecodeOptions::default()).unwrap_err(),
+        stream_error(payload, DecodeOptions::default()),
+    ];
+
+    for error in errors {
+        assert!(
+            matches!(&error, DecodeError::Located { path, entry: 1, .. } if path == "$[0]"),
+            "{error:?}"
+        );
+        assert!(matches!(
+            error.innermost(),
+            DecodeError::UnresolvedPromise { index: 1 }
+        ));
+    }
+}
+
+// The marker of a promise may follow metadata, its index then being the
+// element after it, which continuation lines update in place
+#[test]
+fn reads_promise_markers_after_metadata() {
+    assert_eq!(
+        decode(
+            "[[1],[\"meta\",\"P\",2],\"x\"]\n\n",
+            DecodeOptions::default()
+        )
+        .unwrap(),
+        json!(["x"])
+    );
+    assert_eq!(
+        decode(
+            "[[1],[\"meta\",\"P\",0]]\nP1:[\"x\"]\n\n",
+            DecodeOptions::default()
+        )
+        .unwrap(),
+        json!(["x"])
+    );
+
+    let error = decode("[[1],[\"P\"]]\n\n", DecodeOptions::default()).unwrap_err();
+    assert!(
+        matches!(&error, DecodeError::Located { path, entry: 1, .. } if path == "$[0]"),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::MissingPromiseIndex
+    ));
+}
+
+// Forks of the format name keys and continuation lines with other prefixes,
+// and the default ones are then no longer recognized
+#[test]
+fn reads_custom_prefixes() {
+    let options = || DecodeOptions {
+        key_prefix: "$".into(),
+        promise_prefix: "D".into(),
+        ..DecodeOptions::default()
+    };
+
+    assert_eq!(
+        decode("[{\"$1\":2},\"a\",[\"P\",0]]\nD2:[\"y\"]\n\n", options()).unwrap(),
+        json!({ "a": "y" })
+    );
+
+    let error = decode("[{\"_1\":2},\"a\",\"b\"]\n", options()).unwrap_err();
+    assert!(
+        matches!(&error, DecodeError::Located { path, entry: 0, .. } if path == "$"),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::InvalidKey { key } if key == "_1"
+    ));
+}
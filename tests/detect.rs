@@ -0,0 +1,27 @@
+use json_decoder::{DecodeOptions, Dialect};
+
+fn detect(payload: &str) -> Dialect {
+    Dialect::detect(payload.as_bytes(), &DecodeOptions::default()).expect("one dialect")
+}
+
+// Data that merely holds integers has none of the structure of devalue
+#[test]
+fn takes_plain_integers_for_native() {
+    assert_eq!(detect(r#"[{"id":5,"count":100}]"#), Dialect::Native);
+    assert_eq!(detect(r#"[{"a":1},[2]]"#), Dialect::Native);
+    assert_eq!(detect(r#"[{"_1":2,"count":5},"a",3]"#), Dialect::Native);
+}
+
+#[test]
+fn tells_dialects_from_their_references() {
+    assert_eq!(detect(r#"[{"_1":2},"a","b"]"#), Dialect::Native);
+    assert_eq!(
+        detect(r#"[{"a":1,"b":2},"x",[3,4],-1,-3,true]"#),
+        Dialect::Devalue
+    );
+    assert_eq!(detect(r#"[["Reactive",1],{"a":2},"x"]"#), Dialect::Nuxt);
+    assert_eq!(
+        detect(r#"[{"a":"1","b":"2"},"x",["1","3"],{}]"#),
+        Dialect::Flatted
+    );
+}
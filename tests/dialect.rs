@@ -0,0 +1,125 @@
+use json_decoder::{DecodeError, DecodeOptions, Dialect, JSONDecoder, NonFinite};
+use serde_json::{Value, json};
+
+fn decode(dialect: Dialect, payload: &str) -> Result<Value, DecodeError> {
+    let options = DecodeOptions {
+        dialect,
+        ..DecodeOptions::default()
+    };
+
+    decode_with_options(payload, options)
+}
+
+fn decode_with_options(payload: &str, options: DecodeOptions) -> Result<Value, DecodeError> {
+    JSONDecoder::from_slice_with_options(payload.as_bytes(), options).map(JSONDecoder::into_value)
+}
+
+// What devalue writes for SvelteKit's `__data.json`: typed values, and the
+// negative indexes it reserves for values JSON lacks
+#[test]
+fn decodes_devalue_payloads() {
+    let payload = r#"[{"user":1,"tags":4,"missing":-1,"ratio":-3,"scores":5},{"name":2,"joined":3},"Ada",["Date","2024-01-01T00:00:00.000Z"],["Set",2],[-2,-4,-5,-6]]"#;
+
+    assert_eq!(
+        decode(Dialect::Devalue, payload).unwrap(),
+        json!({
+            "user": { "name": "Ada", "joined": "2024-01-01T00:00:00.000Z" },
+            "tags": ["Ada"],
+            "ratio": null,
+            "scores": [null, null, null, 0]
+        })
+    );
+
+    let options = DecodeOptions {
+        dialect: Dialect::Devalue,
+        non_finite: Some(NonFinite::String),
+        ..DecodeOptions::default()
+    };
+    assert_eq!(
+        decode_with_options(payload, options).unwrap()["scores"],
+        json!([null, "Infinity", "-Infinity", 0])
+    );
+}
+
+// A `__NUXT_DATA__` payload: Vue's reactive wrappers hold their value as is,
+// and refs the server left empty hold theirs as a string
+#[test]
+fn decodes_nuxt_payloads() {
+    let payload = r#"[["ShallowReactive",1],{"data":2,"state":5},["Reactive",3],{"page":4},"home",{"count":6,"user":8},["EmptyRef",7],"_",["EmptyRef",9],"{\"id\":1}"]"#;
+
+    assert_eq!(
+        decode(Dialect::Nuxt, payload).unwrap(),
+        json!({
+            "data": { "page": "home" },
+            "state": { "count": null, "user": { "id": 1 } }
+        })
+    );
+}
+
+// flatted's `stringify` output: shared values are one entry referenced by
+// index strings, while the circular references it exists for are cycles here
+#[test]
+fn decodes_flatted_payloads() {
+    let payload = r#"[{"author":"1","editor":"1","tags":"3","draft":false},{"name":"2"},"Ada",["2","4"],"admin"]"#;
+
+    assert_eq!(
+        decode(Dialect::Flatted, payload).unwrap(),
+        json!({
+            "author": { "name": "Ada" },
+            "editor": { "name": "Ada" },
+            "tags": ["Ada", "admin"],
+            "draft": false
+        })
+    );
+
+    let error = decode(Dialect::Flatted, r#"[{"name":"1","self":"0"},"Ada"]"#).unwrap_err();
+    assert!(
+        matches!(&error, DecodeError::Located { path, entry: 0, .. } if path == "$.self"),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::ReferenceCycle { cycle, .. } if cycle == &[0, 0]
+    ));
+}
+
+// A Next.js RSC response: hint rows are skipped, and `$` strings reference
+// other chunks, a part of one, or stand for other values
+#[test]
+fn decodes_flight_streams() {
+    let payload = concat!(
+        "1:I[\"app/page.js\",[\"static/chunks/page.js\"],\"default\"]\n",
+        "2:HL[\"/style.css\",\"style\"]\n",
+        "0:[\"$\",\"$L1\",null,{\"user\":\"$3\",\"name\":\"$3:name\",\"bio\":\"$4\",",
+        "\"note\":\"$$5 off\",\"since\":\"$D2024-01-01\",\"visits\":\"$n12\",\"extra\":\"$undefined\"}]\n",
+        "3:{\"name\":\"Ada\"}\n",
+        "4:T5,hello\n",
+    );
+
+    assert_eq!(
+        decode(Dialect::Flight, payload).unwrap(),
+        json!([
+            "$",
+            ["app/page.js", ["static/chunks/page.js"], "default"],
+            null,
+            {
+                "user": { "name": "Ada" },
+                "name": "Ada",
+                "bio": "hello",
+                "note": "$5 off",
+                "since": "2024-01-01",
+                "visits": 12
+            }
+        ])
+    );
+
+    let error = decode(Dialect::Flight, "0:[\"$3\"]\n").unwrap_err();
+    assert!(
+        matches!(&error, DecodeError::Located { path, entry: 0, .. } if path == "$[0]"),
+        "{error:?}"
+    );
+    assert!(matches!(
+        error.innermost(),
+        DecodeError::MissingChunk { id } if id == "3"
+    ));
+}
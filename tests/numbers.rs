@@ -0,0 +1,25 @@
+use json_decoder::{DecodeOptions, JSONDecoder};
+
+fn decode(payload: &[u8], arbitrary_precision: bool) -> String {
+    let options = DecodeOptions {
+        arbitrary_precision,
+        ..DecodeOptions::default()
+    };
+    let decoder = JSONDecoder::from_reader_with_options(payload, options).unwrap();
+
+    serde_json::to_string(&decoder.into_value()).unwrap()
+}
+
+// `-0` is read as the float it is, and kept as written with arbitrary
+// precision, which the option alone does not enable without the feature
+#[test]
+fn keeps_negative_zero() {
+    let payload = b"[[1, 2, 3, 4], -0, -0.0, \"-0\", {\"_3\": 1}]\n\n";
+
+    assert_eq!(decode(payload, false), r#"[-0.0,-0.0,"-0",{"-0":-0.0}]"#);
+
+    #[cfg(feature = "arbitrary_precision")]
+    assert_eq!(decode(payload, true), r#"[-0,-0.0,"-0",{"-0":-0}]"#);
+    #[cfg(not(feature = "arbitrary_precision"))]
+    assert_eq!(decode(payload, true), r#"[-0.0,-0.0,"-0",{"-0":-0.0}]"#);
+}
@@ -0,0 +1,97 @@
+use json_decoder::{DecodeOptions, JSONDecoder, JSONEncoder};
+use proptest::prelude::*;
+use serde_json::{Map, Value};
+
+// Arbitrary JSON values, with few distinct scalars so subtrees get shared
+fn value() -> impl Strategy<Value = Value> {
+    let leaf = prop_oneof![
+        Just(Value::Null),
+        any::<bool>().prop_map(Value::from),
+        (-3i64..3).prop_map(Value::from),
+        any::<i64>().prop_map(Value::from),
+        any::<u64>().prop_map(Value::from),
+        any::<f64>()
+            .prop_filter("JSON has no NaN or infinity", |f| f.is_finite())
+            .prop_map(Value::from),
+        prop::sample::select(vec!["", "P", "_0", "a"]).prop_map(Value::from),
+        any::<String>().prop_map(Value::from),
+    ];
+
+    leaf.prop_recursive(6, 64, 6, |inner| {
+        prop_oneof![
+            prop::collection::vec(inner.clone(), 0..6).prop_map(Value::Array),
+            prop::collection::vec((key(), inner), 0..6)
+                .prop_map(|members| Value::Object(members.into_iter().collect::<Map<_, _>>())),
+        ]
+    })
+}
+
+fn key() -> impl Strategy<Value = String> {
+    prop_oneof![
+        prop::sample::select(vec!["", "a", "P", "_1", "~/"]).prop_map(String::from),
+        any::<String>(),
+    ]
+}
+
+// JSON Pointers of every value below the root
+fn pointers(value: &Value, path: &str, found: &mut Vec<String>) {
+    let children: Vec<(String, &Value)> = match value {
+        Value::Array(arr) => arr
+            .iter()
+            .enumerate()
+            .map(|(i, item)| (i.to_string(), item))
+            .collect(),
+        Value::Object(obj) => obj
+            .iter()
+            .map(|(key, item)| (key.replace('~', "~0").replace('/', "~1"), item))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    for (token, child) in children {
+        let pointer = format!("{path}/{token}");
+        pointers(child, &pointer, found);
+        found.push(pointer);
+    }
+}
+
+// Decodes the payload written by `encoder` in strict mode, which also checks
+// that the encoder writes canonical payloads
+fn round_trip(encoder: &JSONEncoder) -> Result<Value, TestCaseError> {
+    let mut payload = Vec::new();
+    encoder
+        .write_to(&mut payload)
+        .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+    let options = DecodeOptions {
+        strict: true,
+        ..DecodeOptions::default()
+    };
+    let decoder = JSONDecoder::from_reader_with_options(&payload[..], options).map_err(|e| {
+        TestCaseError::fail(format!("{e} in {}", String::from_utf8_lossy(&payload)))
+    })?;
+
+    Ok(decoder.into_value())
+}
+
+proptest! {
+    #[test]
+    fn decodes_what_was_encoded(value in value()) {
+        let encoder = JSONEncoder::from_value(&value).unwrap();
+        prop_assert_eq!(round_trip(&encoder)?, value);
+    }
+
+    #[test]
+    fn decodes_deferred_values(value in value(), picks in prop::collection::vec(any::<prop::sample::Index>(), 0..4)) {
+        let mut candidates = Vec::new();
+        pointers(&value, "", &mut candidates);
+        prop_assume!(!candidates.is_empty());
+
+        let mut defer: Vec<&str> = picks.iter().map(|pick| pick.get(&candidates).as_str()).collect();
+        defer.sort_unstable();
+        defer.dedup();
+
+        let encoder = JSONEncoder::with_deferred(&value, &defer).unwrap();
+        prop_assert_eq!(round_trip(&encoder)?, value);
+    }
+}
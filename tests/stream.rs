@@ -0,0 +1,29 @@
+use json_decoder::{DecodeOptions, StreamingDecoder};
+
+const PAYLOAD: &str = "[[1,2,3],\"a\\u0041\",[\"x\", true],1e2]\n";
+
+fn streamed(options: DecodeOptions) -> String {
+    let decoder = StreamingDecoder::from_slice_with_options(PAYLOAD.as_bytes(), options)
+        .expect("loads the payload");
+
+    serde_json::to_string(&decoder).expect("decodes the payload")
+}
+
+#[test]
+fn writes_parsed_literals_by_default() {
+    assert_eq!(
+        streamed(DecodeOptions::default()),
+        r#"["aA",["x",true],100.0]"#
+    );
+}
+
+// Entries without references are copied from the input as written
+#[test]
+fn copies_raw_literals() {
+    let options = DecodeOptions {
+        raw_literals: true,
+        ..DecodeOptions::default()
+    };
+
+    assert_eq!(streamed(options), r#"["a\u0041",["x", true],1e2]"#);
+}